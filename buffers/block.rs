@@ -21,13 +21,13 @@ pub trait Block {
         let mut count = Self::Item::EQUILIBRIUM;
 
         for s in self.as_slice() {
-            total += *s;
+            total += *s * *s;
             count += Self::Item::from_f32(1.0);
         }
 
         total = total / count;
 
-        return total;
+        return total.sqrt();
     }
 
     fn apply<F: Fn(Self::Item) -> Self::Item>(&mut self, f: F) where Self::Item: Copy {
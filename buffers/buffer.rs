@@ -3,7 +3,6 @@ use std::ops::{Add, Mul, Sub, Div, Index, IndexMut, AddAssign, SubAssign, MulAss
 
 use crate::{event::*, Stereo};
 
-use crate::float::sample::*;
 use crate::buffers::block::*;
 
 pub type AudioBuffer = Buffer<f32>;
@@ -168,47 +167,3 @@ impl<'a, T: Copy + Clone> IntoIterator for &'a mut Buffer<T> {
         self.as_slice_mut().into_iter()
     }
 }
-
-/* Ring buffer - here temporarily until private types are unneeded */
-
-pub struct RingBuffer<S> {
-    buffer: Buffer<S>,
-    length: usize,
-    index: usize
-}
-
-impl<S: Sample> RingBuffer<S> {
-    pub fn init(value: S, size: usize) -> Self {
-        Self {
-            buffer: Buffer::init(value, size),
-            length: 1,
-            index: 0
-        }
-    }
-
-    pub fn len(&self) -> usize {
-        self.length
-    }
-
-    pub fn capacity(&self) -> usize {
-        self.buffer.capacity()
-    }
-
-    pub fn resize(&mut self, length: usize) {
-        if length > self.buffer.capacity() {
-            self.buffer.items.resize(length, S::EQUILIBRIUM);
-        } else {
-            self.length = length;
-            for sample in self.buffer.as_slice_mut().iter_mut().skip(length) {
-                *sample = S::EQUILIBRIUM;
-            }
-        }
-    }
-
-    pub fn next(&mut self, input: S) -> S {
-        let output = self.buffer.items[self.index];
-        self.buffer.items[self.index] = input;
-        self.index = (self.index + 1) % self.length;
-        output
-    }
-}
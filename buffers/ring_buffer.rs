@@ -0,0 +1,80 @@
+use crate::float::*;
+use crate::buffers::buffer::*;
+use crate::buffers::block::*;
+
+/// Circular delay line of `len` samples within a larger allocation
+pub struct RingBuffer<S> {
+    buffer: Buffer<S>,
+    length: usize,
+    index: usize
+}
+
+impl<S: Sample> RingBuffer<S> {
+    pub fn init(value: S, size: usize) -> Self {
+        Self {
+            buffer: Buffer::init(value, usize::max(size, 1)),
+            length: 1,
+            index: 0
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Change the delay length, allocating only if it's larger than the capacity
+    pub fn resize(&mut self, length: usize) {
+        let length = usize::max(length, 1);
+
+        if length > self.capacity() {
+            let mut buffer = Buffer::init(S::EQUILIBRIUM, length);
+            buffer.as_slice_mut()[..self.capacity()].copy_from_slice(self.buffer.as_slice());
+            self.buffer = buffer;
+        } else {
+            for sample in self.buffer.as_slice_mut().iter_mut().skip(length) {
+                *sample = S::EQUILIBRIUM;
+            }
+        }
+
+        self.length = length;
+        self.index %= length;
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.as_slice_mut().fill(S::EQUILIBRIUM);
+        self.index = 0;
+    }
+
+    /// Write `input` and return the sample written `len` samples ago
+    pub fn next(&mut self, input: S) -> S {
+        let output = self.buffer.as_slice()[self.index];
+        self.push(input);
+        output
+    }
+
+    pub fn push(&mut self, input: S) {
+        self.buffer.as_slice_mut()[self.index] = input;
+        self.index = (self.index + 1) % self.length;
+    }
+
+    /// Sample written `delay` samples ago, from 1 for the latest to `len`
+    pub fn get(&self, delay: usize) -> S {
+        let delay = delay.clamp(1, self.length);
+        self.buffer.as_slice()[(self.index + self.length - delay) % self.length]
+    }
+
+    /// Linearly interpolated sample from a fractional `delay` between 1 and `len`
+    pub fn read(&self, delay: f32) -> S {
+        let delay = f32::clamp(delay, 1.0, self.length as f32);
+        let whole = delay.floor();
+        let fraction = S::Float::from(delay - whole);
+
+        let a = self.get(whole as usize);
+        let b = self.get(whole as usize + 1);
+        a + (b - a) * fraction
+    }
+}
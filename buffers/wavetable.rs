@@ -1,10 +1,12 @@
-use std::{io::BufReader, fs::File};
+use crate::Loadable;
+use crate::float::*;
+use crate::fft::*;
+use crate::sample::riff::*;
+use crate::sample::pitch::*;
+use crate::sample::sample::*;
 
-use crate::{Float, Loadable};
-
-fn temp() {
-    let wavetable = Wavetable::<f32, 2048>::new();
-}
+/* Frame size assumed when a file gives no hint and isn't a multiple of the table size */
+const DEFAULT_FRAME_SIZE: usize = 2048;
 
 pub struct Wavetable<F: Float, const C: usize> {
     pub table: Vec<[F; C]>
@@ -20,6 +22,216 @@ impl<F: Float, const C: usize> Wavetable<F, C> {
     pub const fn len(&self) -> usize {
         C
     }
+
+    /// Number of frames
+    pub fn frames(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Write the table as a mono WAV file at `sample_rate` with a `clm ` chunk giving the frame size
+    ///
+    /// 32 bit files are written as floats and 8, 16 and 24 bit files as integers.
+    pub fn save(&self, path: &str, sample_rate: u32, bits_per_sample: u16) -> Result<(), String> {
+        let sample_format = match bits_per_sample {
+            32 => hound::SampleFormat::Float,
+            8 | 16 | 24 => hound::SampleFormat::Int,
+            _ => return Err(format!("Unsupported wavetable bit depth {}", bits_per_sample))
+        };
+
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec).map_err(| e | e.to_string())?;
+
+        for frame in &self.table {
+            for s in frame {
+                let s = f32::clamp(s.to_f32(), -1.0, 1.0);
+
+                match sample_format {
+                    hound::SampleFormat::Float => writer.write_sample(s),
+                    hound::SampleFormat::Int => {
+                        let max = ((1_i32 << (bits_per_sample - 1)) - 1) as f32;
+                        writer.write_sample(f32::round(s * max) as i32)
+                    }
+                }.map_err(| e | e.to_string())?;
+            }
+        }
+
+        writer.finalize().map_err(| e | e.to_string())?;
+
+        append_chunks(path, &[Chunk {
+            id: *b"clm ",
+            data: format!("<!>{} 00000000 wavetable", C).into_bytes(),
+        }])
+    }
+}
+
+/// Amplitude and phase of one harmonic
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct Harmonic {
+    pub amplitude: f32,
+    /// Phase offset in radians, zero is a sine starting at the cycle start
+    pub phase: f32,
+}
+
+impl Harmonic {
+    pub fn new(amplitude: f32, phase: f32) -> Self {
+        Self { amplitude, phase }
+    }
+}
+
+impl<F: Float, const C: usize> Wavetable<F, C> {
+    /// Frames from harmonic spectra, where element `k` of a spectrum is harmonic `k + 1`
+    ///
+    /// Harmonics at or above `C / 2` are dropped. Frames aren't normalized.
+    pub fn from_harmonics<H: AsRef<[Harmonic]>>(spectra: &[H]) -> Self {
+        let mut inverse = RealFft::<f64>::new(C);
+        let mut bins = vec![Complex::new(); inverse.bins()];
+        let mut output = vec![0.0; C];
+
+        /* A real sine of amplitude `a` is `N a / 2` in a single bin */
+        let scale = C as f64 / 2.0;
+
+        let table: Vec<[F; C]> = spectra
+            .iter()
+            .map(| spectrum | {
+                bins.fill(Complex::new());
+
+                for (k, harmonic) in spectrum.as_ref().iter().enumerate() {
+                    let bin = k + 1;
+                    if 2 * bin >= C {
+                        break;
+                    }
+
+                    let phase = harmonic.phase as f64 - std::f64::consts::FRAC_PI_2;
+                    bins[bin] = Complex::from_polar(harmonic.amplitude as f64 * scale, phase);
+                }
+
+                inverse.inverse(bins.as_slice(), output.as_mut_slice());
+
+                let mut frame = [F::ZERO; C];
+                for (dest, s) in frame.iter_mut().zip(&output) {
+                    *dest = F::from_f64(*s);
+                }
+
+                frame
+            })
+            .collect();
+
+        Wavetable::from(table)
+    }
+
+    /// Frames sampled from `f(phase, frame)`, with phase from 0 to 1
+    ///
+    /// The function is sampled directly, so it should be smooth or the table
+    /// band limited afterwards by the oscillator's mip levels.
+    pub fn from_fn<Function: Fn(f32, usize) -> f32>(frames: usize, f: Function) -> Self {
+        let table: Vec<[F; C]> = (0..frames)
+            .map(| index | {
+                let mut frame = [F::ZERO; C];
+                for (i, dest) in frame.iter_mut().enumerate() {
+                    *dest = F::from(f(i as f32 / C as f32, index));
+                }
+
+                frame
+            })
+            .collect();
+
+        Wavetable::from(table)
+    }
+
+    /// Frames resynthesized from single cycles of a pitched sample
+    ///
+    /// Uses the sample pitch if it's set, otherwise detects it. Cycles are
+    /// taken from `frames` evenly spaced points between the sample start and
+    /// end, each starting at a rising zero crossing, then resampled to `C`
+    /// points, with DC removed and the table normalized to a peak of 1.
+    pub fn from_sample<S: Sample>(sample: &SampleFile<S>, sample_rate: u32, frames: usize) -> Result<Self, String> {
+        let end = usize::min(sample.end, sample.len());
+        let start = usize::min(sample.start, end);
+        let samples: Vec<f32> = sample.as_slice()[start..end]
+            .iter()
+            .map(| s | s.mono().to_f32())
+            .collect();
+
+        let rate = sample_rate as f32;
+        let pitch = match sample.pitch {
+            Some(hz) => hz,
+            None => detect_pitch(&samples, sample_rate, 20.0, 4000.0)
+                .ok_or_else(|| String::from("Couldn't detect the sample pitch"))?
+        };
+
+        let period = rate / pitch;
+        if frames == 0 || !period.is_finite() || period < 2.0 {
+            return Err(format!("Can't slice cycles at {} Hz", pitch));
+        }
+
+        /* Keep two periods of headroom for local refinement and the zero crossing search */
+        let usable = samples.len() as f32 - period * 3.0;
+        if usable < 0.0 {
+            return Err(String::from("Sample is shorter than three cycles"));
+        }
+
+        /* Oversample each cycle so interpolation never aliases, then band limit to `C` */
+        let points = usize::max(C, period.ceil() as usize);
+        let mut cycles = Vec::with_capacity(frames * points);
+
+        for index in 0..frames {
+            let position = if frames > 1 {
+                usable * index as f32 / (frames - 1) as f32
+            } else {
+                usable / 2.0
+            };
+
+            let at = position as usize;
+            let window = &samples[at..usize::min(at + (period * 3.0) as usize, samples.len())];
+            let period = yin(window, period * 0.9, period * 1.1).unwrap_or(period);
+            let start = rising_zero_crossing(&samples, position, period);
+
+            for i in 0..points {
+                cycles.push(interpolate(&samples, start + period * i as f32 / points as f32));
+            }
+        }
+
+        let mut table = resample_frames::<F, C>(&cycles, points);
+        normalize_frames(&mut table);
+
+        Ok(Wavetable::from(table))
+    }
+}
+
+/// Fractional position of the first rising zero crossing within a period after `position`
+fn rising_zero_crossing(samples: &[f32], position: f32, period: f32) -> f32 {
+    let start = position as usize;
+    let end = usize::min(start + period.ceil() as usize, samples.len() - 1);
+
+    for i in start..end {
+        let (a, b) = (samples[i], samples[i + 1]);
+        if a <= 0.0 && b > 0.0 {
+            return i as f32 + a / (a - b);
+        }
+    }
+
+    position
+}
+
+/// Catmull-Rom interpolation at a fractional index
+fn interpolate(samples: &[f32], position: f32) -> f32 {
+    let last = samples.len() as isize - 1;
+    let index = position.floor();
+    let t = position - index;
+    let at = | offset: isize | samples[isize::clamp(index as isize + offset, 0, last) as usize];
+
+    let (y0, y1, y2, y3) = (at(-1), at(0), at(1), at(2));
+    let c1 = 0.5 * (y2 - y0);
+    let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+    let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+
+    ((c3 * t + c2) * t + c1) * t + y1
 }
 
 impl<F: Float, const C: usize> From<Vec<[F; C]>> for Wavetable<F, C> {
@@ -32,76 +244,175 @@ impl<F: Float, const C: usize> From<Vec<[F; C]>> for Wavetable<F, C> {
 
 impl<F: Float, const C: usize> Loadable for Wavetable<F, C> {
     fn load(path: &str) -> Result<Self, String> where Self: Sized {
-        match hound::WavReader::open(path.to_string()) {
-            Ok(reader) => {
-                return load_wavetable(path, reader)
-            }
-            Err(e) => {
-                Err(e.to_string())
-            }
-        }
+        load_wavetable(path, WavetableHints::default())
     }
 }
 
-fn load_wavetable<F: Float, const C: usize>(path: &str, mut reader: hound::WavReader<BufReader<File>>) -> Result<Wavetable<F, C>, String> {
-    let mut wavetable = Wavetable::<F, C>::new();
-    let length = wavetable.len();
+/// Layout hints for wavetable files without a `clm ` chunk
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct WavetableHints {
+    /// Samples per frame, takes priority over the file
+    pub frame_size: Option<usize>,
+    /// Number of frames in the file, used when the frame size is unknown
+    pub frames: Option<usize>,
+}
 
+/// Load a wavetable from a WAV file of any channel count, bit depth and frame count
+///
+/// The frame size is taken from the hints, then from a Serum style `clm `
+/// chunk, then guessed from the file length, and must divide the file into
+/// whole frames of at least two samples. Frames of a different size are resampled to `C`, channels
+/// are mixed to mono, each frame has its DC removed and the whole table is
+/// normalized to a peak of 1.
+pub fn load_wavetable<F: Float, const C: usize>(path: &str, hints: WavetableHints) -> Result<Wavetable<F, C>, String> {
+    let mut reader = hound::WavReader::open(path).map_err(| e | e.to_string())?;
     let spec = reader.spec();
     let channels = spec.channels as usize;
 
-    if channels != 1 {
-        panic!("Unsupported wavetable channel count");
+    if channels == 0 {
+        return Err(String::from("Wavetable has no channels"));
     }
 
-    println!("{:?}", spec);
-
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => {
+            reader.samples::<f32>()
+                .collect::<Result<_, _>>()
+                .map_err(| e | e.to_string())?
+        },
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>()
+                .map(| v | v.map(| v | v as f32 * scale))
+                .collect::<Result<_, _>>()
+                .map_err(| e | e.to_string())?
+        }
+    };
 
-    if spec.sample_format == hound::SampleFormat::Float {
-        if spec.bits_per_sample == 16 || spec.bits_per_sample == 32 {
-            let size = reader.samples::<f32>().len();
-            let mut i = 0;
+    if !samples.len().is_multiple_of(channels) {
+        return Err(format!("Wavetable of {} samples ends part way through a {} channel frame", samples.len(), channels));
+    }
 
-            println!("Length: {}", size);
+    let mono: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(| frame | frame.iter().sum::<f32>() / channels as f32)
+        .collect();
 
-            reader.samples::<f32>().for_each(| v | {
-                let v = f32::from(v.unwrap());
-                wavetable.table[i / length][length * i / size] = F::from(v);
-                i += 1;
-            });
+    if mono.is_empty() {
+        return Err(String::from("Wavetable is empty"));
+    }
 
-            return Ok(wavetable);
+    let frame_size = match hints.frame_size {
+        Some(size) => size,
+        None => match read_clm_frame_size(path)? {
+            Some(size) => size,
+            None => guess_frame_size(mono.len(), hints.frames, C)
         }
+    };
 
-        return Err(String::from("Unsupported wavetable bit depth"));
+    /* A single sample has no waveform to resample, and the FFT needs at least two */
+    if frame_size < 2 {
+        return Err(format!("Wavetable frame size of {} is too short, frames need at least 2 samples", frame_size));
+    }
 
-    } else if spec.sample_format == hound::SampleFormat::Int {
-        if spec.bits_per_sample == 16 {
-            let size = reader.samples::<i16>().len();
-            let mut i = 0;
+    if !mono.len().is_multiple_of(frame_size) {
+        return Err(format!("Wavetable of {} samples isn't a whole number of {} sample frames", mono.len(), frame_size));
+    }
 
-            reader.samples::<i16>().for_each(| v | {
-                let v = f32::from(v.unwrap());
-                wavetable.table[i / length][length * i / size] = F::from(v);
-                i += 1;
-            });
+    let mut table = resample_frames::<F, C>(&mono, frame_size);
+    normalize_frames(&mut table);
 
-            return Ok(wavetable);
-        } else if spec.bits_per_sample == 24 || spec.bits_per_sample == 32 {
-            let size = reader.samples::<i32>().len();
-            let mut i = 0;
+    Ok(Wavetable::from(table))
+}
 
-            reader.samples::<i32>().for_each(| v | {
-                let v = v.unwrap() as i32;
-                wavetable.table[i / length][length * i / size] = F::from(v as f32);
-                i += 1;
-            });
+/// Frame size from a Serum style `clm ` chunk such as `<!>2048 01000000 wavetable`
+fn read_clm_frame_size(path: &str) -> Result<Option<usize>, String> {
+    let chunk = read_chunks(path)?
+        .into_iter()
+        .find(| chunk | chunk.is(b"clm "));
 
-            return Ok(wavetable);
-        }
+    Ok(chunk.and_then(| chunk | {
+        let text = String::from_utf8_lossy(&chunk.data).to_string();
+        text.trim_start_matches("<!>")
+            .split(| c: char | !c.is_ascii_digit())
+            .next()
+            .and_then(| size | size.parse().ok())
+            .filter(| size | *size > 0)
+    }))
+}
 
-        return Err(String::from("Unsupported wavetable bit depth"));
+fn guess_frame_size(length: usize, frames: Option<usize>, table_size: usize) -> usize {
+    match frames {
+        Some(frames) if frames > 0 && length.is_multiple_of(frames) => length / frames,
+        _ => {
+            if length.is_multiple_of(table_size) {
+                table_size
+            } else if length.is_multiple_of(DEFAULT_FRAME_SIZE) {
+                DEFAULT_FRAME_SIZE
+            } else {
+                /* A single cycle */
+                length
+            }
+        }
     }
+}
+
+/// Split into frames of `frame_size` and band limit each to `C` samples with DC removed
+///
+/// `samples` must be a whole number of frames.
+fn resample_frames<F: Float, const C: usize>(samples: &[f32], frame_size: usize) -> Vec<[F; C]> {
+    let mut forward = RealFft::<f64>::new(frame_size);
+    let mut inverse = RealFft::<f64>::new(C);
+    let mut spectrum = vec![Complex::new(); forward.bins()];
+    let mut bins = vec![Complex::new(); inverse.bins()];
+    let mut input = vec![0.0; frame_size];
+    let mut output = vec![0.0; C];
+
+    let scale = C as f64 / frame_size as f64;
+    let shared = usize::min(forward.bins(), inverse.bins());
+
+    samples
+        .chunks_exact(frame_size)
+        .map(| frame | {
+            for (dest, s) in input.iter_mut().zip(frame) {
+                *dest = *s as f64;
+            }
+
+            forward.forward(input.as_slice(), spectrum.as_mut_slice());
+
+            bins.fill(Complex::new());
+            for k in 1..shared {
+                bins[k] = spectrum[k] * scale;
+            }
 
-    return Err(String::from("Unsupported sample fomat"));
+            /* A truncated spectrum has no meaningful Nyquist bin */
+            if frame_size > C && C.is_multiple_of(2) {
+                bins[C / 2] = Complex::new();
+            }
+
+            inverse.inverse(bins.as_slice(), output.as_mut_slice());
+
+            let mut dest = [F::ZERO; C];
+            for (d, s) in dest.iter_mut().zip(&output) {
+                *d = F::from_f64(*s);
+            }
+
+            dest
+        })
+        .collect()
+}
+
+fn normalize_frames<F: Float, const C: usize>(table: &mut [[F; C]]) {
+    let peak = table
+        .iter()
+        .flat_map(| frame | frame.iter())
+        .fold(0.0, | peak: f32, s | f32::max(peak, s.to_f32().abs()));
+
+    if peak > 0.0 {
+        let gain = F::from(1.0 / peak);
+        for frame in table.iter_mut() {
+            for s in frame.iter_mut() {
+                *s *= gain;
+            }
+        }
+    }
 }
@@ -0,0 +1,5 @@
+mod plan;
+mod real;
+
+pub use plan::*;
+pub use real::*;
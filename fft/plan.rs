@@ -0,0 +1,371 @@
+use crate::buffers::*;
+use crate::float::*;
+
+/// Radices at or below this size are handled by the mixed-radix butterflies,
+/// anything with a larger prime factor goes through Bluestein's algorithm
+const MAX_RADIX: usize = 31;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Normalization {
+    /// Neither direction is scaled, so a round trip is scaled by `N`
+    None,
+    /// The forward transform is scaled by `1 / N`
+    Forward,
+    /// The inverse transform is scaled by `1 / N`
+    Inverse,
+    /// Both directions are scaled by `1 / sqrt(N)`
+    Unitary,
+}
+
+impl Normalization {
+    pub(crate) fn scale<F: Float>(&self, size: usize, inverse: bool) -> Option<F> {
+        let n = F::from_usize(size);
+
+        match (self, inverse) {
+            (Normalization::Forward, false) => Some(F::from(1.0) / n),
+            (Normalization::Inverse, true) => Some(F::from(1.0) / n),
+            (Normalization::Unitary, _) => Some(F::from(1.0) / n.sqrt()),
+            _ => None
+        }
+    }
+}
+
+/// A reusable complex FFT plan of any size
+///
+/// Sizes that factor into radices 2, 3, 4, 5 and other small primes use a
+/// mixed-radix Cooley-Tukey transform, sizes with large prime factors fall
+/// back to Bluestein's chirp-z algorithm. Twiddles and scratch space are
+/// allocated up front so transforms never allocate.
+pub struct Fft<F: Float> {
+    size: usize,
+    normalization: Normalization,
+    algorithm: Algorithm<F>,
+    scratch: Vec<Complex<F>>,
+}
+
+enum Algorithm<F: Float> {
+    MixedRadix(MixedRadix<F>),
+    Bluestein(Box<Bluestein<F>>),
+}
+
+impl<F: Float> Fft<F> {
+    pub fn new(size: usize) -> Self {
+        Self::with_normalization(size, Normalization::Inverse)
+    }
+
+    pub fn with_normalization(size: usize, normalization: Normalization) -> Self {
+        let size = usize::max(size, 1);
+        let factors = factorize(size);
+
+        let algorithm = if factors.iter().any(| (p, _) | *p > MAX_RADIX) {
+            Algorithm::Bluestein(Box::new(Bluestein::new(size)))
+        } else {
+            Algorithm::MixedRadix(MixedRadix::new(size, factors))
+        };
+
+        Self {
+            size,
+            normalization,
+            algorithm,
+            scratch: vec![Complex::ZERO; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    /// Forward transform in place
+    pub fn forward<B: Block<Item = Complex<F>> + ?Sized>(&mut self, block: &mut B) {
+        self.process_in_place(block.as_slice_mut(), false);
+    }
+
+    /// Inverse transform in place
+    pub fn inverse<B: Block<Item = Complex<F>> + ?Sized>(&mut self, block: &mut B) {
+        self.process_in_place(block.as_slice_mut(), true);
+    }
+
+    /// Forward transform from `input` into `output`
+    pub fn forward_into<I, O>(&mut self, input: &I, output: &mut O)
+        where
+            I: Block<Item = Complex<F>> + ?Sized,
+            O: Block<Item = Complex<F>> + ?Sized {
+
+        self.process(input.as_slice(), output.as_slice_mut(), false);
+    }
+
+    /// Inverse transform from `input` into `output`
+    pub fn inverse_into<I, O>(&mut self, input: &I, output: &mut O)
+        where
+            I: Block<Item = Complex<F>> + ?Sized,
+            O: Block<Item = Complex<F>> + ?Sized {
+
+        self.process(input.as_slice(), output.as_slice_mut(), true);
+    }
+
+    fn process_in_place(&mut self, data: &mut [Complex<F>], inverse: bool) {
+        assert_eq!(data.len(), self.size, "FFT block length doesn't match plan size");
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.copy_from_slice(data);
+        self.execute(&mut scratch, data, inverse);
+        self.scratch = scratch;
+    }
+
+    fn process(&mut self, input: &[Complex<F>], output: &mut [Complex<F>], inverse: bool) {
+        assert_eq!(input.len(), self.size, "FFT input length doesn't match plan size");
+        assert_eq!(output.len(), self.size, "FFT output length doesn't match plan size");
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.copy_from_slice(input);
+        self.execute(&mut scratch, output, inverse);
+        self.scratch = scratch;
+    }
+
+    /// Transform `input` into `output`, `input` is used as working space
+    fn execute(&mut self, input: &mut [Complex<F>], output: &mut [Complex<F>], inverse: bool) {
+        match &mut self.algorithm {
+            Algorithm::MixedRadix(plan) => {
+                /* The inverse is computed as conj(fft(conj(x))) so only one twiddle table is needed */
+                if inverse {
+                    for v in input.iter_mut() {
+                        *v = v.conj();
+                    }
+                }
+
+                plan.transform(input, output);
+
+                if inverse {
+                    for v in output.iter_mut() {
+                        *v = v.conj();
+                    }
+                }
+            },
+            Algorithm::Bluestein(plan) => {
+                plan.transform(input, output, inverse);
+            }
+        }
+
+        if let Some(scale) = self.normalization.scale::<F>(self.size, inverse) {
+            for v in output.iter_mut() {
+                *v *= scale;
+            }
+        }
+    }
+}
+
+/* Mixed radix Cooley-Tukey */
+
+struct MixedRadix<F: Float> {
+    size: usize,
+    factors: Vec<(usize, usize)>,
+    twiddles: Vec<Complex<F>>,
+    butterfly: Vec<Complex<F>>,
+}
+
+impl<F: Float> MixedRadix<F> {
+    fn new(size: usize, factors: Vec<(usize, usize)>) -> Self {
+        let max_radix = factors.iter().map(| (p, _) | *p).max().unwrap_or(1);
+
+        Self {
+            size,
+            factors,
+            twiddles: twiddles(size),
+            butterfly: vec![Complex::ZERO; max_radix],
+        }
+    }
+
+    fn transform(&mut self, input: &[Complex<F>], output: &mut [Complex<F>]) {
+        if self.size == 1 {
+            output[0] = input[0];
+            return;
+        }
+
+        self.work(output, input, 0, 1, 0);
+    }
+
+    /// Decimation in time over the factor list, each level writes `p` sub-transforms of length `m`
+    fn work(&mut self, output: &mut [Complex<F>], input: &[Complex<F>], offset: usize, stride: usize, stage: usize) {
+        let (p, m) = self.factors[stage];
+
+        if m == 1 {
+            for (i, out) in output.iter_mut().take(p).enumerate() {
+                *out = input[offset + i * stride];
+            }
+        } else {
+            for i in 0..p {
+                self.work(&mut output[i * m..(i + 1) * m], input, offset + i * stride, stride * p, stage + 1);
+            }
+        }
+
+        match p {
+            2 => self.butterfly_2(output, stride, m),
+            4 => self.butterfly_4(output, stride, m),
+            _ => self.butterfly_generic(output, stride, m, p),
+        }
+    }
+
+    fn butterfly_2(&self, output: &mut [Complex<F>], stride: usize, m: usize) {
+        let (a, b) = output.split_at_mut(m);
+
+        for k in 0..m {
+            let t = b[k] * self.twiddles[k * stride];
+            b[k] = a[k] - t;
+            a[k] += t;
+        }
+    }
+
+    fn butterfly_4(&self, output: &mut [Complex<F>], stride: usize, m: usize) {
+        for k in 0..m {
+            let s0 = output[k + m] * self.twiddles[k * stride];
+            let s1 = output[k + 2 * m] * self.twiddles[2 * k * stride];
+            let s2 = output[k + 3 * m] * self.twiddles[3 * k * stride];
+
+            let s5 = output[k] - s1;
+            let f0 = output[k] + s1;
+            let s3 = s0 + s2;
+            let s4 = s0 - s2;
+
+            output[k] = f0 + s3;
+            output[k + 2 * m] = f0 - s3;
+            output[k + m] = complex(s5.real + s4.imaginary, s5.imaginary - s4.real);
+            output[k + 3 * m] = complex(s5.real - s4.imaginary, s5.imaginary + s4.real);
+        }
+    }
+
+    fn butterfly_generic(&mut self, output: &mut [Complex<F>], stride: usize, m: usize, p: usize) {
+        let n = self.size;
+
+        for u in 0..m {
+            for q in 0..p {
+                self.butterfly[q] = output[u + q * m];
+            }
+
+            for q1 in 0..p {
+                let k = u + q1 * m;
+                let mut index = 0;
+                let mut sum = self.butterfly[0];
+
+                for q in 1..p {
+                    index = (index + stride * k) % n;
+                    sum += self.butterfly[q] * self.twiddles[index];
+                }
+
+                output[k] = sum;
+            }
+        }
+    }
+}
+
+/* Bluestein chirp-z */
+
+struct Bluestein<F: Float> {
+    size: usize,
+    inner: Fft<F>,
+    chirp: Vec<Complex<F>>,
+    kernel: Vec<Complex<F>>,
+    buffer: Vec<Complex<F>>,
+}
+
+impl<F: Float> Bluestein<F> {
+    fn new(size: usize) -> Self {
+        let length = (2 * size - 1).next_power_of_two();
+        let mut inner = Fft::with_normalization(length, Normalization::Inverse);
+
+        /* w[k] = exp(-i * pi * k^2 / n), with k^2 reduced mod 2n to keep precision */
+        let chirp: Vec<Complex<F>> = (0..size)
+            .map(| k | {
+                let k2 = (k as u128 * k as u128 % (2 * size as u128)) as f64;
+                let phase = -std::f64::consts::PI * k2 / size as f64;
+                complex(F::from_f64(phase.cos()), F::from_f64(phase.sin()))
+            })
+            .collect();
+
+        let mut kernel = vec![Complex::ZERO; length];
+        kernel[0] = chirp[0].conj();
+        for k in 1..size {
+            kernel[k] = chirp[k].conj();
+            kernel[length - k] = chirp[k].conj();
+        }
+
+        inner.forward(kernel.as_mut_slice());
+
+        Self {
+            size,
+            inner,
+            chirp,
+            kernel,
+            buffer: vec![Complex::ZERO; length],
+        }
+    }
+
+    fn transform(&mut self, input: &[Complex<F>], output: &mut [Complex<F>], inverse: bool) {
+        for (i, b) in self.buffer.iter_mut().enumerate() {
+            *b = if i < self.size {
+                let x = if inverse { input[i].conj() } else { input[i] };
+                x * self.chirp[i]
+            } else {
+                Complex::ZERO
+            };
+        }
+
+        self.inner.forward(self.buffer.as_mut_slice());
+
+        for (b, k) in self.buffer.iter_mut().zip(&self.kernel) {
+            *b *= *k;
+        }
+
+        self.inner.inverse(self.buffer.as_mut_slice());
+
+        for (i, out) in output.iter_mut().enumerate() {
+            let x = self.buffer[i] * self.chirp[i];
+            *out = if inverse { x.conj() } else { x };
+        }
+    }
+}
+
+/* Helpers */
+
+/// Forward twiddles `exp(-2 * pi * i * k / n)` computed in double precision
+pub(crate) fn twiddles<F: Float>(n: usize) -> Vec<Complex<F>> {
+    (0..n)
+        .map(| k | {
+            let phase = -2.0 * std::f64::consts::PI * k as f64 / n as f64;
+            complex(F::from_f64(phase.cos()), F::from_f64(phase.sin()))
+        })
+        .collect()
+}
+
+/// Split `n` into `(radix, remaining length)` pairs, preferring radix 4
+fn factorize(n: usize) -> Vec<(usize, usize)> {
+    let mut factors = Vec::new();
+    let mut remaining = n;
+    let mut p = 4;
+
+    while remaining > 1 {
+        while !remaining.is_multiple_of(p) {
+            p = match p {
+                4 => 2,
+                2 => 3,
+                _ => p + 2,
+            };
+
+            if p * p > remaining {
+                p = remaining;
+            }
+        }
+
+        remaining /= p;
+        factors.push((p, remaining));
+    }
+
+    factors
+}
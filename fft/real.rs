@@ -0,0 +1,152 @@
+use crate::buffers::*;
+use crate::float::*;
+use crate::fft::plan::*;
+
+/// A reusable FFT plan for real input
+///
+/// A real signal of length `N` produces `N / 2 + 1` complex bins. Even sizes
+/// are computed with a half length complex transform, odd sizes use a full
+/// length complex transform.
+pub struct RealFft<F: Float> {
+    size: usize,
+    normalization: Normalization,
+    inner: Fft<F>,
+    twiddles: Vec<Complex<F>>,
+    buffer: Vec<Complex<F>>,
+}
+
+impl<F: Float> RealFft<F> {
+    pub fn new(size: usize) -> Self {
+        Self::with_normalization(size, Normalization::Inverse)
+    }
+
+    pub fn with_normalization(size: usize, normalization: Normalization) -> Self {
+        let size = usize::max(size, 2);
+        let inner_size = if size.is_multiple_of(2) { size / 2 } else { size };
+
+        Self {
+            size,
+            normalization,
+            inner: Fft::with_normalization(inner_size, Normalization::None),
+            twiddles: twiddles(size),
+            buffer: vec![Complex::ZERO; inner_size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of complex bins produced by the forward transform
+    pub fn bins(&self) -> usize {
+        self.size / 2 + 1
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    /// Transform `N` real samples into `N / 2 + 1` complex bins
+    pub fn forward<I, O>(&mut self, input: &I, output: &mut O)
+        where
+            I: Block<Item = F> + ?Sized,
+            O: Block<Item = Complex<F>> + ?Sized {
+
+        let input = input.as_slice();
+        let output = output.as_slice_mut();
+
+        assert_eq!(input.len(), self.size, "FFT input length doesn't match plan size");
+        assert_eq!(output.len(), self.bins(), "FFT output length doesn't match bin count");
+
+        if !self.size.is_multiple_of(2) {
+            for (b, x) in self.buffer.iter_mut().zip(input) {
+                *b = complex(*x, F::ZERO);
+            }
+
+            self.inner.forward(self.buffer.as_mut_slice());
+            output.copy_from_slice(&self.buffer[..output.len()]);
+        } else {
+            let half = self.size / 2;
+
+            for (i, b) in self.buffer.iter_mut().enumerate() {
+                *b = complex(input[2 * i], input[2 * i + 1]);
+            }
+
+            self.inner.forward(self.buffer.as_mut_slice());
+
+            /* Untangle the even and odd spectra of the packed signal */
+            let h = F::from(0.5);
+            for (k, out) in output.iter_mut().enumerate() {
+                let a = self.buffer[k % half];
+                let b = self.buffer[(half - k % half) % half].conj();
+                let even = (a + b) * h;
+                let odd = -(a - b).mul_i() * h;
+                *out = even + self.twiddles[k] * odd;
+            }
+        }
+
+        if let Some(scale) = self.normalization.scale::<F>(self.size, false) {
+            for v in output.iter_mut() {
+                *v *= scale;
+            }
+        }
+    }
+
+    /// Transform `N / 2 + 1` complex bins back into `N` real samples
+    pub fn inverse<I, O>(&mut self, input: &I, output: &mut O)
+        where
+            I: Block<Item = Complex<F>> + ?Sized,
+            O: Block<Item = F> + ?Sized {
+
+        let input = input.as_slice();
+        let output = output.as_slice_mut();
+
+        assert_eq!(input.len(), self.bins(), "FFT input length doesn't match bin count");
+        assert_eq!(output.len(), self.size, "FFT output length doesn't match plan size");
+
+        let n = self.size;
+
+        if !n.is_multiple_of(2) {
+            for (k, b) in self.buffer.iter_mut().enumerate() {
+                *b = if k < input.len() { input[k] } else { input[n - k].conj() };
+            }
+
+            self.inner.inverse(self.buffer.as_mut_slice());
+
+            for (out, b) in output.iter_mut().zip(&self.buffer) {
+                *out = b.real;
+            }
+        } else {
+            let half = n / 2;
+
+            /* Rebuild the packed half length spectrum */
+            let h = F::from(0.5);
+            for (k, b) in self.buffer.iter_mut().enumerate() {
+                let x = input[k];
+                let y = input[half - k].conj();
+                let even = (x + y) * h;
+                let odd = (x - y) * self.twiddles[k].conj() * h;
+                *b = even + odd.mul_i();
+            }
+
+            self.inner.inverse(self.buffer.as_mut_slice());
+
+            /* The half length inverse is scaled by N / 2, match the full length convention */
+            let two = F::from(2.0);
+            for (i, b) in self.buffer.iter().enumerate() {
+                output[2 * i] = b.real * two;
+                output[2 * i + 1] = b.imaginary * two;
+            }
+        }
+
+        if let Some(scale) = self.normalization.scale::<F>(n, true) {
+            for v in output.iter_mut() {
+                *v *= scale;
+            }
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use std::f64::consts::PI;
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/* Seconds over which coefficients glide to a new design, long enough to avoid zipper noise */
+const SMOOTHING_TIME: f64 = 0.005;
+
+/// Response of a biquad, from Robert Bristow-Johnson's Audio EQ Cookbook
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BiquadType {
+    Lowpass,
+    Highpass,
+    /// Bandpass with a peak gain of 0 dB
+    Bandpass,
+    Notch,
+    Allpass,
+    /// Bell boosting or cutting by the gain around the frequency
+    Peak,
+    LowShelf,
+    HighShelf,
+}
+
+/// Normalised coefficients of `(b0 + b1 z^-1 + b2 z^-2) / (1 + a1 z^-1 + a2 z^-2)`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BiquadCoefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl BiquadCoefficients {
+    /// Passes the input unchanged
+    pub const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    /// Design a filter, with `gain` in dB used only by the peak and shelves
+    pub fn design(filter_type: BiquadType, frequency: f32, q: f32, gain: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let frequency = f64::clamp(frequency as f64, 1.0, sample_rate * 0.49);
+        let q = f64::max(q as f64, 0.01);
+
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = (f64::sin(w0), f64::cos(w0));
+        let alpha = sin / (2.0 * q);
+        let a = f64::powf(10.0, gain as f64 / 40.0);
+        let shelf = 2.0 * f64::sqrt(a) * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match filter_type {
+            BiquadType::Lowpass => ((1.0 - cos) * 0.5, 1.0 - cos, (1.0 - cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Highpass => ((1.0 + cos) * 0.5, -(1.0 + cos), (1.0 + cos) * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Bandpass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Allpass => (1.0 - alpha, -2.0 * cos, 1.0 + alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadType::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a
+            ),
+            BiquadType::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                (a + 1.0) + (a - 1.0) * cos + shelf,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - shelf
+            ),
+            BiquadType::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                (a + 1.0) - (a - 1.0) * cos + shelf,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - shelf
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// First-order lowpass or highpass from the bilinear transform, for odd-order cascades
+    fn first_order(lowpass: bool, frequency: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let frequency = f64::clamp(frequency as f64, 1.0, sample_rate * 0.49);
+        let k = f64::tan(PI * frequency / sample_rate);
+        let a1 = (k - 1.0) / (k + 1.0);
+
+        if lowpass {
+            let b0 = k / (k + 1.0);
+            Self { b0, b1: b0, b2: 0.0, a1, a2: 0.0 }
+        } else {
+            let b0 = 1.0 / (k + 1.0);
+            Self { b0, b1: -b0, b2: 0.0, a1, a2: 0.0 }
+        }
+    }
+
+    /// Gain at `frequency` as a linear magnitude
+    pub fn magnitude(&self, frequency: f32, sample_rate: u32) -> f32 {
+        let w = 2.0 * PI * frequency as f64 / sample_rate as f64;
+        let (c1, s1) = (f64::cos(w), f64::sin(w));
+        let (c2, s2) = (f64::cos(2.0 * w), f64::sin(2.0 * w));
+
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = -self.b1 * s1 - self.b2 * s2;
+        let den_re = 1.0 + self.a1 * c1 + self.a2 * c2;
+        let den_im = -self.a1 * s1 - self.a2 * s2;
+
+        f64::sqrt((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)) as f32
+    }
+}
+
+/// Coefficients gliding linearly to a target
+///
+/// Stable biquads form a convex region of `(a1, a2)`, so each intermediate
+/// set of coefficients between two stable designs is itself stable.
+#[derive(Copy, Clone)]
+struct Smoothed {
+    current: BiquadCoefficients,
+    target: BiquadCoefficients,
+    remaining: usize,
+}
+
+impl Smoothed {
+    fn from(coefficients: BiquadCoefficients) -> Self {
+        Self {
+            current: coefficients,
+            target: coefficients,
+            remaining: 0,
+        }
+    }
+
+    fn set(&mut self, target: BiquadCoefficients, samples: usize) {
+        self.target = target;
+        self.remaining = samples;
+
+        if samples == 0 {
+            self.current = target;
+        }
+    }
+
+    fn snap(&mut self) {
+        self.current = self.target;
+        self.remaining = 0;
+    }
+
+    fn next(&mut self) -> BiquadCoefficients {
+        if self.remaining > 0 {
+            let t = 1.0 / self.remaining as f64;
+            let (c, g) = (&mut self.current, &self.target);
+
+            c.b0 += (g.b0 - c.b0) * t;
+            c.b1 += (g.b1 - c.b1) * t;
+            c.b2 += (g.b2 - c.b2) * t;
+            c.a1 += (g.a1 - c.a1) * t;
+            c.a2 += (g.a2 - c.a2) * t;
+
+            self.remaining -= 1;
+        }
+
+        self.current
+    }
+}
+
+/// State of one channel in transposed direct form II
+#[derive(Copy, Clone, Default)]
+struct BiquadState {
+    s1: f64,
+    s2: f64,
+}
+
+impl BiquadState {
+    fn process(&mut self, c: &BiquadCoefficients, x: f64) -> f64 {
+        let y = c.b0 * x + self.s1;
+        self.s1 = c.b1 * x - c.a1 * y + self.s2;
+        self.s2 = c.b2 * x - c.a2 * y;
+        y
+    }
+}
+
+/// Second-order IIR filter for any sample type, filtering each channel independently
+///
+/// Runs in transposed direct form II with double precision state, so low
+/// cutoffs stay quiet. Changing a setting glides the coefficients to the new
+/// design over a few milliseconds, so parameters can be swept while running.
+pub struct Biquad<S: Sample> {
+    filter_type: BiquadType,
+    frequency: f32,
+    q: f32,
+    gain: f32,
+
+    sample_rate: u32,
+    coefficients: Smoothed,
+    states: Vec<BiquadState>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> Biquad<S> {
+    pub fn from(filter_type: BiquadType, frequency: f32, q: f32) -> Self {
+        let mut biquad = Self {
+            filter_type,
+            frequency,
+            q,
+            gain: 0.0,
+            sample_rate: 44100,
+            coefficients: Smoothed::from(BiquadCoefficients::IDENTITY),
+            states: vec![BiquadState::default(); S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        biquad.update();
+        biquad.coefficients.snap();
+        biquad
+    }
+
+    pub fn lowpass(frequency: f32, q: f32) -> Self {
+        Self::from(BiquadType::Lowpass, frequency, q)
+    }
+
+    pub fn highpass(frequency: f32, q: f32) -> Self {
+        Self::from(BiquadType::Highpass, frequency, q)
+    }
+
+    pub fn bandpass(frequency: f32, q: f32) -> Self {
+        Self::from(BiquadType::Bandpass, frequency, q)
+    }
+
+    pub fn notch(frequency: f32, q: f32) -> Self {
+        Self::from(BiquadType::Notch, frequency, q)
+    }
+
+    pub fn allpass(frequency: f32, q: f32) -> Self {
+        Self::from(BiquadType::Allpass, frequency, q)
+    }
+
+    pub fn peak(frequency: f32, q: f32, gain: f32) -> Self {
+        let mut biquad = Self::from(BiquadType::Peak, frequency, q);
+        biquad.set_gain(gain);
+        biquad.coefficients.snap();
+        biquad
+    }
+
+    pub fn low_shelf(frequency: f32, q: f32, gain: f32) -> Self {
+        let mut biquad = Self::from(BiquadType::LowShelf, frequency, q);
+        biquad.set_gain(gain);
+        biquad.coefficients.snap();
+        biquad
+    }
+
+    pub fn high_shelf(frequency: f32, q: f32, gain: f32) -> Self {
+        let mut biquad = Self::from(BiquadType::HighShelf, frequency, q);
+        biquad.set_gain(gain);
+        biquad.coefficients.snap();
+        biquad
+    }
+
+    pub fn filter_type(&self) -> BiquadType {
+        self.filter_type
+    }
+
+    pub fn set_type(&mut self, filter_type: BiquadType) {
+        self.filter_type = filter_type;
+        self.update();
+    }
+
+    pub fn set_frequency(&mut self, hz: f32) {
+        self.frequency = hz;
+        self.update();
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.q = q;
+        self.update();
+    }
+
+    /// Gain in dB of the peak and shelves
+    pub fn set_gain(&mut self, db: f32) {
+        self.gain = db;
+        self.update();
+    }
+
+    /// Coefficients the filter is gliding towards
+    pub fn coefficients(&self) -> BiquadCoefficients {
+        self.coefficients.target
+    }
+
+    /// Gain of the current design at `frequency` as a linear magnitude
+    pub fn magnitude(&self, frequency: f32) -> f32 {
+        self.coefficients.target.magnitude(frequency, self.sample_rate)
+    }
+
+    fn update(&mut self) {
+        let coefficients = BiquadCoefficients::design(self.filter_type, self.frequency, self.q, self.gain, self.sample_rate);
+        let samples = (SMOOTHING_TIME * self.sample_rate as f64) as usize;
+        self.coefficients.set(coefficients, samples);
+    }
+}
+
+impl<S: Sample> Processor for Biquad<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.states.fill(BiquadState::default());
+        self.coefficients.snap();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+        self.coefficients.snap();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        let coefficients = self.coefficients.next();
+        let states = &mut self.states;
+
+        S::from_channels(| c | {
+            let x = input.channel(c).to_f64();
+            S::Float::from_f64(states[c].process(&coefficients, x))
+        })
+    }
+}
+
+impl<S: Sample> Param for Biquad<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "frequency" => self.set_frequency(value),
+            "q" => self.set_q(value),
+            "gain" => self.set_gain(value),
+            _ => ()
+        }
+    }
+}
+
+/// Alignment of a cascade of biquads
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CascadeResponse {
+    /// Maximally flat passband, 3 dB down at the frequency
+    Butterworth,
+    /// Two Butterworths in series, 6 dB down at the frequency so lowpass and highpass sum flat for crossovers
+    LinkwitzRiley,
+}
+
+/// Section of a cascade, second order with its Q or first order
+#[derive(Copy, Clone)]
+struct Section {
+    q: Option<f32>,
+    coefficients: Smoothed,
+}
+
+/// Higher-order Butterworth or Linkwitz-Riley lowpass or highpass built from biquads in series
+///
+/// Odd Butterworth orders end in a first-order section. Linkwitz-Riley
+/// orders must be even, and the outputs of a second-order crossover are in
+/// antiphase, so invert one band when summing them.
+pub struct BiquadCascade<S: Sample> {
+    response: CascadeResponse,
+    filter_type: BiquadType,
+    order: usize,
+    frequency: f32,
+
+    sample_rate: u32,
+    sections: Vec<Section>,
+    states: Vec<Vec<BiquadState>>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> BiquadCascade<S> {
+    /// Lowpass or highpass Butterworth of `order` from 1 upwards
+    pub fn butterworth(filter_type: BiquadType, order: usize, frequency: f32) -> Result<Self, String> {
+        Self::from(CascadeResponse::Butterworth, filter_type, order, frequency)
+    }
+
+    /// Lowpass or highpass Linkwitz-Riley of an even `order`, such as 4 for the usual LR4 crossover
+    pub fn linkwitz_riley(filter_type: BiquadType, order: usize, frequency: f32) -> Result<Self, String> {
+        Self::from(CascadeResponse::LinkwitzRiley, filter_type, order, frequency)
+    }
+
+    pub fn from(response: CascadeResponse, filter_type: BiquadType, order: usize, frequency: f32) -> Result<Self, String> {
+        if filter_type != BiquadType::Lowpass && filter_type != BiquadType::Highpass {
+            return Err(format!("Cascades are lowpass or highpass, not {:?}", filter_type));
+        }
+
+        if order == 0 {
+            return Err("Cascade order must be at least 1".to_string());
+        }
+
+        let butterworth = match response {
+            CascadeResponse::Butterworth => vec![order],
+            CascadeResponse::LinkwitzRiley if order.is_multiple_of(2) => vec![order / 2, order / 2],
+            CascadeResponse::LinkwitzRiley => return Err(format!("Linkwitz-Riley order must be even, not {}", order)),
+        };
+
+        /* Each Butterworth has a biquad for each conjugate pair of poles, plus a real pole for odd orders */
+        let mut sections = Vec::new();
+        for n in butterworth {
+            for k in 0..n / 2 {
+                /* Angle of the pair from the negative real axis, offset by half a step when a real pole takes the middle */
+                let angle = std::f32::consts::PI * (2 * k + 1 + n % 2) as f32 / (2 * n) as f32;
+                sections.push(Section {
+                    q: Some(1.0 / (2.0 * f32::cos(angle))),
+                    coefficients: Smoothed::from(BiquadCoefficients::IDENTITY),
+                });
+            }
+
+            if n % 2 == 1 {
+                sections.push(Section {
+                    q: None,
+                    coefficients: Smoothed::from(BiquadCoefficients::IDENTITY),
+                });
+            }
+        }
+
+        let mut cascade = Self {
+            response,
+            filter_type,
+            order,
+            frequency,
+            sample_rate: 44100,
+            states: vec![vec![BiquadState::default(); sections.len()]; S::CHANNELS],
+            sections,
+            _phantom: PhantomData,
+        };
+
+        cascade.update(0);
+        Ok(cascade)
+    }
+
+    pub fn response(&self) -> CascadeResponse {
+        self.response
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn set_frequency(&mut self, hz: f32) {
+        self.frequency = hz;
+        self.update((SMOOTHING_TIME * self.sample_rate as f64) as usize);
+    }
+
+    /// Gain at `frequency` as a linear magnitude
+    pub fn magnitude(&self, frequency: f32) -> f32 {
+        self.sections
+            .iter()
+            .map(| section | section.coefficients.target.magnitude(frequency, self.sample_rate))
+            .product()
+    }
+
+    fn update(&mut self, samples: usize) {
+        let lowpass = self.filter_type == BiquadType::Lowpass;
+
+        for section in self.sections.iter_mut() {
+            let coefficients = match section.q {
+                Some(q) => BiquadCoefficients::design(self.filter_type, self.frequency, q, 0.0, self.sample_rate),
+                None => BiquadCoefficients::first_order(lowpass, self.frequency, self.sample_rate),
+            };
+
+            section.coefficients.set(coefficients, samples);
+        }
+    }
+}
+
+impl<S: Sample> Processor for BiquadCascade<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        for states in self.states.iter_mut() {
+            states.fill(BiquadState::default());
+        }
+
+        for section in self.sections.iter_mut() {
+            section.coefficients.snap();
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update(0);
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        for section in self.sections.iter_mut() {
+            section.coefficients.next();
+        }
+
+        let sections = &self.sections;
+        let states = &mut self.states;
+
+        S::from_channels(| c | {
+            let mut x = input.channel(c).to_f64();
+
+            for (section, state) in sections.iter().zip(states[c].iter_mut()) {
+                x = state.process(&section.coefficients.current, x);
+            }
+
+            S::Float::from_f64(x)
+        })
+    }
+}
+
+impl<S: Sample> Param for BiquadCascade<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        if name == "frequency" {
+            self.set_frequency(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn db(db: f32) -> f32 {
+        f32::powf(10.0, db / 20.0)
+    }
+
+    #[test]
+    fn butterworth_is_half_power_at_cutoff() {
+        for order in 1..=6 {
+            for filter_type in [BiquadType::Lowpass, BiquadType::Highpass] {
+                let mut cascade = BiquadCascade::<f32>::butterworth(filter_type, order, 1000.0).unwrap();
+                cascade.prepare(SAMPLE_RATE, 64);
+
+                let magnitude = cascade.magnitude(1000.0);
+                assert!((magnitude - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3, "order {} gave {}", order, magnitude);
+            }
+        }
+    }
+
+    #[test]
+    fn linkwitz_riley_is_half_gain_at_cutoff() {
+        for order in [2, 4, 8] {
+            let mut cascade = BiquadCascade::<f32>::linkwitz_riley(BiquadType::Lowpass, order, 1000.0).unwrap();
+            cascade.prepare(SAMPLE_RATE, 64);
+
+            let magnitude = cascade.magnitude(1000.0);
+            assert!((magnitude - 0.5).abs() < 1e-3, "order {} gave {}", order, magnitude);
+        }
+
+        assert!(BiquadCascade::<f32>::linkwitz_riley(BiquadType::Lowpass, 3, 1000.0).is_err());
+    }
+
+    #[test]
+    fn peak_and_shelf_reach_their_gain() {
+        let peak = Biquad::<f32>::peak(1000.0, 1.0, 6.0);
+        assert!((peak.magnitude(1000.0) - db(6.0)).abs() < 1e-3);
+
+        let shelf = Biquad::<f32>::low_shelf(1000.0, 0.707, 6.0);
+        assert!((shelf.magnitude(0.0) - db(6.0)).abs() < 1e-3);
+        assert!((shelf.magnitude(20000.0) - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn processed_sine_matches_magnitude() {
+        for frequency in [250.0, 1000.0, 4000.0] {
+            let mut biquad = Biquad::<f32>::lowpass(1000.0, 0.707);
+            biquad.prepare(SAMPLE_RATE, 64);
+
+            /* Skip the transient, then take the peak level over whole cycles */
+            let mut peak: f32 = 0.0;
+            for n in 0..SAMPLE_RATE as usize {
+                let x = f32::sin(2.0 * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE as f32);
+                let y = biquad.process(x);
+                if n > SAMPLE_RATE as usize / 2 {
+                    peak = f32::max(peak, y.abs());
+                }
+            }
+
+            let expected = biquad.magnitude(frequency);
+            assert!((peak - expected).abs() < expected * 0.01, "{} Hz gave {} for {}", frequency, peak, expected);
+        }
+    }
+}
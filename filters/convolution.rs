@@ -0,0 +1,374 @@
+use std::f64::consts::PI;
+use std::marker::PhantomData;
+
+use crate::filters::*;
+use crate::float::*;
+use crate::routing::param::*;
+use crate::spectral::*;
+use crate::traits::*;
+
+/* Zero crossings either side of each resampled point */
+const RESAMPLE_ZEROS: f64 = 24.0;
+
+/* Shape of the resampling window, around 90 dB of stopband */
+const RESAMPLE_BETA: f64 = 9.0;
+
+/* Fade applied where the response is cut short, so the truncation doesn't click */
+const TRIM_FADE: f32 = 0.01;
+
+/// How the channels of an impulse response map inputs to outputs
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum IrLayout {
+    /// One response applied to every channel
+    Mono,
+    /// Left to left and right to right
+    Stereo,
+    /// Left to left, left to right, right to left and right to right
+    TrueStereo,
+}
+
+/// Impulse response at its recorded sample rate, with one, two or four channels
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImpulseResponse {
+    channels: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl ImpulseResponse {
+    /// Channels in the order of their `IrLayout`, which must all be the same length
+    pub fn from(channels: Vec<Vec<f32>>, sample_rate: u32) -> Result<Self, String> {
+        if !matches!(channels.len(), 1 | 2 | 4) {
+            return Err(format!("Impulse responses need 1, 2 or 4 channels, not {}", channels.len()));
+        }
+
+        if channels[0].is_empty() {
+            return Err(String::from("Impulse response is empty"));
+        }
+
+        if channels.iter().any(| c | c.len() != channels[0].len()) {
+            return Err(String::from("Impulse response channels differ in length"));
+        }
+
+        if sample_rate == 0 {
+            return Err(String::from("Impulse response sample rate must be positive"));
+        }
+
+        Ok(Self { channels, sample_rate })
+    }
+
+    pub fn layout(&self) -> IrLayout {
+        match self.channels.len() {
+            1 => IrLayout::Mono,
+            2 => IrLayout::Stereo,
+            _ => IrLayout::TrueStereo,
+        }
+    }
+
+    pub fn channel(&self, index: usize) -> &[f32] {
+        &self.channels[index]
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length in samples at the recorded sample rate
+    pub fn length(&self) -> usize {
+        self.channels[0].len()
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f32 {
+        self.length() as f32 / self.sample_rate as f32
+    }
+}
+
+impl Loadable for ImpulseResponse {
+    fn load(path: &str) -> Result<Self, String> where Self: Sized {
+        let mut reader = hound::WavReader::open(path).map_err(| e | e.to_string())?;
+        let spec = reader.spec();
+        let channels = spec.channels as usize;
+
+        if !matches!(channels, 1 | 2 | 4) {
+            return Err(format!("Impulse responses need 1, 2 or 4 channels, not {}", channels));
+        }
+
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => {
+                reader.samples::<f32>()
+                    .collect::<Result<_, _>>()
+                    .map_err(| e | e.to_string())?
+            },
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>()
+                    .map(| v | v.map(| v | v as f32 * scale))
+                    .collect::<Result<_, _>>()
+                    .map_err(| e | e.to_string())?
+            }
+        };
+
+        let deinterleaved = (0..channels)
+            .map(| c | samples.iter().skip(c).step_by(channels).copied().collect())
+            .collect();
+
+        ImpulseResponse::from(deinterleaved, spec.sample_rate)
+    }
+}
+
+/// Kernel from one input channel to one output channel
+#[derive(Clone)]
+struct ConvolutionPath {
+    input: usize,
+    output: usize,
+    convolver: PartitionedConvolver,
+}
+
+/// Convolution with an impulse response, for reverbs of real rooms and cabinet simulation
+///
+/// The response is trimmed, stretched, pre-delayed and resampled to the
+/// engine rate whenever those settings or the sample rate change, which
+/// allocates and isn't real-time safe. Processing itself has no latency.
+/// Mono responses apply to every channel, stereo responses run each side
+/// separately and true-stereo responses feed both inputs to both outputs.
+pub struct Convolution<S: Sample> {
+    ir: ImpulseResponse,
+    partitioning: Partitioning,
+    trim_start: f32,
+    trim_length: Option<f32>,
+    pre_delay: f32,
+    stretch: f32,
+    mix: f32,
+
+    sample_rate: u32,
+    length: usize,
+    paths: Vec<ConvolutionPath>,
+    wet: Vec<f32>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> Convolution<S> {
+    pub fn from(ir: ImpulseResponse) -> Self {
+        let sample_rate = ir.sample_rate();
+        let mut convolution = Self {
+            ir,
+            partitioning: Partitioning::Uniform(64),
+            trim_start: 0.0,
+            trim_length: None,
+            pre_delay: 0.0,
+            stretch: 1.0,
+            mix: 1.0,
+            sample_rate,
+            length: 0,
+            paths: Vec::new(),
+            wet: vec![0.0; S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        convolution.update();
+        convolution
+    }
+
+    pub fn set_impulse_response(&mut self, ir: ImpulseResponse) {
+        self.ir = ir;
+        self.update();
+    }
+
+    pub fn impulse_response(&self) -> &ImpulseResponse {
+        &self.ir
+    }
+
+    /// Uniform partitions, the default, keep the load the same on every block. Non-uniform
+    /// ones are cheaper on average for long responses but aren't real-time safe.
+    pub fn set_partitioning(&mut self, partitioning: Partitioning) {
+        self.partitioning = partitioning;
+        self.update();
+    }
+
+    /// Skip `start` seconds of the response and keep at most `length` seconds after it
+    pub fn set_trim(&mut self, start: f32, length: Option<f32>) {
+        self.trim_start = f32::max(start, 0.0);
+        self.trim_length = length.map(| l | f32::max(l, 0.0));
+        self.update();
+    }
+
+    /// Silence in seconds before the response starts
+    pub fn set_pre_delay(&mut self, seconds: f32) {
+        self.pre_delay = f32::max(seconds, 0.0);
+        self.update();
+    }
+
+    /// Stretch the response in time by `factor`, lengthening the decay and lowering its colour
+    pub fn set_stretch(&mut self, factor: f32) {
+        self.stretch = f32::clamp(factor, 0.1, 10.0);
+        self.update();
+    }
+
+    /// Balance from 0 for only the dry input to 1 for only the convolved signal
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = f32::clamp(mix, 0.0, 1.0);
+    }
+
+    /// Length of the prepared response in samples at the engine rate, including the pre-delay
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    fn update(&mut self) {
+        let rate = self.ir.sample_rate() as f32;
+        let start = usize::min((self.trim_start * rate) as usize, self.ir.length() - 1);
+        let end = match self.trim_length {
+            Some(length) => usize::clamp(start + (length * rate) as usize, start + 1, self.ir.length()),
+            None => self.ir.length(),
+        };
+
+        /* Resampling to the engine rate and stretching are one change of rate, scaled to keep the gain */
+        let ratio = self.sample_rate as f64 * self.stretch as f64 / self.ir.sample_rate() as f64;
+        let fade = if end < self.ir.length() { (TRIM_FADE * rate) as usize } else { 0 };
+        let delay = (self.pre_delay * self.sample_rate as f32) as usize;
+
+        let trimmed = | c: usize | self.ir.channels[c][start..end].to_vec();
+
+        /* Mono engines hear the average of the left and right outputs */
+        let mono = | channels: &[usize] | {
+            let mut sum = vec![0.0; end - start];
+            for &c in channels {
+                for (s, x) in sum.iter_mut().zip(&self.ir.channels[c][start..end]) {
+                    *s += x * 0.5;
+                }
+            }
+
+            sum
+        };
+
+        let routes: Vec<(usize, usize, Vec<f32>)> = match (self.ir.layout(), S::CHANNELS) {
+            (IrLayout::Mono, channels) => (0..channels).map(| c | (c, c, trimmed(0))).collect(),
+            (IrLayout::Stereo, 1) => vec![(0, 0, mono(&[0, 1]))],
+            (IrLayout::Stereo, channels) => (0..channels).map(| c | (c, c, trimmed(c % 2))).collect(),
+            (IrLayout::TrueStereo, 1) => vec![(0, 0, mono(&[0, 1, 2, 3]))],
+            (IrLayout::TrueStereo, _) => vec![
+                (0, 0, trimmed(0)),
+                (0, 1, trimmed(1)),
+                (1, 0, trimmed(2)),
+                (1, 1, trimmed(3)),
+            ],
+        };
+
+        let fit = | mut channel: Vec<f32> | {
+            apply_fade(&mut channel, fade);
+
+            let mut kernel = vec![0.0; delay];
+            kernel.extend(resample(&channel, ratio).iter().map(| x | (*x as f64 / ratio) as f32));
+            kernel
+        };
+
+        let kernels: Vec<(usize, usize, Vec<f32>)> = routes
+            .into_iter()
+            .map(| (input, output, channel) | (input, output, fit(channel)))
+            .collect();
+
+        self.length = kernels[0].2.len();
+        self.paths = kernels
+            .into_iter()
+            .map(| (input, output, kernel) | ConvolutionPath {
+                input,
+                output,
+                convolver: PartitionedConvolver::from(&kernel, self.partitioning),
+            })
+            .collect();
+    }
+}
+
+impl<S: Sample> Processor for Convolution<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        for path in self.paths.iter_mut() {
+            path.convolver.reset();
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.update();
+        }
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        self.wet.fill(0.0);
+        for path in self.paths.iter_mut() {
+            self.wet[path.output] += path.convolver.process(input.channel(path.input).to_f32());
+        }
+
+        let mix = self.mix;
+        let wet = &self.wet;
+        S::from_channels(| c | S::Float::from(input.channel(c).to_f32() * (1.0 - mix) + wet[c] * mix))
+    }
+}
+
+impl<S: Sample> Param for Convolution<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        if name == "mix" {
+            self.set_mix(value);
+        }
+    }
+}
+
+/// Raised cosine fade over the last `length` samples
+fn apply_fade(samples: &mut [f32], length: usize) {
+    let length = usize::min(length, samples.len());
+    let start = samples.len() - length;
+
+    for (i, s) in samples[start..].iter_mut().enumerate() {
+        *s *= 0.5 + 0.5 * f32::cos(std::f32::consts::PI * (i + 1) as f32 / length as f32);
+    }
+}
+
+/// Band-limited resampling to `ratio` output samples per input sample
+///
+/// Uses a Kaiser windowed sinc, tabulated since long responses need
+/// millions of taps, with the cutoff lowered to the new Nyquist when
+/// downsampling. The level of each sample is kept, not the sum of them.
+fn resample(samples: &[f32], ratio: f64) -> Vec<f32> {
+    if (ratio - 1.0).abs() < 1e-9 {
+        return samples.to_vec();
+    }
+
+    const RESOLUTION: usize = 512;
+
+    let norm = bessel_i0(RESAMPLE_BETA);
+    let table: Vec<f64> = (0..=RESAMPLE_ZEROS as usize * RESOLUTION + 1)
+        .map(| i | {
+            let x = i as f64 / RESOLUTION as f64;
+            let r = f64::min(x / RESAMPLE_ZEROS, 1.0);
+            let sinc = if x == 0.0 { 1.0 } else { f64::sin(PI * x) / (PI * x) };
+            sinc * bessel_i0(RESAMPLE_BETA * f64::sqrt(1.0 - r * r)) / norm
+        })
+        .collect();
+
+    let cutoff = f64::min(ratio, 1.0);
+    let radius = RESAMPLE_ZEROS / cutoff;
+    let length = f64::ceil(samples.len() as f64 * ratio) as usize;
+
+    (0..length)
+        .map(| m | {
+            let t = m as f64 / ratio;
+            let first = f64::max(f64::ceil(t - radius), 0.0) as usize;
+            let last = usize::min(f64::floor(t + radius) as usize, samples.len() - 1);
+
+            let sum: f64 = (first..=last)
+                .map(| k | {
+                    let position = f64::abs(t - k as f64) * cutoff * RESOLUTION as f64;
+                    let i = position as usize;
+                    let fraction = position - i as f64;
+                    let kernel = table[i] + (table[i + 1] - table[i]) * fraction;
+                    samples[k] as f64 * kernel
+                })
+                .sum();
+
+            (sum * cutoff) as f32
+        })
+        .collect()
+}
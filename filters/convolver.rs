@@ -0,0 +1,228 @@
+use crate::fft::*;
+use crate::float::*;
+
+/// Uniformly partitioned FFT convolution of a mono signal with a fixed kernel
+///
+/// The kernel is split into partitions of the block size, each transformed
+/// once up front. Every block of input is transformed once and multiplied
+/// against every partition through a frequency-domain delay line, so the
+/// cost per sample grows with the kernel length over the block size rather
+/// than with the kernel length. Output blocks line up with input blocks.
+#[derive(Clone)]
+pub struct Convolver {
+    block_size: usize,
+    fft: RealFftPlan,
+    partitions: Vec<Vec<Complex<f32>>>,
+    history: Vec<Vec<Complex<f32>>>,
+    position: usize,
+    window: Vec<f32>,
+    accumulator: Vec<Complex<f32>>,
+    frame: Vec<f32>,
+}
+
+/* RealFft holds scratch space and isn't Clone, so convolvers rebuild their plan when cloned */
+struct RealFftPlan(RealFft<f32>);
+
+impl Clone for RealFftPlan {
+    fn clone(&self) -> Self {
+        RealFftPlan(RealFft::new(self.0.size()))
+    }
+}
+
+impl Convolver {
+    pub fn from(kernel: &[f32], block_size: usize) -> Self {
+        let block_size = usize::max(block_size, 1);
+        let size = block_size * 2;
+        let mut fft = RealFft::new(size);
+        let bins = fft.bins();
+
+        /* Each partition is zero padded to twice the block so the circular convolution doesn't wrap */
+        let count = usize::max(kernel.len().div_ceil(block_size), 1);
+        let mut frame = vec![0.0; size];
+        let partitions = (0..count)
+            .map(| p | {
+                frame.fill(0.0);
+                for (f, k) in frame.iter_mut().zip(kernel.iter().skip(p * block_size).take(block_size)) {
+                    *f = *k;
+                }
+
+                let mut spectrum = vec![Complex::ZERO; bins];
+                fft.forward(frame.as_slice(), spectrum.as_mut_slice());
+                spectrum
+            })
+            .collect();
+
+        Self {
+            block_size,
+            fft: RealFftPlan(fft),
+            partitions,
+            history: vec![vec![Complex::ZERO; bins]; count],
+            position: 0,
+            window: vec![0.0; size],
+            accumulator: vec![Complex::ZERO; bins],
+            frame,
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Number of partitions the kernel was split into
+    pub fn partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    pub fn reset(&mut self) {
+        for spectrum in self.history.iter_mut() {
+            spectrum.fill(Complex::ZERO);
+        }
+
+        self.window.fill(0.0);
+        self.position = 0;
+    }
+
+    /// Convolve the next `block_size` input samples, overwriting `block_size` output samples
+    pub fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        let block = self.block_size;
+
+        /* Overlap-save, sliding the newest block into the second half of the window */
+        self.window.copy_within(block.., 0);
+        self.window[block..].copy_from_slice(&input[..block]);
+
+        let count = self.partitions.len();
+        self.position = (self.position + count - 1) % count;
+        self.fft.0.forward(self.window.as_slice(), self.history[self.position].as_mut_slice());
+
+        self.accumulator.fill(Complex::ZERO);
+        for (p, partition) in self.partitions.iter().enumerate() {
+            let spectrum = &self.history[(self.position + p) % count];
+            for ((a, x), h) in self.accumulator.iter_mut().zip(spectrum).zip(partition) {
+                *a += *x * *h;
+            }
+        }
+
+        self.fft.0.inverse(self.accumulator.as_slice(), self.frame.as_mut_slice());
+
+        /* The first half wrapped around and is discarded */
+        output[..block].copy_from_slice(&self.frame[block..]);
+    }
+}
+
+/* Largest block a non-uniform partitioning grows to */
+const MAX_PARTITION: usize = 16384;
+
+/// How a `PartitionedConvolver` splits its kernel after the direct-form head
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Partitioning {
+    /// Every partition the given size, the head included
+    Uniform(usize),
+    /// Partitions starting at the given size and growing by four each stage, needing far fewer
+    /// operations for long kernels. The big transforms all run on the sample that completes
+    /// their block, so those blocks can overrun a real-time deadline.
+    NonUniform(usize),
+}
+
+/// One stage of uniform partitions, whose output lags its input by one block
+#[derive(Clone)]
+struct ConvolverStage {
+    convolver: Convolver,
+    input: Vec<f32>,
+    output: Vec<f32>,
+    index: usize,
+}
+
+/// Zero-latency convolution of a mono signal with a fixed kernel of any length
+///
+/// The first block of taps runs in direct form. The rest goes through
+/// stages of FFT convolution, each starting far enough into the kernel that
+/// its block is finished by the time its output is due.
+#[derive(Clone)]
+pub struct PartitionedConvolver {
+    /// Head taps reversed, so the dot product runs over the history from oldest to newest
+    head: Vec<f32>,
+    /// Input history stored twice, so the newest samples are always one contiguous slice
+    history: Vec<f32>,
+    position: usize,
+    stages: Vec<ConvolverStage>,
+}
+
+impl PartitionedConvolver {
+    pub fn from(kernel: &[f32], partitioning: Partitioning) -> Self {
+        let kernel = if kernel.is_empty() { &[0.0][..] } else { kernel };
+
+        let first = match partitioning {
+            Partitioning::Uniform(size) | Partitioning::NonUniform(size) => usize::max(size, 1),
+        };
+
+        let head_length = usize::min(first, kernel.len());
+        let mut stages = Vec::new();
+        let mut block = first;
+        let mut offset = first;
+
+        while offset < kernel.len() {
+            /* Each stage covers taps up to twice the next block size, where the next stage can start */
+            let end = match partitioning {
+                Partitioning::NonUniform(_) if block < MAX_PARTITION => usize::min(block * 8, kernel.len()),
+                _ => kernel.len(),
+            };
+
+            /* A stage delays by one block, so offsets past that are padded with a zero partition */
+            let mut segment = vec![0.0; offset - block];
+            segment.extend_from_slice(&kernel[offset..end]);
+
+            stages.push(ConvolverStage {
+                convolver: Convolver::from(&segment, block),
+                input: vec![0.0; block],
+                output: vec![0.0; block],
+                index: 0,
+            });
+
+            offset = end;
+            block *= 4;
+        }
+
+        Self {
+            head: kernel[..head_length].iter().rev().copied().collect(),
+            history: vec![0.0; head_length * 2],
+            position: 0,
+            stages,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+
+        for stage in self.stages.iter_mut() {
+            stage.convolver.reset();
+            stage.input.fill(0.0);
+            stage.output.fill(0.0);
+            stage.index = 0;
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let length = self.head.len();
+        self.history[self.position] = x;
+        self.history[self.position + length] = x;
+        self.position = (self.position + 1) % length;
+
+        let window = &self.history[self.position..self.position + length];
+        let mut y: f32 = self.head.iter().zip(window).map(| (h, x) | h * x).sum();
+
+        /* Each stage returns the block before, so its output arrives exactly one block later */
+        for stage in self.stages.iter_mut() {
+            y += stage.output[stage.index];
+            stage.input[stage.index] = x;
+            stage.index += 1;
+
+            if stage.index == stage.input.len() {
+                stage.convolver.process_block(&stage.input, &mut stage.output);
+                stage.index = 0;
+            }
+        }
+
+        y
+    }
+}
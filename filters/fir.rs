@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+
+use crate::filters::*;
+use crate::float::*;
+use crate::traits::*;
+
+/* Kernels up to this length run in direct form, longer ones go through FFT convolution */
+const DIRECT_LIMIT: usize = 128;
+
+/* Block size of the partitioned convolution, which is also the head of a long kernel run in direct form */
+const PARTITION_SIZE: usize = 64;
+
+/// Finite impulse response filter with a fixed kernel
+///
+/// Short kernels run in direct form. Long kernels run their first block of
+/// taps in direct form and the rest through partitioned FFT convolution,
+/// which finishes each block just in time for the next, so there is no
+/// added latency beyond that of the kernel itself.
+pub struct Fir<S: Sample> {
+    taps: Vec<f32>,
+    channels: Vec<PartitionedConvolver>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> Fir<S> {
+    pub fn from(taps: &[f32]) -> Self {
+        let mut fir = Self {
+            taps: Vec::new(),
+            channels: Vec::new(),
+            _phantom: PhantomData,
+        };
+
+        fir.set_taps(taps);
+        fir
+    }
+
+    /// Replace the kernel, clearing the filter state
+    pub fn set_taps(&mut self, taps: &[f32]) {
+        self.taps = if taps.is_empty() { vec![0.0] } else { taps.to_vec() };
+
+        let size = if self.taps.len() > DIRECT_LIMIT { PARTITION_SIZE } else { self.taps.len() };
+        let channel = PartitionedConvolver::from(&self.taps, Partitioning::Uniform(size));
+        self.channels = vec![channel; S::CHANNELS];
+    }
+
+    pub fn taps(&self) -> &[f32] {
+        &self.taps
+    }
+
+    /// Magnitude response of the kernel at `freq` hertz
+    pub fn magnitude(&self, freq: f32, sample_rate: u32) -> f32 {
+        fir_magnitude(&self.taps, freq, sample_rate)
+    }
+}
+
+impl<S: Sample> Processor for Fir<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        for channel in self.channels.iter_mut() {
+            channel.reset();
+        }
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        let channels = &mut self.channels;
+        S::from_channels(| c | S::Float::from(channels[c].process(input.channel(c).to_f32())))
+    }
+}
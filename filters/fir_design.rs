@@ -0,0 +1,642 @@
+use std::f64::consts::PI;
+use std::ops::Range;
+
+use crate::fft::*;
+use crate::float::*;
+use crate::spectral::*;
+
+/* Points per coefficient in the Parks-McClellan frequency grid */
+const GRID_DENSITY: usize = 16;
+
+/* Remez exchange iterations before settling for the current extremals */
+const MAX_ITERATIONS: usize = 40;
+
+/* Exchanges for more extremals than this start from a smaller design */
+const SCALING_EXTREMALS: usize = 64;
+
+/* Floor on the magnitude before taking its log, -160 dB below the peak */
+const MAGNITUDE_FLOOR: f64 = 1e-8;
+
+/// Ideal response of a windowed-sinc FIR, with edges in hertz
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FirBand {
+    Lowpass(f32),
+    Highpass(f32),
+    Bandpass(f32, f32),
+    Bandstop(f32, f32),
+}
+
+/// One band of a Parks-McClellan design, with edges in hertz
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EquirippleBand {
+    pub start: f32,
+    pub end: f32,
+    /// Desired linear gain across the band
+    pub gain: f32,
+    /// Relative importance of the error in this band
+    pub weight: f32,
+}
+
+impl EquirippleBand {
+    pub fn from(start: f32, end: f32, gain: f32, weight: f32) -> Self {
+        Self { start, end, gain, weight }
+    }
+}
+
+/// Linear-phase FIR from a truncated ideal response shaped by `window`
+///
+/// Highpass and bandstop responses pass Nyquist, which needs an odd length.
+/// The kernel is normalised to unity gain in the middle of its passband.
+pub fn fir_windowed_sinc(band: FirBand, length: usize, window: Window, sample_rate: u32) -> Result<Vec<f32>, String> {
+    if length == 0 {
+        return Err("FIR length must be at least one".to_string());
+    }
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let (low, high) = match band {
+        FirBand::Lowpass(f) | FirBand::Highpass(f) => (f, f),
+        FirBand::Bandpass(a, b) | FirBand::Bandstop(a, b) => (a, b),
+    };
+
+    if low <= 0.0 || high >= nyquist || low > high {
+        return Err(format!("FIR band edges must lie between 0 and {} Hz", nyquist));
+    }
+
+    if matches!(band, FirBand::Highpass(_) | FirBand::Bandstop(_, _)) && length.is_multiple_of(2) {
+        return Err("Highpass and bandstop FIR filters need an odd length".to_string());
+    }
+
+    let low = low as f64 / sample_rate as f64;
+    let high = high as f64 / sample_rate as f64;
+    let center = (length - 1) as f64 / 2.0;
+    let window = window.generate_symmetric::<f64>(length);
+
+    let lowpass = | fc: f64, n: usize | 2.0 * fc * sinc(2.0 * fc * (n as f64 - center));
+    let impulse = | n: usize | if n as f64 == center { 1.0 } else { 0.0 };
+
+    let taps: Vec<f64> = (0..length)
+        .map(| n | {
+            let ideal = match band {
+                FirBand::Lowpass(_) => lowpass(low, n),
+                FirBand::Highpass(_) => impulse(n) - lowpass(low, n),
+                FirBand::Bandpass(_, _) => lowpass(high, n) - lowpass(low, n),
+                FirBand::Bandstop(_, _) => impulse(n) - lowpass(high, n) + lowpass(low, n),
+            };
+
+            ideal * window[n]
+        })
+        .collect();
+
+    let reference = match band {
+        FirBand::Lowpass(_) | FirBand::Bandstop(_, _) => 0.0,
+        FirBand::Highpass(_) => 0.5,
+        FirBand::Bandpass(_, _) => (low + high) / 2.0,
+    };
+
+    let gain = response(&taps, reference).norm();
+    Ok(taps.iter().map(| t | (t / gain) as f32).collect())
+}
+
+/// Linear-phase FIR with the smallest peak weighted error across `bands`, from the Parks-McClellan algorithm
+///
+/// Gaps between bands are left unconstrained as transition regions. Even
+/// lengths always have a zero at Nyquist, so a band passing Nyquist needs an
+/// odd length, like a windowed-sinc highpass. Designs long enough that their
+/// ripple would fall below double precision return an error.
+pub fn fir_equiripple(bands: &[EquirippleBand], length: usize, sample_rate: u32) -> Result<Vec<f32>, String> {
+    if length < 3 {
+        return Err("Equiripple FIR length must be at least three".to_string());
+    }
+
+    if bands.is_empty() {
+        return Err("Equiripple FIR design needs at least one band".to_string());
+    }
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let mut previous = 0.0;
+    for band in bands {
+        if band.start < previous || band.end <= band.start || band.end > nyquist {
+            return Err(format!("Equiripple bands must be increasing and lie between 0 and {} Hz", nyquist));
+        }
+
+        if band.weight <= 0.0 {
+            return Err("Equiripple band weights must be positive".to_string());
+        }
+
+        previous = band.end;
+    }
+
+    let top = bands[bands.len() - 1];
+    if length.is_multiple_of(2) && top.end >= nyquist && top.gain != 0.0 {
+        return Err("Equiripple bands passing Nyquist need an odd length".to_string());
+    }
+
+    /* Number of cosine terms in the amplitude response */
+    let r = length / 2 + length % 2;
+    let even = length.is_multiple_of(2);
+
+    /* Dense grid over the bands in cycles per sample */
+    let spacing = 0.5 / (GRID_DENSITY * r) as f64;
+    let mut grid = Vec::new();
+    let mut desired = Vec::new();
+    let mut weights = Vec::new();
+    let mut ranges = Vec::new();
+
+    for band in bands {
+        let start = band.start as f64 / sample_rate as f64;
+        let end = band.end as f64 / sample_rate as f64;
+        let points = usize::max(((end - start) / spacing + 0.5) as usize, 1);
+
+        for i in 0..points {
+            grid.push(start + i as f64 * spacing);
+            desired.push(band.gain as f64);
+            weights.push(band.weight as f64);
+        }
+
+        *grid.last_mut().unwrap() = end;
+        ranges.push(grid.len() - points..grid.len());
+    }
+
+    /* Even lengths are a cosine series times cos(πf), so fit the rest and avoid its zero at Nyquist */
+    if even {
+        let last = grid.len() - 1;
+        grid[last] = f64::min(grid[last], 0.5 - spacing);
+
+        for ((f, d), w) in grid.iter().zip(desired.iter_mut()).zip(weights.iter_mut()) {
+            let c = f64::cos(PI * f);
+            *d /= c;
+            *w *= c;
+        }
+    }
+
+    if grid.len() <= r {
+        return Err("Equiripple bands are too narrow for the FIR length".to_string());
+    }
+
+    let extremals = exchange(&grid, &desired, &weights, &ranges, r + 1)?;
+    let interpolation = Remez::from(&extremals, &grid, &desired, &weights)?;
+
+    /* Sample the amplitude response at the DFT frequencies and invert it */
+    let amplitudes: Vec<f64> = (0..=length / 2)
+        .map(| k | {
+            let f = k as f64 / length as f64;
+            let c = if even { f64::cos(PI * f) } else { 1.0 };
+            interpolation.amplitude(f) * c
+        })
+        .collect();
+
+    let center = (length - 1) as f64 / 2.0;
+    let terms = if even { length / 2 - 1 } else { (length - 1) / 2 };
+
+    let taps: Vec<f32> = (0..length)
+        .map(| n | {
+            let x = 2.0 * PI * (n as f64 - center) / length as f64;
+            let sum = (1..=terms).fold(amplitudes[0], | sum, k | sum + 2.0 * amplitudes[k] * f64::cos(x * k as f64));
+            (sum / length as f64) as f32
+        })
+        .collect();
+
+    if taps.iter().any(| t | !t.is_finite()) {
+        return Err("Equiripple design failed to converge".to_string());
+    }
+
+    Ok(taps)
+}
+
+/// Linear-phase FIR following an arbitrary magnitude response, from `(hertz, gain)` points
+///
+/// Gains are interpolated linearly between points and held beyond the first
+/// and last. The response is sampled on a dense grid, transformed back to a
+/// symmetric kernel and shaped by `window` to smooth the truncation.
+pub fn fir_frequency_sampling(points: &[(f32, f32)], length: usize, window: Window, sample_rate: u32) -> Result<Vec<f32>, String> {
+    if length == 0 {
+        return Err("FIR length must be at least one".to_string());
+    }
+
+    if points.is_empty() {
+        return Err("Frequency sampling needs at least one point".to_string());
+    }
+
+    if points.windows(2).any(| p | p[1].0 < p[0].0) {
+        return Err("Frequency sampling points must be in increasing frequency".to_string());
+    }
+
+    let size = grid_size(length);
+    let magnitudes: Vec<f64> = (0..=size / 2)
+        .map(| k | {
+            let hz = (k as f64 * sample_rate as f64 / size as f64) as f32;
+            interpolate(points, hz) as f64
+        })
+        .collect();
+
+    Ok(from_magnitudes(&magnitudes, length, window))
+}
+
+/// Minimum-phase kernel with the same length and magnitude response as `taps`
+///
+/// Uses the homomorphic method, folding the real cepstrum of the log
+/// magnitude so all the zeros lie inside the unit circle. The energy moves
+/// to the start of the kernel, so latency drops at the cost of a nonlinear phase.
+pub fn fir_minimum_phase(taps: &[f32]) -> Vec<f32> {
+    if taps.len() < 2 {
+        return taps.to_vec();
+    }
+
+    /* A long transform keeps the cepstrum from aliasing */
+    let size = usize::max((taps.len() * 16).next_power_of_two(), 1024);
+    let mut fft = RealFft::<f64>::new(size);
+    let mut frame = vec![0.0; size];
+    let mut spectrum = vec![Complex::ZERO; fft.bins()];
+
+    for (f, t) in frame.iter_mut().zip(taps) {
+        *f = *t as f64;
+    }
+
+    fft.forward(frame.as_slice(), spectrum.as_mut_slice());
+
+    /* Zeros on the unit circle have no log, so floor them well below the peak */
+    let peak = spectrum.iter().fold(0.0, | peak, s | f64::max(peak, s.norm()));
+    let floor = f64::max(peak * MAGNITUDE_FLOOR, f64::MIN_POSITIVE);
+    for s in spectrum.iter_mut() {
+        *s = complex(f64::ln(f64::max(s.norm(), floor)), 0.0);
+    }
+
+    fft.inverse(spectrum.as_slice(), frame.as_mut_slice());
+
+    /* Fold the anticausal half of the cepstrum onto the causal half */
+    let half = size / 2;
+    for (n, c) in frame.iter_mut().enumerate() {
+        if n > half {
+            *c = 0.0;
+        } else if n > 0 && n < half {
+            *c *= 2.0;
+        }
+    }
+
+    fft.forward(frame.as_slice(), spectrum.as_mut_slice());
+    for s in spectrum.iter_mut() {
+        *s = Complex::from_polar(f64::exp(s.real), s.imaginary);
+    }
+
+    fft.inverse(spectrum.as_slice(), frame.as_mut_slice());
+    frame[..taps.len()].iter().map(| t | *t as f32).collect()
+}
+
+/// Linear-phase kernel with the same length and magnitude response as `taps`, shaped by `window`
+pub fn fir_linear_phase(taps: &[f32], window: Window) -> Vec<f32> {
+    if taps.len() < 2 {
+        return taps.to_vec();
+    }
+
+    let size = grid_size(taps.len());
+    let mut fft = RealFft::<f64>::new(size);
+    let mut frame = vec![0.0; size];
+    let mut spectrum = vec![Complex::ZERO; fft.bins()];
+
+    for (f, t) in frame.iter_mut().zip(taps) {
+        *f = *t as f64;
+    }
+
+    fft.forward(frame.as_slice(), spectrum.as_mut_slice());
+
+    let magnitudes: Vec<f64> = spectrum.iter().map(| s | s.norm()).collect();
+    from_magnitudes(&magnitudes, taps.len(), window)
+}
+
+/// Magnitude response of an FIR kernel at `freq` hertz
+pub fn fir_magnitude(taps: &[f32], freq: f32, sample_rate: u32) -> f32 {
+    let taps: Vec<f64> = taps.iter().map(| t | *t as f64).collect();
+    response(&taps, freq as f64 / sample_rate as f64).norm() as f32
+}
+
+/// Remez exchange for `count` extremals on the grid, returning them once the error is level
+fn exchange(grid: &[f64], desired: &[f64], weights: &[f64], ranges: &[Range<usize>], count: usize) -> Result<Vec<usize>, String> {
+    let spaced = | | (0..count).map(| i | i * (grid.len() - 1) / (count - 1)).collect::<Vec<usize>>();
+
+    /* On long designs evenly spaced extremals level the error below rounding, so start from a smaller design */
+    let mut extremals = if count > SCALING_EXTREMALS {
+        exchange(grid, desired, weights, ranges, count / 2)
+            .ok()
+            .and_then(| coarse | scale_extremals(&coarse, grid, ranges, count))
+            .unwrap_or_else(spaced)
+    } else {
+        spaced()
+    };
+
+    let mut error = vec![0.0; grid.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let interpolation = Remez::from(&extremals, grid, desired, weights)?;
+        for (i, e) in error.iter_mut().enumerate() {
+            *e = weights[i] * (desired[i] - interpolation.amplitude(grid[i]));
+        }
+
+        /* Extremes below the levelled error would let it shrink and the exchange cycle, with some slack for rounding */
+        let Some(found) = find_extremals(&error, ranges, interpolation.delta.abs() * 0.99, count) else {
+            break;
+        };
+
+        /* Converged once the error is level across the extremals */
+        let (min, max) = found
+            .iter()
+            .map(| &i | error[i].abs())
+            .fold((f64::MAX, 0.0), | (min, max), e | (f64::min(min, e), f64::max(max, e)));
+
+        extremals = found;
+        if max == 0.0 || (max - min) / max < 1e-4 {
+            return Ok(extremals);
+        }
+    }
+
+    Err("Equiripple design failed to converge, its ripple may be below double precision".to_string())
+}
+
+/// Spread the extremals of a smaller design to `count`, keeping their density within each band
+fn scale_extremals(extremals: &[usize], grid: &[f64], ranges: &[Range<usize>], count: usize) -> Option<Vec<usize>> {
+    let bands: Vec<Vec<usize>> = ranges
+        .iter()
+        .map(| range | extremals.iter().copied().filter(| e | range.contains(e)).collect())
+        .collect();
+
+    /* Each band keeps its share of the extremals, with the rounding made up in the largest */
+    let mut counts: Vec<usize> = bands.iter().map(| band | band.len() * count / extremals.len()).collect();
+    let largest = (0..bands.len()).max_by_key(| &b | bands[b].len()).unwrap_or(0);
+    counts[largest] += count - counts.iter().sum::<usize>();
+
+    let mut scaled = Vec::with_capacity(count);
+    for ((band, range), target) in bands.iter().zip(ranges).zip(counts) {
+        let mut previous = None;
+
+        for j in 0..target {
+            /* Interpolate the frequencies of the band's extremals at a fractional index */
+            let f = match band.len() {
+                0 => grid[range.start] + (grid[range.end - 1] - grid[range.start]) * j as f64 / usize::max(target - 1, 1) as f64,
+                1 => grid[band[0]],
+                n => {
+                    let x = j as f64 * (n - 1) as f64 / usize::max(target - 1, 1) as f64;
+                    let i = usize::min(x as usize, n - 2);
+                    let t = x - i as f64;
+                    grid[band[i]] + (grid[band[i + 1]] - grid[band[i]]) * t
+                }
+            };
+
+            /* Nearest grid point, kept strictly after the one before */
+            let nearest = range.start + grid[range.clone()].partition_point(| g | *g < f);
+            let nearest = usize::min(nearest, range.end - 1);
+            let index = match previous {
+                Some(p) if nearest <= p => p + 1,
+                _ => nearest,
+            };
+
+            if index >= range.end {
+                return None;
+            }
+
+            scaled.push(index);
+            previous = Some(index);
+        }
+    }
+
+    Some(scaled)
+}
+
+/// Barycentric Lagrange interpolation through the current extremals of a Remez exchange
+struct Remez {
+    /// Levelled error at the extremals
+    delta: f64,
+    x: Vec<f64>,
+    y: Vec<f64>,
+    weights: Vec<f64>,
+}
+
+impl Remez {
+    fn from(extremals: &[usize], grid: &[f64], desired: &[f64], weights: &[f64]) -> Result<Self, String> {
+        let x: Vec<f64> = extremals.iter().map(| &e | f64::cos(2.0 * PI * grid[e])).collect();
+
+        /* The products over- or underflow for long filters, so sum their logs and track the sign apart */
+        let logs: Vec<(f64, f64)> = x
+            .iter()
+            .enumerate()
+            .map(| (i, xi) | {
+                x.iter()
+                    .enumerate()
+                    .filter(| (j, _) | *j != i)
+                    .fold((0.0, 1.0), | (log, sign), (_, xj) | {
+                        let d = xi - xj;
+                        (log + f64::ln(d.abs()), if d < 0.0 { -sign } else { sign })
+                    })
+            })
+            .collect();
+
+        /* Scaling every weight alike cancels in the interpolation, so the largest is set to one */
+        let smallest = logs.iter().fold(f64::MAX, | smallest, (log, _) | f64::min(smallest, *log));
+        let barycentric: Vec<f64> = logs
+            .iter()
+            .map(| (log, sign) | sign * f64::exp(smallest - log))
+            .collect();
+
+        /* The levelled error that alternates across the extremals */
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for (i, (&e, b)) in extremals.iter().zip(&barycentric).enumerate() {
+            let sign = if i.is_multiple_of(2) { 1.0 } else { -1.0 };
+            numerator += b * desired[e];
+            denominator += sign * b / weights[e];
+        }
+
+        let delta = numerator / denominator;
+        if !delta.is_finite() || barycentric.iter().any(| b | !b.is_finite()) {
+            return Err("Equiripple design failed to converge".to_string());
+        }
+
+        let y = extremals
+            .iter()
+            .enumerate()
+            .map(| (i, &e) | {
+                let sign = if i.is_multiple_of(2) { 1.0 } else { -1.0 };
+                desired[e] - sign * delta / weights[e]
+            })
+            .collect();
+
+        Ok(Self { delta, x, y, weights: barycentric })
+    }
+
+    /// Amplitude response at `freq` cycles per sample
+    fn amplitude(&self, freq: f64) -> f64 {
+        let xc = f64::cos(2.0 * PI * freq);
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+
+        for ((x, y), w) in self.x.iter().zip(&self.y).zip(&self.weights) {
+            let c = xc - x;
+            if c == 0.0 {
+                return *y;
+            }
+
+            let c = w / c;
+            numerator += c * y;
+            denominator += c;
+        }
+
+        numerator / denominator
+    }
+}
+
+/// Local extremes of the error within each band at least `level` in size that alternate in sign, trimmed to `count`
+fn find_extremals(error: &[f64], ranges: &[Range<usize>], level: f64, count: usize) -> Option<Vec<usize>> {
+    let mut found = Vec::new();
+
+    /* Band edges only compare against their neighbour inside the band, never across a transition */
+    for range in ranges {
+        for i in range.clone() {
+            let e = error[i];
+            let before = if i > range.start { error[i - 1] } else { 0.0 };
+            let after = if i + 1 < range.end { error[i + 1] } else { 0.0 };
+
+            let peak = (e > 0.0 && e >= before && e > after) || (e < 0.0 && e <= before && e < after);
+            if peak && e.abs() >= level {
+                found.push(i);
+            }
+        }
+    }
+
+    /* Keep only the largest of each run of extremes with the same sign, so they alternate */
+    let mut alternating: Vec<usize> = Vec::with_capacity(found.len());
+    for i in found {
+        match alternating.last_mut() {
+            Some(last) if (error[*last] > 0.0) == (error[i] > 0.0) => {
+                if error[i].abs() > error[*last].abs() {
+                    *last = i;
+                }
+            }
+            _ => alternating.push(i),
+        }
+    }
+
+    /* Then drop whichever end is smaller until there are just enough */
+    let mut found = alternating;
+    while found.len() > count {
+        if error[found[found.len() - 1]].abs() < error[found[0]].abs() {
+            found.pop();
+        } else {
+            found.remove(0);
+        }
+    }
+
+    if found.len() < count {
+        None
+    } else {
+        Some(found)
+    }
+}
+
+/// Symmetric kernel of `length` taps from a zero-phase magnitude response sampled on a dense real FFT grid
+fn from_magnitudes(magnitudes: &[f64], length: usize, window: Window) -> Vec<f32> {
+    let size = (magnitudes.len() - 1) * 2;
+    let mut fft = RealFft::<f64>::new(size);
+    let mut frame = vec![0.0; size];
+
+    /* Delay by half the length so the kernel is centred and causal */
+    let delay = (length - 1) as f64 / 2.0;
+    let spectrum: Vec<Complex<f64>> = magnitudes
+        .iter()
+        .enumerate()
+        .map(| (k, m) | Complex::from_polar(*m, -2.0 * PI * k as f64 * delay / size as f64))
+        .collect();
+
+    fft.inverse(spectrum.as_slice(), frame.as_mut_slice());
+
+    let window = window.generate_symmetric::<f64>(length);
+    frame.iter().zip(&window).map(| (t, w) | (t * w) as f32).collect()
+}
+
+/// Power of two transform size that samples a response densely enough for a kernel of `length`
+fn grid_size(length: usize) -> usize {
+    usize::max((length * 8).next_power_of_two(), 512)
+}
+
+/// Gain at `hz` interpolated linearly between points
+fn interpolate(points: &[(f32, f32)], hz: f32) -> f32 {
+    let first = points[0];
+    let last = points[points.len() - 1];
+
+    if hz <= first.0 {
+        return first.1;
+    }
+
+    if hz >= last.0 {
+        return last.1;
+    }
+
+    let i = points.partition_point(| p | p.0 <= hz);
+    let (f0, g0) = points[i - 1];
+    let (f1, g1) = points[i];
+
+    if f1 == f0 {
+        g1
+    } else {
+        g0 + (g1 - g0) * (hz - f0) / (f1 - f0)
+    }
+}
+
+/// Complex response of a kernel at `freq` cycles per sample
+fn response(taps: &[f64], freq: f64) -> Complex<f64> {
+    taps.iter()
+        .enumerate()
+        .fold(Complex::ZERO, | sum, (n, t)| sum + Complex::from_polar(*t, -2.0 * PI * freq * n as f64))
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        f64::sin(PI * x) / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Largest deviation from `gain` across a band, sampled every 10 Hz
+    fn ripple(taps: &[f32], start: f32, end: f32, gain: f32) -> f32 {
+        let steps = ((end - start) / 10.0) as usize;
+        (0..=steps)
+            .map(| i | fir_magnitude(taps, start + (end - start) * i as f32 / steps as f32, SAMPLE_RATE))
+            .fold(0.0, | ripple, m | f32::max(ripple, (m - gain).abs()))
+    }
+
+    #[test]
+    fn equiripple_weights_set_the_ripple_ratio() {
+        let bands = [EquirippleBand::from(0.0, 4000.0, 1.0, 1.0), EquirippleBand::from(5000.0, 22050.0, 0.0, 10.0)];
+        let taps = fir_equiripple(&bands, 101, SAMPLE_RATE).unwrap();
+
+        let pass = ripple(&taps, 0.0, 4000.0, 1.0);
+        let stop = ripple(&taps, 5000.0, 22050.0, 0.0);
+        assert!((pass / stop - 10.0).abs() < 0.5, "ripples {} and {}", pass, stop);
+    }
+
+    #[test]
+    fn long_equiripple_designs_converge() {
+        let bands = [EquirippleBand::from(0.0, 4000.0, 1.0, 1.0), EquirippleBand::from(5000.0, 22050.0, 0.0, 1.0)];
+
+        for length in [350, 511, 512] {
+            let taps = fir_equiripple(&bands, length, SAMPLE_RATE).unwrap();
+            assert!(taps.iter().all(| t | t.is_finite()));
+
+            /* Kaiser's estimate puts the ripple of these lengths beyond -120 dB */
+            let pass = ripple(&taps, 0.0, 4000.0, 1.0);
+            let stop = ripple(&taps, 5000.0, 22050.0, 0.0);
+            assert!(pass < 1e-6 && stop < 1e-6, "length {} has ripples {} and {}", length, pass, stop);
+        }
+    }
+
+    #[test]
+    fn even_equiripple_rejects_passing_nyquist() {
+        let bands = [EquirippleBand::from(0.0, 1000.0, 0.0, 1.0), EquirippleBand::from(2000.0, 22050.0, 1.0, 1.0)];
+        assert!(fir_equiripple(&bands, 64, SAMPLE_RATE).is_err());
+        assert!(fir_equiripple(&bands, 65, SAMPLE_RATE).is_ok());
+    }
+}
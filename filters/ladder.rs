@@ -0,0 +1,460 @@
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::spectral::*;
+use crate::traits::*;
+
+/* Taps per phase of the oversampling filters, enough for a transition from 20 kHz to 24 kHz at 44.1 kHz */
+const OVERSAMPLING_TAPS: usize = 64;
+
+/* Extra solves per sample with the nonlinearities linearised around the previous solve */
+const REFINEMENTS: usize = 2;
+
+/* Highest oversampling factor */
+const MAX_OVERSAMPLING: usize = 16;
+
+/// Integrator states of one channel of an analog model, with the arguments of its nonlinearities last sample
+#[derive(Copy, Clone, Default)]
+pub struct ModelState {
+    s: [f32; 4],
+    args: [f32; 5],
+}
+
+/// Circuit simulated by an `AnalogFilter`
+///
+/// Models are systems of integrators with tanh nonlinearities. Each sample
+/// the nonlinearities are linearised around their values the sample before
+/// and the integrators are solved together with the trapezoidal rule, so
+/// the feedback has no unit delay and stays stable at any resonance. The
+/// filter then solves the sample again around the new values, which keeps
+/// the saturation from lagging at high cutoffs.
+pub trait FilterModel {
+    /// Frequency the model resonates at relative to the cutoff of its integrators
+    const TUNING: f32;
+    /// Feedback at a resonance of 0
+    const MIN_FEEDBACK: f32 = 0.0;
+    /// Feedback at a resonance of 1, a little past the point of self-oscillation
+    const MAX_FEEDBACK: f32;
+
+    /// Filter one sample with integrator gain `g`
+    fn tick(state: &mut ModelState, x: f32, g: f32, feedback: f32) -> f32;
+
+    /// Gain at DC with `feedback`
+    fn passband(_feedback: f32) -> f32 {
+        1.0
+    }
+}
+
+/// Gain of a tanh relative to a straight line for argument `x`
+fn saturation(x: f32) -> f32 {
+    if x.abs() < 1e-4 {
+        1.0
+    } else {
+        f32::tanh(x) / x
+    }
+}
+
+/// Solve `y = s + g (A y + b x)` for one trapezoidal step of `N` integrators and update their states
+fn trapezoid<const N: usize>(a: [[f32; N]; N], b: [f32; N], x: f32, g: f32, s: &mut [f32]) -> [f32; N] {
+    let mut m = [[0.0; N]; N];
+    let mut r = [0.0; N];
+
+    for i in 0..N {
+        for j in 0..N {
+            m[i][j] = if i == j { 1.0 } else { 0.0 } - g * a[i][j];
+        }
+
+        r[i] = s[i] + g * b[i] * x;
+    }
+
+    /* Gaussian elimination with partial pivoting, as strong feedback can dwarf the diagonal */
+    for i in 0..N {
+        let pivot = (i..N).max_by(| p, q | m[*p][i].abs().total_cmp(&m[*q][i].abs())).unwrap_or(i);
+        m.swap(i, pivot);
+        r.swap(i, pivot);
+
+        let pivot_row = m[i];
+        for row in i + 1..N {
+            let factor = m[row][i] / pivot_row[i];
+            for (value, pivot) in m[row][i..].iter_mut().zip(&pivot_row[i..]) {
+                *value -= factor * pivot;
+            }
+
+            r[row] -= factor * r[i];
+        }
+    }
+
+    let mut y = [0.0; N];
+    for i in (0..N).rev() {
+        let sum: f32 = (i + 1..N).map(| j | m[i][j] * y[j]).sum();
+        y[i] = (r[i] - sum) / m[i][i];
+    }
+
+    for i in 0..N {
+        s[i] = 2.0 * y[i] - s[i];
+    }
+
+    y
+}
+
+/// Four-pole transistor ladder lowpass in the style of Moog, with every stage saturating
+pub struct LadderModel;
+
+impl FilterModel for LadderModel {
+    const TUNING: f32 = 1.0;
+    const MAX_FEEDBACK: f32 = 4.4;
+
+    fn tick(state: &mut ModelState, x: f32, g: f32, k: f32) -> f32 {
+        let [su, s1, s2, s3, s4] = state.args.map(saturation);
+
+        /* Each stage follows tanh of its input minus tanh of its output, the first fed by the input less the feedback */
+        let a = [
+            [-s1, 0.0, 0.0, -k * su],
+            [s1, -s2, 0.0, 0.0],
+            [0.0, s2, -s3, 0.0],
+            [0.0, 0.0, s3, -s4],
+        ];
+
+        let y = trapezoid(a, [su, 0.0, 0.0, 0.0], x, g, &mut state.s);
+        state.args = [x - k * y[3], y[0], y[1], y[2], y[3]];
+        y[3]
+    }
+
+    fn passband(k: f32) -> f32 {
+        1.0 / (1.0 + k)
+    }
+}
+
+/// Four-pole diode ladder lowpass in the style of the TB-303, where neighbouring stages load each other
+pub struct DiodeModel;
+
+impl FilterModel for DiodeModel {
+    /* The coupled stages resonate well below the cutoff of each one */
+    const TUNING: f32 = 1.3765;
+    const MAX_FEEDBACK: f32 = 24.3;
+
+    fn tick(state: &mut ModelState, x: f32, g: f32, k: f32) -> f32 {
+        let [d0, d1, d2, d3, _] = state.args.map(saturation);
+
+        /* Each diode pair conducts tanh of the voltage across it, and the upper stages see half the current */
+        let a = [
+            [-d0 - d1, d1, 0.0, -k * d0],
+            [0.5 * d1, -0.5 * (d1 + d2), 0.5 * d2, 0.0],
+            [0.0, 0.5 * d2, -0.5 * (d2 + d3), 0.5 * d3],
+            [0.0, 0.0, 0.5 * d3, -0.5 * d3],
+        ];
+
+        let y = trapezoid(a, [d0, 0.0, 0.0, 0.0], x, g, &mut state.s);
+        state.args = [x - k * y[3] - y[0], y[0] - y[1], y[1] - y[2], y[2] - y[3], 0.0];
+        y[3]
+    }
+
+    fn passband(k: f32) -> f32 {
+        1.0 / (1.0 + k)
+    }
+}
+
+/// Two-pole Sallen-Key lowpass in the style of the Korg MS-20, with the feedback amplifier clipping
+pub struct SallenKeyModel;
+
+impl FilterModel for SallenKeyModel {
+    const TUNING: f32 = 1.0;
+    /* A gain of 1 gives a Q of 0.5 and 3 oscillates */
+    const MIN_FEEDBACK: f32 = 1.0;
+    const MAX_FEEDBACK: f32 = 3.3;
+
+    fn tick(state: &mut ModelState, x: f32, g: f32, k: f32) -> f32 {
+        let gain = k * saturation(state.args[0]);
+
+        /* States are the voltage across the feedback capacitor and the output capacitor's voltage */
+        let a = [
+            [-2.0, 1.0 - 2.0 * gain],
+            [1.0, gain - 1.0],
+        ];
+
+        let y = trapezoid(a, [1.0, 0.0], x, g, &mut state.s[..2]);
+        state.args[0] = k * y[1];
+        y[1]
+    }
+}
+
+/// Polyphase windowed-sinc upsampler and decimator for one channel
+#[derive(Clone)]
+struct Oversampler {
+    factor: usize,
+    phases: Vec<[f32; OVERSAMPLING_TAPS]>,
+    kernel: Vec<f32>,
+    input: [f32; OVERSAMPLING_TAPS],
+    output: Vec<f32>,
+    input_index: usize,
+    output_index: usize,
+}
+
+impl Oversampler {
+    fn new(factor: usize) -> Self {
+        let length = factor * OVERSAMPLING_TAPS;
+        let window: Vec<f32> = Window::Kaiser(8.0).generate_symmetric(length);
+        let center = (length - 1) as f32 / 2.0;
+
+        /* Centre the transition on the original Nyquist frequency, so whatever the decimator folds back lands above 20 kHz */
+        let cutoff = 1.0 / factor as f32;
+        let mut kernel: Vec<f32> = window.iter()
+            .enumerate()
+            .map(| (n, w) | {
+                let x = (n as f32 - center) * cutoff;
+                let sinc = if x == 0.0 { 1.0 } else { f32::sin(PI * x) / (PI * x) };
+                sinc * w
+            })
+            .collect();
+
+        let sum: f32 = kernel.iter().sum();
+        for tap in kernel.iter_mut() {
+            *tap /= sum;
+        }
+
+        let mut phases = vec![[0.0; OVERSAMPLING_TAPS]; factor];
+        for (n, tap) in kernel.iter().enumerate() {
+            phases[n % factor][n / factor] = tap * factor as f32;
+        }
+
+        Self {
+            factor,
+            phases,
+            kernel,
+            input: [0.0; OVERSAMPLING_TAPS],
+            output: vec![0.0; length],
+            input_index: 0,
+            output_index: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.input = [0.0; OVERSAMPLING_TAPS];
+        self.output.fill(0.0);
+    }
+
+    /// Run `f` `factor` times on the upsampled input and return its decimated output
+    fn process<P: FnMut(f32) -> f32>(&mut self, x: f32, mut f: P) -> f32 {
+        if self.factor == 1 {
+            return f(x);
+        }
+
+        self.input_index = (self.input_index + 1) % OVERSAMPLING_TAPS;
+        self.input[self.input_index] = x;
+
+        for phase in &self.phases {
+            let mut up = 0.0;
+            for (k, tap) in phase.iter().enumerate() {
+                up += tap * self.input[(self.input_index + OVERSAMPLING_TAPS - k) % OVERSAMPLING_TAPS];
+            }
+
+            self.output_index = (self.output_index + 1) % self.output.len();
+            self.output[self.output_index] = f(up);
+        }
+
+        let length = self.output.len();
+        self.kernel.iter()
+            .enumerate()
+            .map(| (k, tap) | tap * self.output[(self.output_index + length - k) % length])
+            .sum()
+    }
+}
+
+/// Nonlinear analog filter model for any sample type
+///
+/// Resonance runs from 0 to a little past self-oscillation at 1, and the
+/// saturation keeps the oscillation at a steady level. Oversampling runs the
+/// model at a multiple of the sample rate so its distortion doesn't alias
+/// and its tuning holds up near Nyquist, at the cost of 64 samples of latency.
+pub struct AnalogFilter<M: FilterModel, S: Sample> {
+    cutoff: f32,
+    resonance: f32,
+    drive: f32,
+    compensation: f32,
+    oversampling: usize,
+
+    sample_rate: u32,
+    g: f32,
+    states: Vec<ModelState>,
+    oversamplers: Vec<Oversampler>,
+    _phantom: PhantomData<(M, S)>,
+}
+
+pub type LadderFilter<S> = AnalogFilter<LadderModel, S>;
+pub type DiodeFilter<S> = AnalogFilter<DiodeModel, S>;
+pub type SallenKeyFilter<S> = AnalogFilter<SallenKeyModel, S>;
+
+impl<M: FilterModel, S: Sample> AnalogFilter<M, S> {
+    pub fn from(cutoff: f32, resonance: f32) -> Self {
+        let mut filter = Self {
+            cutoff,
+            resonance: 0.0,
+            drive: 1.0,
+            compensation: 0.0,
+            oversampling: 1,
+            sample_rate: 44100,
+            g: 0.0,
+            states: vec![ModelState::default(); S::CHANNELS],
+            oversamplers: vec![Oversampler::new(1); S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        filter.set_resonance(resonance);
+        filter.update();
+        filter
+    }
+
+    pub fn set_cutoff(&mut self, hz: f32) {
+        self.cutoff = hz;
+        self.update();
+    }
+
+    /// Resonance from 0 to 1, which self-oscillates
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = f32::clamp(resonance, 0.0, 1.0);
+    }
+
+    /// Gain into the nonlinearities, above 1 for more distortion
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = f32::max(drive, 0.0);
+    }
+
+    /// How much of the passband's loss at high resonance is made up, from 0 for none to 1 for all
+    pub fn set_compensation(&mut self, amount: f32) {
+        self.compensation = f32::clamp(amount, 0.0, 1.0);
+    }
+
+    /// Run the model at `factor` times the sample rate, from 1 for none to 16
+    pub fn set_oversampling(&mut self, factor: usize) {
+        let factor = usize::clamp(factor, 1, MAX_OVERSAMPLING);
+
+        if factor != self.oversampling {
+            self.oversampling = factor;
+            self.oversamplers = vec![Oversampler::new(factor); S::CHANNELS];
+            self.update();
+        }
+    }
+
+    pub fn oversampling(&self) -> usize {
+        self.oversampling
+    }
+
+    fn feedback(&self) -> f32 {
+        M::MIN_FEEDBACK + (M::MAX_FEEDBACK - M::MIN_FEEDBACK) * self.resonance
+    }
+
+    fn update(&mut self) {
+        let rate = (self.sample_rate as usize * self.oversampling) as f32;
+        /* Prewarp at the resonance rather than the integrators, so the resonance lands on the cutoff up to Nyquist */
+        let frequency = f32::clamp(self.cutoff, 1.0, rate * 0.49);
+        self.g = f32::tan(PI * frequency / rate) * M::TUNING;
+    }
+}
+
+impl<M: FilterModel, S: Sample> Processor for AnalogFilter<M, S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.states.fill(ModelState::default());
+
+        for oversampler in self.oversamplers.iter_mut() {
+            oversampler.reset();
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        let (g, feedback, drive) = (self.g, self.feedback(), self.drive);
+        let gain = f32::powf(M::passband(feedback), -self.compensation);
+        let states = &mut self.states;
+        let oversamplers = &mut self.oversamplers;
+
+        S::from_channels(| c | {
+            let x = input.channel(c).to_f32() * drive;
+            let state = &mut states[c];
+            let y = oversamplers[c].process(x, | x | {
+                /* Solving again around the nonlinearities of the last solution keeps the level steady near Nyquist */
+                for _ in 0..REFINEMENTS {
+                    let mut guess = *state;
+                    M::tick(&mut guess, x, g, feedback);
+                    state.args = guess.args;
+                }
+
+                M::tick(state, x, g, feedback)
+            });
+
+            S::Float::from(y * gain)
+        })
+    }
+}
+
+impl<M: FilterModel, S: Sample> Param for AnalogFilter<M, S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "cutoff" => self.set_cutoff(value),
+            "resonance" => self.set_resonance(value),
+            "drive" => self.set_drive(value),
+            "compensation" => self.set_compensation(value),
+            _ => ()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    /// Peak gain in dB of a steady sine at `hz` through `f`
+    fn sine_gain<P: FnMut(f32) -> f32>(hz: f32, mut f: P) -> f32 {
+        let length = SAMPLE_RATE as usize / 10;
+        let peak = (0..length)
+            .map(| n | f(f32::sin(2.0 * PI * hz * n as f32 / SAMPLE_RATE)))
+            .skip(length / 2)
+            .fold(0.0, | peak, y | f32::max(peak, y.abs()));
+
+        20.0 * f32::log10(peak)
+    }
+
+    /// Peak output over a quarter second of self-oscillation started by an impulse
+    fn ringing<M: FilterModel>(cutoff: f32) -> f32 {
+        let mut filter = AnalogFilter::<M, f32>::from(cutoff, 1.0);
+        filter.prepare(SAMPLE_RATE as u32, 64);
+
+        (0..SAMPLE_RATE as usize / 4)
+            .map(| n | filter.process(if n == 0 { 0.5 } else { 0.0 }))
+            .fold(0.0, | peak, y | f32::max(peak, y.abs()))
+    }
+
+    #[test]
+    fn self_oscillation_stays_bounded_near_nyquist() {
+        for cutoff in [1000.0, 10000.0, 18000.0, 21000.0] {
+            for (name, peak) in [
+                ("ladder", ringing::<LadderModel>(cutoff)),
+                ("diode", ringing::<DiodeModel>(cutoff)),
+                ("Sallen-Key", ringing::<SallenKeyModel>(cutoff)),
+            ] {
+                assert!(peak < 1.0, "{} filter at {} Hz rang at {}", name, cutoff, peak);
+            }
+        }
+    }
+
+    #[test]
+    fn oversampling_passband_is_flat() {
+        for factor in [2, 4, 8, 16] {
+            for hz in [1000.0, 10000.0, 16000.0, 20000.0] {
+                let mut oversampler = Oversampler::new(factor);
+                let gain = sine_gain(hz, | x | oversampler.process(x, | x | x));
+                assert!(gain.abs() < 0.1, "{}x oversampling gave {} dB at {} Hz", factor, gain, hz);
+            }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+mod biquad;
+mod convolution;
+mod convolver;
+mod fir;
+mod fir_design;
+mod ladder;
+mod svf;
+
+pub use biquad::*;
+pub use convolution::*;
+pub use convolver::*;
+pub use fir::*;
+pub use fir_design::*;
+pub use ladder::*;
+pub use svf::*;
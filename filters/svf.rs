@@ -0,0 +1,210 @@
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/* Smallest damping in linear mode, a Q of 200, since zero would ring forever */
+const MIN_LINEAR_DAMPING: f32 = 0.005;
+
+/* Damping taken away in nonlinear mode, so full resonance oscillates and the saturation holds it steady */
+const NONLINEAR_OVERDRIVE: f32 = 0.05;
+
+/// Output of a state-variable filter used when it runs as a `Processor`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SvfOutput {
+    Lowpass,
+    Bandpass,
+    Highpass,
+    Notch,
+    /// Lowpass minus highpass, boosting around the cutoff and flat elsewhere
+    Peak,
+}
+
+/// Every output of a state-variable filter for one sample
+#[derive(Copy, Clone, Debug)]
+pub struct SvfOutputs<S> {
+    pub lowpass: S,
+    pub bandpass: S,
+    pub highpass: S,
+    pub notch: S,
+    pub peak: S,
+}
+
+/// Integrator states of one channel
+#[derive(Copy, Clone, Default)]
+struct SvfState {
+    s1: f32,
+    s2: f32,
+    /// Previous inputs of the two integrators, where nonlinear mode saturates
+    x1: f32,
+    x2: f32,
+}
+
+/// Zero-delay-feedback state-variable filter from the topology-preserving transform
+///
+/// The trapezoidal integrators keep their state consistent when the cutoff
+/// or resonance changes, so both can be modulated every sample without
+/// clicks or blowing up. In nonlinear mode each integrator saturates like a
+/// tanh, so resonance up to self-oscillation stays bounded.
+pub struct StateVariableFilter<S: Sample> {
+    output: SvfOutput,
+    cutoff: f32,
+    damping: f32,
+    nonlinear: bool,
+
+    sample_rate: u32,
+    g: f32,
+    states: Vec<SvfState>,
+    scratch: Vec<[f32; 4]>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> StateVariableFilter<S> {
+    pub fn from(output: SvfOutput, cutoff: f32, resonance: f32) -> Self {
+        let mut filter = Self {
+            output,
+            cutoff,
+            damping: 2.0,
+            nonlinear: false,
+            sample_rate: 44100,
+            g: 0.0,
+            states: vec![SvfState::default(); S::CHANNELS],
+            scratch: vec![[0.0; 4]; S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        filter.set_resonance(resonance);
+        filter.update();
+        filter
+    }
+
+    pub fn set_output(&mut self, output: SvfOutput) {
+        self.output = output;
+    }
+
+    pub fn set_cutoff(&mut self, hz: f32) {
+        self.cutoff = hz;
+        self.update();
+    }
+
+    /// Resonance from 0 for a Q of 0.5 to 1 for self-oscillation in nonlinear mode, or a Q of 200 in linear mode
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.damping = 2.0 * (1.0 - f32::clamp(resonance, 0.0, 1.0));
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.damping = 1.0 / f32::max(q, 0.01);
+    }
+
+    /// Saturate the integrators, adding drive and taming resonance at high levels
+    pub fn set_nonlinear(&mut self, nonlinear: bool) {
+        self.nonlinear = nonlinear;
+    }
+
+    fn update(&mut self) {
+        let cutoff = f32::clamp(self.cutoff, 1.0, self.sample_rate as f32 * 0.49);
+        self.g = f32::tan(PI * cutoff / self.sample_rate as f32);
+    }
+
+    /// Lowpass, bandpass, highpass and the input of one channel
+    fn tick(state: &mut SvfState, x: f32, g: f32, damping: f32, nonlinear: bool) -> (f32, f32, f32) {
+        /* Saturating integrators are linearised around their last input, which keeps the solve in one step.
+         * The resonance path saturates too, adding damping as the bandpass grows, which sets the level
+         * of self-oscillation. */
+        let (g1, g2, damping) = if nonlinear {
+            let saturation = Self::saturation(state.x2);
+            (g * Self::saturation(state.x1), g * saturation, damping - NONLINEAR_OVERDRIVE + (1.0 - saturation))
+        } else {
+            (g, g, f32::max(damping, MIN_LINEAR_DAMPING))
+        };
+
+        let highpass = (x - (damping + g2) * state.s1 - state.s2) / (1.0 + g1 * (damping + g2));
+        let bandpass = g1 * highpass + state.s1;
+        let lowpass = g2 * bandpass + state.s2;
+
+        state.s1 = 2.0 * bandpass - state.s1;
+        state.s2 = 2.0 * lowpass - state.s2;
+        state.x1 = highpass;
+        state.x2 = bandpass;
+
+        (lowpass, bandpass, highpass)
+    }
+
+    /// Gain of a tanh integrator relative to a linear one for input `x`
+    fn saturation(x: f32) -> f32 {
+        if x.abs() < 1e-4 {
+            1.0
+        } else {
+            f32::tanh(x) / x
+        }
+    }
+
+    /// Run the filter for one sample and return all of its outputs
+    pub fn process_outputs(&mut self, input: S) -> SvfOutputs<S> {
+        let (g, damping, nonlinear) = (self.g, self.damping, self.nonlinear);
+        let states = &mut self.states;
+        let scratch = &mut self.scratch;
+
+        let lowpass = S::from_channels(| c | {
+            let (lowpass, bandpass, highpass) = Self::tick(&mut states[c], input.channel(c).to_f32(), g, damping, nonlinear);
+            scratch[c] = [bandpass, highpass, lowpass + highpass, lowpass - highpass];
+            S::Float::from(lowpass)
+        });
+
+        SvfOutputs {
+            lowpass,
+            bandpass: S::from_channels(| c | S::Float::from(scratch[c][0])),
+            highpass: S::from_channels(| c | S::Float::from(scratch[c][1])),
+            notch: S::from_channels(| c | S::Float::from(scratch[c][2])),
+            peak: S::from_channels(| c | S::Float::from(scratch[c][3])),
+        }
+    }
+}
+
+impl<S: Sample> Processor for StateVariableFilter<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.states.fill(SvfState::default());
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        let (g, damping, nonlinear) = (self.g, self.damping, self.nonlinear);
+        let output = self.output;
+        let states = &mut self.states;
+
+        S::from_channels(| c | {
+            let x = input.channel(c).to_f32();
+            let (lowpass, bandpass, highpass) = Self::tick(&mut states[c], x, g, damping, nonlinear);
+
+            let y = match output {
+                SvfOutput::Lowpass => lowpass,
+                SvfOutput::Bandpass => bandpass,
+                SvfOutput::Highpass => highpass,
+                SvfOutput::Notch => lowpass + highpass,
+                SvfOutput::Peak => lowpass - highpass,
+            };
+
+            S::Float::from(y)
+        })
+    }
+}
+
+impl<S: Sample> Param for StateVariableFilter<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "cutoff" => self.set_cutoff(value),
+            "resonance" => self.set_resonance(value),
+            "q" => self.set_q(value),
+            _ => ()
+        }
+    }
+}
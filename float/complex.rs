@@ -1,4 +1,4 @@
-use std::ops::{Mul, Add};
+use std::ops::{Mul, Add, Sub, Neg, Div, AddAssign, SubAssign, MulAssign};
 
 use crate::Float;
 
@@ -7,7 +7,7 @@ pub const fn complex<F: Float>(real: F, imaginary: F) -> Complex<F> {
 }
 
 pub type Complex32 = Complex<f32>;
-pub type Complex64 = Complex<f32>;
+pub type Complex64 = Complex<f64>;
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct Complex<F: Float> {
@@ -31,6 +31,41 @@ impl<F: Float> Complex<F> {
     pub fn from(real: F, imaginary: F) -> Self {
         Self { real, imaginary }
     }
+
+    /// Complex number with magnitude `norm` and angle `phase` in radians
+    pub fn from_polar(norm: F, phase: F) -> Self {
+        Self {
+            real: norm * phase.cos(),
+            imaginary: norm * phase.sin()
+        }
+    }
+
+    pub fn conj(self) -> Self {
+        Self {
+            real: self.real,
+            imaginary: F::ZERO - self.imaginary
+        }
+    }
+
+    /// Multiply by the imaginary unit
+    pub fn mul_i(self) -> Self {
+        Self {
+            real: F::ZERO - self.imaginary,
+            imaginary: self.real
+        }
+    }
+
+    pub fn norm_sqr(self) -> F {
+        self.real * self.real + self.imaginary * self.imaginary
+    }
+
+    pub fn norm(self) -> F {
+        self.norm_sqr().sqrt()
+    }
+
+    pub fn arg(self) -> F {
+        self.imaginary.atan2(self.real)
+    }
 }
 
 impl<F: Float> Mul for Complex<F> {
@@ -43,12 +78,34 @@ impl<F: Float> Mul for Complex<F> {
         let bd = self.imaginary * rhs.imaginary;
 
         Self {
-            real: ac + bd,
+            real: ac - bd,
             imaginary: adi + bci
         }
     }
 }
 
+impl<F: Float> Mul<F> for Complex<F> {
+    type Output = Complex<F>;
+
+    fn mul(self, rhs: F) -> Self::Output {
+        Self {
+            real: self.real * rhs,
+            imaginary: self.imaginary * rhs
+        }
+    }
+}
+
+impl<F: Float> Div<F> for Complex<F> {
+    type Output = Complex<F>;
+
+    fn div(self, rhs: F) -> Self::Output {
+        Self {
+            real: self.real / rhs,
+            imaginary: self.imaginary / rhs
+        }
+    }
+}
+
 impl<F: Float> Add for Complex<F> {
     type Output = Complex<F>;
 
@@ -59,3 +116,52 @@ impl<F: Float> Add for Complex<F> {
         }
     }
 }
+
+impl<F: Float> Sub for Complex<F> {
+    type Output = Complex<F>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            real: self.real - rhs.real,
+            imaginary: self.imaginary - rhs.imaginary
+        }
+    }
+}
+
+impl<F: Float> Neg for Complex<F> {
+    type Output = Complex<F>;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            real: F::ZERO - self.real,
+            imaginary: F::ZERO - self.imaginary
+        }
+    }
+}
+
+impl<F: Float> AddAssign for Complex<F> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.real += rhs.real;
+        self.imaginary += rhs.imaginary;
+    }
+}
+
+impl<F: Float> SubAssign for Complex<F> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.real -= rhs.real;
+        self.imaginary -= rhs.imaginary;
+    }
+}
+
+impl<F: Float> MulAssign for Complex<F> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl<F: Float> MulAssign<F> for Complex<F> {
+    fn mul_assign(&mut self, rhs: F) {
+        self.real *= rhs;
+        self.imaginary *= rhs;
+    }
+}
@@ -11,11 +11,17 @@ pub trait Float: Copy + Clone + From<f32>
     const PI: Self;
 
     fn from_usize(v: usize) -> Self;
+    fn from_f64(v: f64) -> Self;
+    fn to_f32(self) -> f32;
+    fn to_f64(self) -> f64;
 
     fn sin(self) -> Self;
     fn cos(self) -> Self;
     fn tan(self) -> Self;
     fn atan(self) -> Self;
+    fn atan2(self, x: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn floor(self) -> Self;
 
     fn powf(self, e: Self) -> Self;
     fn avg(self, v: Self) -> Self;
@@ -41,6 +47,18 @@ impl Float for f32 {
         v as f32
     }
 
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
     fn sin(self) -> Self {
         f32::sin(self)
     }
@@ -57,6 +75,18 @@ impl Float for f32 {
         f32::atan(self)
     }
 
+    fn atan2(self, x: Self) -> Self {
+        f32::atan2(self, x)
+    }
+
+    fn sqrt(self) -> Self {
+        f32::sqrt(self)
+    }
+
+    fn floor(self) -> Self {
+        f32::floor(self)
+    }
+
     fn avg(self, v: Self) -> Self {
         (self + v) / 2.0
     }
@@ -84,6 +114,18 @@ impl Float for f64 {
         v as f64
     }
 
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
     fn sin(self) -> Self {
         f64::sin(self)
     }
@@ -100,6 +142,18 @@ impl Float for f64 {
         f64::atan(self)
     }
 
+    fn atan2(self, x: Self) -> Self {
+        f64::atan2(self, x)
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn floor(self) -> Self {
+        f64::floor(self)
+    }
+
     fn avg(self, v: Self) -> Self {
         (self + v) / 2.0
     }
@@ -18,6 +18,10 @@ pub trait Sample: Copy + Clone + From<Self::Float>
     fn apply<Function: Fn(Self::Float) -> Self::Float>(self, f: Function) -> Self where Self: Sized;
 
     fn mono(self) -> Self::Float;
+    fn channel(self, index: usize) -> Self::Float;
+
+    /// Sample built from the value of each channel in order
+    fn from_channels<Function: FnMut(usize) -> Self::Float>(f: Function) -> Self;
 
     fn sin(self) -> Self {
         self.apply(Float::sin)
@@ -36,7 +40,7 @@ pub trait Sample: Copy + Clone + From<Self::Float>
     }
 
     fn sqrt(self) -> Self {
-        todo!()
+        Self::apply(self, Float::sqrt)
     }
 
     fn gain(&self, db: Self::Float) -> Self {
@@ -74,6 +78,14 @@ impl Sample for f32 {
         self
     }
 
+    fn channel(self, _index: usize) -> Self::Float {
+        self
+    }
+
+    fn from_channels<Function: FnMut(usize) -> Self::Float>(mut f: Function) -> Self {
+        f(0)
+    }
+
     fn powf(self, e: Self) -> Self {
         Float::powf(self, e)
     }
@@ -83,7 +95,7 @@ impl Sample for f32 {
     }
 
     fn max(self, rhs: Self) -> Self {
-        Float::min(self, rhs)
+        Float::max(self, rhs)
     }
 }
 
@@ -109,6 +121,14 @@ impl Sample for f64 {
         self
     }
 
+    fn channel(self, _index: usize) -> Self::Float {
+        self
+    }
+
+    fn from_channels<Function: FnMut(usize) -> Self::Float>(mut f: Function) -> Self {
+        f(0)
+    }
+
     fn powf(self, e: Self) -> Self {
         Float::powf(self, e)
     }
@@ -118,6 +138,6 @@ impl Sample for f64 {
     }
 
     fn max(self, rhs: Self) -> Self {
-        Float::min(self, rhs)
+        Float::max(self, rhs)
     }
 }
@@ -154,6 +154,17 @@ impl<F: Float> Sample for Stereo<F> {
         Float::avg(self.left, self.right)
     }
 
+    fn channel(self, index: usize) -> Self::Float {
+        if index == 0 { self.left } else { self.right }
+    }
+
+    fn from_channels<Function: FnMut(usize) -> Self::Float>(mut f: Function) -> Self {
+        let left = f(0);
+        let right = f(1);
+
+        Self { left, right }
+    }
+
     fn powf(self, e: Self) -> Self {
         Self {
             left: Float::powf(self.left, e.left),
@@ -6,6 +6,13 @@ pub mod event;
 pub mod traits;
 pub mod float;
 pub mod routing;
+pub mod fft;
+pub mod spectral;
+pub mod metering;
+pub mod oscillators;
+pub mod synth;
+pub mod modulation;
+pub mod filters;
 
 pub use buffers::*;
 pub use math::*;
@@ -15,6 +22,13 @@ pub use event::*;
 pub use traits::*;
 pub use float::*;
 pub use routing::*;
+pub use fft::*;
+pub use spectral::*;
+pub use metering::*;
+pub use oscillators::*;
+pub use synth::*;
+pub use modulation::*;
+pub use filters::*;
 
 extern crate lazy_static;
 
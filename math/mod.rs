@@ -3,8 +3,10 @@ pub mod conv;
 pub mod fast;
 pub mod faster;
 pub mod float;
+pub mod random;
 
 pub use crate::math::float::*;
+pub use crate::math::random::*;
 
 //type float = f32;
 //struct float(f32);
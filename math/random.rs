@@ -0,0 +1,83 @@
+/// Small seeded random number generator for reproducible renders
+///
+/// xoshiro256** seeded through SplitMix64, so nearby seeds give unrelated
+/// sequences. The sequence for a seed never changes between versions.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: [u64; 4],
+    spare: Option<f64>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut state = [0; 4];
+
+        for s in state.iter_mut() {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *s = z ^ (z >> 31);
+        }
+
+        Self {
+            state,
+            spare: None,
+        }
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    /// Uniform in `[0, 1)`
+    #[inline]
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1_u64 << 53) as f64)
+    }
+
+    /// Uniform in `[-1, 1)`
+    #[inline]
+    pub fn bipolar(&mut self) -> f64 {
+        self.uniform() * 2.0 - 1.0
+    }
+
+    /// Uniform integer in `[0, n)`
+    #[inline]
+    pub fn below(&mut self, n: usize) -> usize {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as usize
+    }
+
+    /// Normally distributed with zero mean and unit variance
+    pub fn gaussian(&mut self) -> f64 {
+        if let Some(spare) = self.spare.take() {
+            return spare;
+        }
+
+        /* Marsaglia polar method */
+        loop {
+            let u = self.bipolar();
+            let v = self.bipolar();
+            let s = u * u + v * v;
+
+            if s > 0.0 && s < 1.0 {
+                let scale = f64::sqrt(-2.0 * f64::ln(s) / s);
+                self.spare = Some(v * scale);
+                return u * scale;
+            }
+        }
+    }
+}
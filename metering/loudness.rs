@@ -0,0 +1,344 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::traits::*;
+
+/* Gating block histogram covering -70 LUFS to +30 LUFS in 0.1 LU steps */
+const HISTOGRAM_MIN: f64 = -70.0;
+const HISTOGRAM_STEP: f64 = 0.1;
+const HISTOGRAM_SIZE: usize = 1000;
+
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+/// ITU-R BS.1770 / EBU R128 loudness meter
+///
+/// Measures K-weighted momentary (400 ms), short-term (3 s) and gated
+/// integrated loudness in LUFS, and the EBU Tech 3342 loudness range in LU.
+pub struct LoudnessMeter<S: Sample> {
+    sample_rate: u32,
+    weights: Vec<f64>,
+    filters: Vec<KWeighting>,
+    block_length: usize,
+    block_position: usize,
+    block_sum: f64,
+    blocks: [f64; SHORT_TERM_BLOCKS],
+    block_index: usize,
+    block_count: usize,
+    momentary: f64,
+    short_term: f64,
+    max_momentary: f64,
+    max_short_term: f64,
+    gating: Histogram,
+    range: Histogram,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> LoudnessMeter<S> {
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_channels(sample_rate, S::CHANNELS)
+    }
+
+    /// Meter for frames of `channels` samples passed to `process_frame`
+    ///
+    /// Five and six channel meters assume ITU channel order (L, R, C, LFE, Ls, Rs)
+    /// and weight the surrounds by +1.5 dB and the LFE by zero.
+    pub fn with_channels(sample_rate: u32, channels: usize) -> Self {
+        let weights = match channels {
+            5 => vec![1.0, 1.0, 1.0, 1.41, 1.41],
+            6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+            _ => vec![1.0; channels],
+        };
+
+        Self {
+            sample_rate,
+            weights,
+            filters: vec![KWeighting::new(sample_rate); channels],
+            block_length: usize::max(sample_rate as usize / 10, 1),
+            block_position: 0,
+            block_sum: 0.0,
+            blocks: [0.0; SHORT_TERM_BLOCKS],
+            block_index: 0,
+            block_count: 0,
+            momentary: 0.0,
+            short_term: 0.0,
+            max_momentary: 0.0,
+            max_short_term: 0.0,
+            gating: Histogram::new(),
+            range: Histogram::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Per channel power weights
+    pub fn set_channel_weights(&mut self, weights: &[f32]) {
+        for (dest, w) in self.weights.iter_mut().zip(weights) {
+            *dest = *w as f64;
+        }
+    }
+
+    pub fn process_frame(&mut self, frame: &[S::Float]) {
+        for (channel, x) in frame.iter().enumerate().take(self.channels()) {
+            self.push(channel, x.to_f64());
+        }
+
+        self.advance();
+    }
+
+    fn push(&mut self, channel: usize, x: f64) {
+        let y = self.filters[channel].process(x);
+        self.block_sum += self.weights[channel] * y * y;
+    }
+
+    fn advance(&mut self) {
+        self.block_position += 1;
+
+        if self.block_position == self.block_length {
+            self.end_block();
+        }
+    }
+
+    /// Called every 100 ms, gating blocks overlap by 75 %
+    fn end_block(&mut self) {
+        self.blocks[self.block_index] = self.block_sum / self.block_length as f64;
+        self.block_index = (self.block_index + 1) % SHORT_TERM_BLOCKS;
+        self.block_count += 1;
+        self.block_position = 0;
+        self.block_sum = 0.0;
+
+        self.momentary = self.mean(MOMENTARY_BLOCKS);
+        self.short_term = self.mean(SHORT_TERM_BLOCKS);
+
+        if self.block_count >= MOMENTARY_BLOCKS {
+            self.max_momentary = f64::max(self.max_momentary, self.momentary);
+            self.gating.add(self.momentary);
+        }
+
+        if self.block_count >= SHORT_TERM_BLOCKS {
+            self.max_short_term = f64::max(self.max_short_term, self.short_term);
+            self.range.add(self.short_term);
+        }
+    }
+
+    fn mean(&self, count: usize) -> f64 {
+        let mut sum = 0.0;
+        for i in 0..count {
+            sum += self.blocks[(self.block_index + SHORT_TERM_BLOCKS - 1 - i) % SHORT_TERM_BLOCKS];
+        }
+
+        sum / count as f64
+    }
+
+    /// Loudness of the last 400 ms in LUFS
+    pub fn momentary(&self) -> f32 {
+        energy_to_loudness(self.momentary) as f32
+    }
+
+    /// Loudness of the last 3 s in LUFS
+    pub fn short_term(&self) -> f32 {
+        energy_to_loudness(self.short_term) as f32
+    }
+
+    pub fn max_momentary(&self) -> f32 {
+        energy_to_loudness(self.max_momentary) as f32
+    }
+
+    pub fn max_short_term(&self) -> f32 {
+        energy_to_loudness(self.max_short_term) as f32
+    }
+
+    /// Gated loudness since the last reset in LUFS
+    pub fn integrated(&self) -> f32 {
+        let (energy, count) = self.gating.gated_energy(HISTOGRAM_MIN);
+        if count == 0 {
+            return f32::NEG_INFINITY;
+        }
+
+        let threshold = energy_to_loudness(energy / count as f64) - 10.0;
+        let (energy, count) = self.gating.gated_energy(threshold);
+        if count == 0 {
+            return f32::NEG_INFINITY;
+        }
+
+        energy_to_loudness(energy / count as f64) as f32
+    }
+
+    /// Loudness range since the last reset in LU
+    pub fn loudness_range(&self) -> f32 {
+        let (energy, count) = self.range.gated_energy(HISTOGRAM_MIN);
+        if count == 0 {
+            return 0.0;
+        }
+
+        let threshold = energy_to_loudness(energy / count as f64) - 20.0;
+        let start = histogram_index(threshold);
+        let total: u64 = self.range.counts[start..].iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let percentile = | fraction: f64 | {
+            let target = ((total - 1) as f64 * fraction).round() as u64;
+            let mut seen = 0;
+            for (i, count) in self.range.counts.iter().enumerate().skip(start) {
+                seen += count;
+                if seen > target {
+                    return histogram_loudness(i);
+                }
+            }
+
+            histogram_loudness(HISTOGRAM_SIZE - 1)
+        };
+
+        (percentile(0.95) - percentile(0.10)) as f32
+    }
+}
+
+impl<S: Sample> Processor for LoudnessMeter<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        let channels = self.channels();
+        let weights = std::mem::take(&mut self.weights);
+
+        *self = Self::with_channels(self.sample_rate, channels);
+        self.weights = weights;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.reset();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        for channel in 0..usize::min(S::CHANNELS, self.channels()) {
+            self.push(channel, input.channel(channel).to_f64());
+        }
+
+        self.advance();
+        input
+    }
+}
+
+/* Helpers */
+
+pub(crate) fn energy_to_loudness(energy: f64) -> f64 {
+    if energy > 0.0 {
+        -0.691 + 10.0 * f64::log10(energy)
+    } else {
+        f64::NEG_INFINITY
+    }
+}
+
+fn histogram_index(loudness: f64) -> usize {
+    let index = f64::floor((loudness - HISTOGRAM_MIN) / HISTOGRAM_STEP);
+    f64::clamp(index, 0.0, (HISTOGRAM_SIZE - 1) as f64) as usize
+}
+
+fn histogram_loudness(index: usize) -> f64 {
+    HISTOGRAM_MIN + (index as f64 + 0.5) * HISTOGRAM_STEP
+}
+
+/// Block loudness histogram, keeping the exact energy of each bin so gated means aren't quantised
+struct Histogram {
+    counts: Vec<u64>,
+    energies: Vec<f64>,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; HISTOGRAM_SIZE],
+            energies: vec![0.0; HISTOGRAM_SIZE],
+        }
+    }
+
+    fn add(&mut self, energy: f64) {
+        let loudness = energy_to_loudness(energy);
+        if loudness >= HISTOGRAM_MIN {
+            let index = histogram_index(loudness);
+            self.counts[index] += 1;
+            self.energies[index] += energy;
+        }
+    }
+
+    /// Total energy and block count of blocks above `threshold` LUFS
+    fn gated_energy(&self, threshold: f64) -> (f64, u64) {
+        let start = histogram_index(threshold);
+        let energy = self.energies[start..].iter().sum();
+        let count = self.counts[start..].iter().sum();
+
+        (energy, count)
+    }
+}
+
+/// The two stage BS.1770 K-weighting filter, a high shelf followed by a high pass
+#[derive(Copy, Clone)]
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        use std::f64::consts::PI;
+
+        let rate = sample_rate as f64;
+
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = f64::tan(PI * f0 / rate);
+        let vh = f64::powf(10.0, gain / 20.0);
+        let vb = f64::powf(vh, 0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        let shelf = Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]
+        );
+
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = f64::tan(PI * f0 / rate);
+        let a0 = 1.0 + k / q + k * k;
+
+        let highpass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]
+        );
+
+        Self {
+            stages: [shelf, highpass]
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.stages[0].process(x);
+        self.stages[1].process(y)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
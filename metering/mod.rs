@@ -0,0 +1,18 @@
+mod peak;
+mod rms;
+mod loudness;
+mod true_peak;
+
+pub use peak::*;
+pub use rms::*;
+pub use loudness::*;
+pub use true_peak::*;
+
+/// Linear gain to decibels, with silence floored at -144 dB
+pub(crate) fn gain_to_dbfs(gain: f64) -> f32 {
+    if gain > 0.0 {
+        f32::max(20.0 * f64::log10(gain) as f32, -144.0)
+    } else {
+        -144.0
+    }
+}
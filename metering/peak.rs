@@ -0,0 +1,108 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::traits::*;
+use crate::metering::*;
+
+/// Sample peak meter
+///
+/// Tracks the maximum absolute sample value of each channel since the last
+/// reset, along with a level that falls at a fixed rate for display.
+pub struct PeakMeter<S: Sample> {
+    sample_rate: u32,
+    fall: f64,
+    decay: f64,
+    peaks: Vec<f64>,
+    levels: Vec<f64>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> PeakMeter<S> {
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_channels(sample_rate, S::CHANNELS)
+    }
+
+    /// Meter for frames of `channels` samples passed to `process_frame`
+    pub fn with_channels(sample_rate: u32, channels: usize) -> Self {
+        let mut meter = Self {
+            sample_rate,
+            fall: 20.0,
+            decay: 1.0,
+            peaks: vec![0.0; channels],
+            levels: vec![0.0; channels],
+            _phantom: PhantomData,
+        };
+
+        meter.set_fall(20.0);
+        meter
+    }
+
+    pub fn channels(&self) -> usize {
+        self.peaks.len()
+    }
+
+    /// Rate in dB per second at which `level` falls
+    pub fn set_fall(&mut self, db_per_second: f32) {
+        self.fall = f64::max(db_per_second as f64, 0.0);
+        self.decay = f64::powf(10.0, -self.fall / 20.0 / self.sample_rate as f64);
+    }
+
+    pub fn process_frame(&mut self, frame: &[S::Float]) {
+        for (channel, x) in frame.iter().enumerate().take(self.channels()) {
+            self.push(channel, x.to_f64());
+        }
+    }
+
+    fn push(&mut self, channel: usize, x: f64) {
+        let x = f64::abs(x);
+
+        self.peaks[channel] = f64::max(self.peaks[channel], x);
+        self.levels[channel] = f64::max(self.levels[channel] * self.decay, x);
+    }
+
+    /// Maximum absolute sample value since the last reset
+    pub fn peak(&self, channel: usize) -> f32 {
+        self.peaks[channel] as f32
+    }
+
+    pub fn peak_db(&self, channel: usize) -> f32 {
+        gain_to_dbfs(self.peaks[channel])
+    }
+
+    /// Maximum peak across all channels in dBFS
+    pub fn max_peak_db(&self) -> f32 {
+        gain_to_dbfs(self.peaks.iter().fold(0.0, | a, b | f64::max(a, *b)))
+    }
+
+    /// Falling peak level for display
+    pub fn level(&self, channel: usize) -> f32 {
+        self.levels[channel] as f32
+    }
+
+    pub fn level_db(&self, channel: usize) -> f32 {
+        gain_to_dbfs(self.levels[channel])
+    }
+}
+
+impl<S: Sample> Processor for PeakMeter<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.peaks.fill(0.0);
+        self.levels.fill(0.0);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.set_fall(self.fall as f32);
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        for channel in 0..usize::min(S::CHANNELS, self.channels()) {
+            self.push(channel, input.channel(channel).to_f64());
+        }
+
+        input
+    }
+}
@@ -0,0 +1,117 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::traits::*;
+use crate::metering::*;
+
+/// Windowed RMS meter
+///
+/// Keeps a running sum of squares over a sliding rectangular window for each channel.
+pub struct RmsMeter<S: Sample> {
+    sample_rate: u32,
+    window: f32,
+    length: usize,
+    index: usize,
+    history: Vec<Vec<f64>>,
+    sums: Vec<f64>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> RmsMeter<S> {
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_channels(sample_rate, S::CHANNELS)
+    }
+
+    /// Meter for frames of `channels` samples passed to `process_frame`
+    pub fn with_channels(sample_rate: u32, channels: usize) -> Self {
+        let mut meter = Self {
+            sample_rate,
+            window: 0.3,
+            length: 1,
+            index: 0,
+            history: vec![Vec::new(); channels],
+            sums: vec![0.0; channels],
+            _phantom: PhantomData,
+        };
+
+        meter.set_window(0.3);
+        meter
+    }
+
+    pub fn channels(&self) -> usize {
+        self.sums.len()
+    }
+
+    /// Window length in seconds
+    pub fn set_window(&mut self, seconds: f32) {
+        self.window = f32::max(seconds, 0.0);
+        self.length = usize::max((self.window as f64 * self.sample_rate as f64).round() as usize, 1);
+
+        for history in &mut self.history {
+            history.clear();
+            history.resize(self.length, 0.0);
+        }
+
+        self.sums.fill(0.0);
+        self.index = 0;
+    }
+
+    pub fn process_frame(&mut self, frame: &[S::Float]) {
+        for (channel, x) in frame.iter().enumerate().take(self.channels()) {
+            self.push(channel, x.to_f64());
+        }
+
+        self.advance();
+    }
+
+    fn push(&mut self, channel: usize, x: f64) {
+        let square = x * x;
+        let slot = &mut self.history[channel][self.index];
+
+        self.sums[channel] += square - *slot;
+        *slot = square;
+    }
+
+    fn advance(&mut self) {
+        self.index += 1;
+
+        /* Recompute the sums once per window to stop rounding error building up */
+        if self.index == self.length {
+            self.index = 0;
+            for (sum, history) in self.sums.iter_mut().zip(&self.history) {
+                *sum = history.iter().sum();
+            }
+        }
+    }
+
+    pub fn rms(&self, channel: usize) -> f32 {
+        f64::sqrt(f64::max(self.sums[channel], 0.0) / self.length as f64) as f32
+    }
+
+    pub fn rms_db(&self, channel: usize) -> f32 {
+        gain_to_dbfs(self.rms(channel) as f64)
+    }
+}
+
+impl<S: Sample> Processor for RmsMeter<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.set_window(self.window);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.set_window(self.window);
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        for channel in 0..usize::min(S::CHANNELS, self.channels()) {
+            self.push(channel, input.channel(channel).to_f64());
+        }
+
+        self.advance();
+        input
+    }
+}
@@ -0,0 +1,136 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::traits::*;
+use crate::spectral::*;
+use crate::metering::*;
+
+const OVERSAMPLING: usize = 4;
+const PHASE_TAPS: usize = 12;
+
+/// BS.1770 true peak meter
+///
+/// Estimates the inter-sample peak of each channel by upsampling four times
+/// with a 48 tap polyphase interpolator.
+pub struct TruePeakMeter<S: Sample> {
+    phases: [[f64; PHASE_TAPS]; OVERSAMPLING],
+    history: Vec<[f64; PHASE_TAPS]>,
+    index: usize,
+    peaks: Vec<f64>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> TruePeakMeter<S> {
+    pub fn new() -> Self {
+        Self::with_channels(S::CHANNELS)
+    }
+
+    /// Meter for frames of `channels` samples passed to `process_frame`
+    pub fn with_channels(channels: usize) -> Self {
+        let length = OVERSAMPLING * PHASE_TAPS;
+        let window: Vec<f64> = Window::Kaiser(8.0).generate_symmetric(length);
+        let center = (length - 1) as f64 / 2.0;
+
+        /* Windowed sinc with its cutoff at the original Nyquist frequency */
+        let mut phases = [[0.0; PHASE_TAPS]; OVERSAMPLING];
+        for (n, w) in window.iter().enumerate() {
+            let x = (n as f64 - center) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 { 1.0 } else { f64::sin(std::f64::consts::PI * x) / (std::f64::consts::PI * x) };
+            phases[n % OVERSAMPLING][n / OVERSAMPLING] = sinc * w;
+        }
+
+        for phase in &mut phases {
+            let sum: f64 = phase.iter().sum();
+            for tap in phase.iter_mut() {
+                *tap /= sum;
+            }
+        }
+
+        Self {
+            phases,
+            history: vec![[0.0; PHASE_TAPS]; channels],
+            index: 0,
+            peaks: vec![0.0; channels],
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.peaks.len()
+    }
+
+    pub fn process_frame(&mut self, frame: &[S::Float]) {
+        for (channel, x) in frame.iter().enumerate().take(self.channels()) {
+            self.push(channel, x.to_f64());
+        }
+
+        self.advance();
+    }
+
+    fn push(&mut self, channel: usize, x: f64) {
+        let history = &mut self.history[channel];
+        history[self.index] = x;
+
+        let mut peak = f64::abs(x);
+        for phase in &self.phases {
+            let mut y = 0.0;
+            for (k, tap) in phase.iter().enumerate() {
+                y += tap * history[(self.index + PHASE_TAPS - k) % PHASE_TAPS];
+            }
+
+            peak = f64::max(peak, f64::abs(y));
+        }
+
+        self.peaks[channel] = f64::max(self.peaks[channel], peak);
+    }
+
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % PHASE_TAPS;
+    }
+
+    /// Maximum true peak since the last reset
+    pub fn true_peak(&self, channel: usize) -> f32 {
+        self.peaks[channel] as f32
+    }
+
+    /// Maximum true peak since the last reset in dBTP
+    pub fn true_peak_db(&self, channel: usize) -> f32 {
+        gain_to_dbfs(self.peaks[channel])
+    }
+
+    /// Maximum true peak across all channels in dBTP
+    pub fn max_true_peak_db(&self) -> f32 {
+        gain_to_dbfs(self.peaks.iter().fold(0.0, | a, b | f64::max(a, *b)))
+    }
+}
+
+impl<S: Sample> Default for TruePeakMeter<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Sample> Processor for TruePeakMeter<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        for history in &mut self.history {
+            *history = [0.0; PHASE_TAPS];
+        }
+
+        self.peaks.fill(0.0);
+        self.index = 0;
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        for channel in 0..usize::min(S::CHANNELS, self.channels()) {
+            self.push(channel, input.channel(channel).to_f64());
+        }
+
+        self.advance();
+        input
+    }
+}
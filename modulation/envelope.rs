@@ -0,0 +1,441 @@
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/// Shape of a segment from its start level to its target
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Curve {
+    Linear,
+    /// Exponential approach, fast then slow for positive amounts and slow then fast for negative ones
+    Exponential(f32),
+    /// Progress raised to a power, slow then fast above 1 and fast then slow below it
+    Power(f32),
+}
+
+impl Curve {
+    /// Progress from 0 to 1 at time `t` from 0 to 1
+    fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Curve::Linear => t,
+            Curve::Exponential(k) if k.abs() > 1e-3 => (1.0 - f32::exp(-k * t)) / (1.0 - f32::exp(-k)),
+            Curve::Exponential(_) => t,
+            Curve::Power(p) => f32::powf(t, f32::max(p, 1e-3)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Segment {
+    /// Level at the end of the segment
+    pub level: f32,
+    /// Duration in seconds
+    pub time: f32,
+    pub curve: Curve,
+}
+
+impl Segment {
+    pub fn new(level: f32, time: f32, curve: Curve) -> Self {
+        Self { level, time, curve }
+    }
+}
+
+/// What a note does while another is held
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Trigger {
+    /// Restart from the current level
+    Retrigger,
+    /// Keep running until every note is released
+    Legato,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Stage {
+    Idle,
+    Running(usize),
+    Holding(usize),
+}
+
+/// Multi-segment envelope with sustain and loop points
+///
+/// Segments run in order from the current level. While a note is held the
+/// envelope holds at the end of the sustain segment, or cycles through the
+/// loop segments. Releasing the note continues with the segment after them.
+pub struct Envelope {
+    segments: Vec<Segment>,
+    sustain: Option<usize>,
+    loop_segments: Option<(usize, usize)>,
+    trigger: Trigger,
+    velocity_sensitivity: f32,
+
+    sample_rate: u32,
+    stage: Stage,
+    level: f32,
+    start: f32,
+    t: f32,
+    dt: f32,
+    held: usize,
+    velocity: f32,
+}
+
+impl Envelope {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Self {
+            segments,
+            sustain: None,
+            loop_segments: None,
+            trigger: Trigger::Retrigger,
+            velocity_sensitivity: 0.0,
+            sample_rate: 44100,
+            stage: Stage::Idle,
+            level: 0.0,
+            start: 0.0,
+            t: 0.0,
+            dt: 0.0,
+            held: 0,
+            velocity: 1.0,
+        }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Replace the segments, keeping the current stage if it still exists
+    pub fn set_segments(&mut self, segments: Vec<Segment>) {
+        self.segments = segments;
+
+        match self.stage {
+            Stage::Running(i) | Stage::Holding(i) if i >= self.segments.len() => self.stage = Stage::Idle,
+            _ => ()
+        }
+    }
+
+    /// Hold at the end of segment `index` while the note is held
+    pub fn set_sustain(&mut self, index: Option<usize>) {
+        self.sustain = index;
+    }
+
+    /// Cycle from segment `start` to `end` inclusive while the note is held
+    pub fn set_loop(&mut self, segments: Option<(usize, usize)>) {
+        self.loop_segments = segments.filter(| (start, end) | start <= end);
+    }
+
+    pub fn set_trigger(&mut self, trigger: Trigger) {
+        self.trigger = trigger;
+    }
+
+    /// How much note pressure scales the output, from 0 to 1
+    pub fn set_velocity_sensitivity(&mut self, sensitivity: f32) {
+        self.velocity_sensitivity = f32::clamp(sensitivity, 0.0, 1.0);
+    }
+
+    /// Current level before velocity scaling
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// The envelope hasn't finished its last segment
+    pub fn active(&self) -> bool {
+        self.stage != Stage::Idle
+    }
+
+    /// Last segment before the release
+    fn release_point(&self) -> Option<usize> {
+        match (self.loop_segments, self.sustain) {
+            (Some((_, end)), _) => Some(end),
+            (None, sustain) => sustain,
+        }
+    }
+
+    fn start_segment(&mut self, index: usize) {
+        let mut index = index;
+
+        /* Zero length segments complete immediately, bounded in case a loop has no length */
+        for _ in 0..=self.segments.len() {
+            let segment = match self.segments.get(index) {
+                Some(segment) => *segment,
+                None => {
+                    self.stage = Stage::Idle;
+                    return;
+                }
+            };
+
+            let samples = segment.time * self.sample_rate as f32;
+            if samples >= 1.0 {
+                self.stage = Stage::Running(index);
+                self.start = self.level;
+                self.t = 0.0;
+                self.dt = 1.0 / samples;
+                return;
+            }
+
+            self.level = segment.level;
+            match self.next_segment(index) {
+                Some(next) => index = next,
+                None => return
+            }
+        }
+
+        self.stage = Stage::Holding(index);
+    }
+
+    /// Segment to run after `index` finishes, or `None` if the envelope holds
+    fn next_segment(&mut self, index: usize) -> Option<usize> {
+        if self.held > 0 {
+            if let Some((start, end)) = self.loop_segments {
+                if index == end {
+                    return Some(start);
+                }
+            } else if self.sustain == Some(index) {
+                self.stage = Stage::Holding(index);
+                return None;
+            }
+        }
+
+        Some(index + 1)
+    }
+
+    fn release(&mut self) {
+        let point = match self.release_point() {
+            Some(point) => point,
+            None => return
+        };
+
+        match self.stage {
+            Stage::Running(i) | Stage::Holding(i) if i <= point => self.start_segment(point + 1),
+            _ => ()
+        }
+    }
+}
+
+impl Generator for Envelope {
+    type Output = f32;
+
+    fn reset(&mut self) {
+        self.stage = Stage::Idle;
+        self.level = 0.0;
+        self.held = 0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let output = self.level * (1.0 - self.velocity_sensitivity + self.velocity_sensitivity * self.velocity);
+
+        if let Stage::Running(index) = self.stage {
+            let segment = self.segments[index];
+            self.t += self.dt;
+
+            if self.t >= 1.0 {
+                self.level = segment.level;
+                if let Some(next) = self.next_segment(index) {
+                    self.start_segment(next);
+                }
+            } else {
+                self.level = self.start + (segment.level - self.start) * segment.curve.apply(self.t);
+            }
+        }
+
+        output
+    }
+}
+
+impl Playable for Envelope {
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.held += 1;
+
+        if self.held == 1 || self.trigger == Trigger::Retrigger || self.stage == Stage::Idle {
+            self.velocity = f32::clamp(pressure, 0.0, 1.0);
+            self.start_segment(0);
+        }
+    }
+
+    fn note_off(&mut self) {
+        self.held = self.held.saturating_sub(1);
+
+        if self.held == 0 {
+            self.release();
+        }
+    }
+}
+
+/// Attack, hold, decay, sustain, release envelope
+///
+/// Attack is linear, decay and release are exponential. Hold is zero for a plain ADSR.
+pub struct Adsr {
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    envelope: Envelope,
+}
+
+impl Adsr {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        Self::ahdsr(attack, 0.0, decay, sustain, release)
+    }
+
+    pub fn ahdsr(attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        let mut envelope = Envelope::new(Vec::new());
+        envelope.set_sustain(Some(2));
+
+        let mut adsr = Self {
+            attack,
+            hold,
+            decay,
+            sustain,
+            release,
+            envelope,
+        };
+
+        adsr.update();
+        adsr
+    }
+
+    fn update(&mut self) {
+        self.envelope.set_segments(vec![
+            Segment::new(1.0, self.attack, Curve::Linear),
+            Segment::new(1.0, self.hold, Curve::Linear),
+            Segment::new(self.sustain, self.decay, Curve::Exponential(5.0)),
+            Segment::new(0.0, self.release, Curve::Exponential(5.0)),
+        ]);
+    }
+
+    /// Attack time in seconds
+    pub fn set_attack(&mut self, seconds: f32) {
+        self.attack = f32::max(seconds, 0.0);
+        self.update();
+    }
+
+    /// Time in seconds at full level before the decay
+    pub fn set_hold(&mut self, seconds: f32) {
+        self.hold = f32::max(seconds, 0.0);
+        self.update();
+    }
+
+    /// Decay time in seconds
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = f32::max(seconds, 0.0);
+        self.update();
+    }
+
+    /// Sustain level from 0 to 1
+    pub fn set_sustain(&mut self, level: f32) {
+        self.sustain = f32::clamp(level, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Release time in seconds
+    pub fn set_release(&mut self, seconds: f32) {
+        self.release = f32::max(seconds, 0.0);
+        self.update();
+    }
+
+    pub fn envelope(&mut self) -> &mut Envelope {
+        &mut self.envelope
+    }
+
+    pub fn active(&self) -> bool {
+        self.envelope.active()
+    }
+}
+
+impl Generator for Adsr {
+    type Output = f32;
+
+    fn reset(&mut self) {
+        self.envelope.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.envelope.prepare(sample_rate, block_size);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        self.envelope.generate()
+    }
+}
+
+impl Playable for Adsr {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.envelope.note_on(pitch, pressure);
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+}
+
+impl Param for Adsr {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "attack" => self.set_attack(value),
+            "hold" => self.set_hold(value),
+            "decay" => self.set_decay(value),
+            "sustain" => self.set_sustain(value),
+            "release" => self.set_release(value),
+            _ => ()
+        }
+    }
+}
+
+/// A playable generator shaped by an envelope, both receiving every note event
+pub struct Enveloped<G, E> {
+    pub generator: G,
+    pub envelope: E,
+}
+
+impl<G, E> Enveloped<G, E> {
+    pub fn from(generator: G, envelope: E) -> Self {
+        Self { generator, envelope }
+    }
+}
+
+impl<S, G, E> Generator for Enveloped<G, E>
+    where
+        S: Sample,
+        G: Generator<Output = S>,
+        E: Generator<Output = f32> {
+
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.generator.reset();
+        self.envelope.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.generator.prepare(sample_rate, block_size);
+        self.envelope.prepare(sample_rate, block_size);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let gain = self.envelope.generate();
+        self.generator.generate() * S::Float::from(gain)
+    }
+}
+
+impl<G: Playable, E: Playable> Playable for Enveloped<G, E> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.generator.note_on(pitch, pressure);
+        self.envelope.note_on(pitch, pressure);
+    }
+
+    fn note_off(&mut self) {
+        self.generator.note_off();
+        self.envelope.note_off();
+    }
+
+    fn bend(&mut self, pitch: f32) {
+        self.generator.bend(pitch);
+        self.envelope.bend(pitch);
+    }
+
+    fn set_pressure(&mut self, pressure: f32) {
+        self.generator.set_pressure(pressure);
+        self.envelope.set_pressure(pressure);
+    }
+}
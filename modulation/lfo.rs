@@ -0,0 +1,309 @@
+use crate::math::random::*;
+use crate::routing::param::*;
+use crate::time::*;
+use crate::traits::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    SawUp,
+    SawDown,
+    Square,
+    /// A new random value every cycle
+    SampleAndHold,
+    /// Random values every cycle with cosine interpolation between them
+    SmoothRandom,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Modifier {
+    Straight,
+    Dotted,
+    Triplet,
+}
+
+/// A note length for tempo synced rates, where a beat is a quarter note
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Division {
+    pub numerator: u32,
+    pub denominator: u32,
+    pub modifier: Modifier,
+}
+
+impl Division {
+    /// Straight note length such as `Division::new(1, 4)` for a quarter note
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator,
+            denominator,
+            modifier: Modifier::Straight,
+        }
+    }
+
+    pub fn dotted(self) -> Self {
+        Self { modifier: Modifier::Dotted, ..self }
+    }
+
+    pub fn triplet(self) -> Self {
+        Self { modifier: Modifier::Triplet, ..self }
+    }
+
+    /// Length in beats
+    pub fn beats(&self) -> f64 {
+        let beats = 4.0 * self.numerator as f64 / u32::max(self.denominator, 1) as f64;
+
+        match self.modifier {
+            Modifier::Straight => beats,
+            Modifier::Dotted => beats * 1.5,
+            Modifier::Triplet => beats * 2.0 / 3.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LfoRate {
+    Hz(f32),
+    Sync(Division),
+}
+
+/// Low frequency oscillator producing control values
+///
+/// Runs free in Hz or locked to the host transport from `set_time`. Output is
+/// from -1 to 1, or 0 to 1 when unipolar, and can drive any `Param` through
+/// `modulate`.
+pub struct Lfo {
+    shape: LfoShape,
+    rate: LfoRate,
+    offset: f32,
+    retrigger: bool,
+    unipolar: bool,
+    fade: f32,
+    seed: u64,
+
+    sample_rate: u32,
+    block_size: usize,
+    phase: f64,
+    beat: f64,
+    beats_per_sample: f64,
+    trigger_beat: f64,
+    fade_level: f32,
+    fade_step: f32,
+    random: Random,
+    held: [f32; 2],
+}
+
+impl Lfo {
+    pub fn new(shape: LfoShape, rate: LfoRate) -> Self {
+        let mut lfo = Self {
+            shape,
+            rate,
+            offset: 0.0,
+            retrigger: false,
+            unipolar: false,
+            fade: 0.0,
+            seed: 0,
+            sample_rate: 44100,
+            block_size: 1,
+            phase: 0.0,
+            beat: 0.0,
+            beats_per_sample: 0.0,
+            trigger_beat: 0.0,
+            fade_level: 1.0,
+            fade_step: 0.0,
+            random: Random::new(0),
+            held: [0.0; 2],
+        };
+
+        lfo.reset();
+        lfo
+    }
+
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+    }
+
+    /// Phase offset in cycles
+    pub fn set_offset(&mut self, offset: f32) {
+        self.offset = offset - f32::floor(offset);
+    }
+
+    /// Restart the cycle and fade-in on every note
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+
+    /// Output from 0 to 1 instead of -1 to 1
+    pub fn set_unipolar(&mut self, unipolar: bool) {
+        self.unipolar = unipolar;
+    }
+
+    /// Seconds to fade in from zero after a trigger
+    pub fn set_fade(&mut self, seconds: f32) {
+        self.fade = f32::max(seconds, 0.0);
+    }
+
+    /// Seed of the random shapes
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.random = Random::new(seed);
+    }
+
+    /// Follow the transport for the next block
+    pub fn set_time(&mut self, time: &TimeMessage) {
+        self.beat = time.start();
+        self.beats_per_sample = time.length() / self.block_size as f64;
+    }
+
+    /// Restart the cycle and fade-in
+    pub fn trigger(&mut self) {
+        self.phase = 0.0;
+        self.trigger_beat = self.beat;
+
+        if self.fade > 0.0 {
+            self.fade_level = 0.0;
+            self.fade_step = 1.0 / (self.fade * self.sample_rate as f32);
+        } else {
+            self.fade_level = 1.0;
+        }
+    }
+
+    /// Set `name` on `target` to `base` plus the next value scaled by `depth`
+    pub fn modulate<P: Param>(&mut self, target: &mut P, name: &'static str, base: f32, depth: f32) {
+        let value = self.generate();
+        target.set_param(name, base + value * depth);
+    }
+
+    fn next_random(&mut self) {
+        self.held[0] = self.held[1];
+        self.held[1] = self.random.bipolar() as f32;
+    }
+
+    fn value(&self, phase: f32) -> f32 {
+        use std::f32::consts::PI;
+
+        match self.shape {
+            LfoShape::Sine => f32::sin(2.0 * PI * phase),
+            LfoShape::Triangle => {
+                if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - 4.0 * phase
+                } else {
+                    4.0 * phase - 4.0
+                }
+            },
+            LfoShape::SawUp => 2.0 * phase - 1.0,
+            LfoShape::SawDown => 1.0 - 2.0 * phase,
+            LfoShape::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::SampleAndHold => self.held[1],
+            LfoShape::SmoothRandom => {
+                let t = 0.5 - 0.5 * f32::cos(PI * phase);
+                self.held[0] + (self.held[1] - self.held[0]) * t
+            }
+        }
+    }
+}
+
+impl Generator for Lfo {
+    type Output = f32;
+
+    fn reset(&mut self) {
+        self.random = Random::new(self.seed);
+        self.held = [self.random.bipolar() as f32, self.random.bipolar() as f32];
+        self.beat = 0.0;
+        self.trigger_beat = 0.0;
+        self.trigger();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.block_size = usize::max(block_size, 1);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let phase = (self.phase + self.offset as f64).rem_euclid(1.0) as f32;
+        let output = self.value(phase) * self.fade_level;
+
+        self.fade_level = f32::min(self.fade_level + self.fade_step, 1.0);
+        self.beat += self.beats_per_sample;
+
+        let next = match self.rate {
+            LfoRate::Hz(hz) => self.phase + hz as f64 / self.sample_rate as f64,
+            LfoRate::Sync(division) => (self.beat - self.trigger_beat) / division.beats(),
+        };
+
+        /* A new cycle, or a transport jump, draws the next random value, counted on the offset phase the shapes read */
+        let offset = self.offset as f64;
+        if (next + offset).floor() != (self.phase + offset).floor() {
+            self.next_random();
+        }
+
+        self.phase = match self.rate {
+            LfoRate::Hz(_) => next.rem_euclid(1.0),
+            LfoRate::Sync(_) => next,
+        };
+
+        if self.unipolar {
+            output * 0.5 + 0.5
+        } else {
+            output
+        }
+    }
+}
+
+impl Phased for Lfo {
+    fn get_phase(&self) -> f32 {
+        self.phase.rem_euclid(1.0) as f32
+    }
+
+    fn set_phase(&mut self, phase: f32) {
+        let phase = phase.rem_euclid(1.0) as f64;
+
+        match self.rate {
+            LfoRate::Hz(_) => self.phase = phase,
+            LfoRate::Sync(division) => {
+                self.trigger_beat = self.beat - phase * division.beats();
+                self.phase = phase;
+            }
+        }
+    }
+}
+
+impl Playable for Lfo {
+    fn note_on(&mut self, _pitch: f32, _pressure: f32) {
+        if self.retrigger {
+            self.trigger();
+        }
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl Param for Lfo {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            /* Only retunes a free running LFO, a synced one keeps its division */
+            "rate" => {
+                if let LfoRate::Hz(_) = self.rate {
+                    self.rate = LfoRate::Hz(value);
+                }
+            },
+            "offset" => self.set_offset(value),
+            "fade" => self.set_fade(value),
+            _ => ()
+        }
+    }
+}
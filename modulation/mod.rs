@@ -0,0 +1,5 @@
+mod lfo;
+mod envelope;
+
+pub use lfo::*;
+pub use envelope::*;
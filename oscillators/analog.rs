@@ -0,0 +1,368 @@
+use crate::float::*;
+use crate::traits::*;
+use crate::oscillators::blep::*;
+use crate::oscillators::sync::*;
+
+/// A point in the cycle where a waveform jumps or changes slope
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Breakpoint<F: Float> {
+    /// Position in the cycle from 0 to 1
+    pub phase: F,
+    /// Change in value
+    pub jump: F,
+    /// Change in slope per cycle
+    pub slope: F,
+}
+
+/// A single cycle of a waveform that `Oscillator` can band limit
+pub trait Waveform<F: Float> {
+    /// Naive value at `phase`, from 0 to 1
+    fn value(&self, phase: F) -> F;
+
+    /// Derivative of the value with respect to phase
+    fn slope(&self, phase: F) -> F;
+
+    /// Discontinuities in the cycle
+    fn breakpoints(&self) -> [Option<Breakpoint<F>>; 2];
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SawWave;
+
+impl<F: Float> Waveform<F> for SawWave {
+    fn value(&self, phase: F) -> F {
+        phase * F::from(2.0) - F::from(1.0)
+    }
+
+    fn slope(&self, _phase: F) -> F {
+        F::from(2.0)
+    }
+
+    fn breakpoints(&self) -> [Option<Breakpoint<F>>; 2] {
+        [Some(Breakpoint { phase: F::ZERO, jump: F::from(-2.0), slope: F::ZERO }), None]
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct PulseWave<F: Float> {
+    pub width: F,
+}
+
+impl<F: Float> Default for PulseWave<F> {
+    fn default() -> Self {
+        Self { width: F::from(0.5) }
+    }
+}
+
+impl<F: Float> Waveform<F> for PulseWave<F> {
+    fn value(&self, phase: F) -> F {
+        if phase < self.width { F::from(1.0) } else { F::from(-1.0) }
+    }
+
+    fn slope(&self, _phase: F) -> F {
+        F::ZERO
+    }
+
+    fn breakpoints(&self) -> [Option<Breakpoint<F>>; 2] {
+        [
+            Some(Breakpoint { phase: F::ZERO, jump: F::from(2.0), slope: F::ZERO }),
+            Some(Breakpoint { phase: self.width, jump: F::from(-2.0), slope: F::ZERO }),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct TriangleWave;
+
+impl<F: Float> Waveform<F> for TriangleWave {
+    fn value(&self, phase: F) -> F {
+        if phase < F::from(0.5) {
+            phase * F::from(4.0) - F::from(1.0)
+        } else {
+            F::from(3.0) - phase * F::from(4.0)
+        }
+    }
+
+    fn slope(&self, phase: F) -> F {
+        if phase < F::from(0.5) { F::from(4.0) } else { F::from(-4.0) }
+    }
+
+    fn breakpoints(&self) -> [Option<Breakpoint<F>>; 2] {
+        [
+            Some(Breakpoint { phase: F::ZERO, jump: F::ZERO, slope: F::from(8.0) }),
+            Some(Breakpoint { phase: F::from(0.5), jump: F::ZERO, slope: F::from(-8.0) }),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SineWave;
+
+impl<F: Float> Waveform<F> for SineWave {
+    fn value(&self, phase: F) -> F {
+        (phase * F::from(2.0) * F::PI).sin()
+    }
+
+    fn slope(&self, phase: F) -> F {
+        (phase * F::from(2.0) * F::PI).cos() * F::from(2.0) * F::PI
+    }
+
+    fn breakpoints(&self) -> [Option<Breakpoint<F>>; 2] {
+        [None, None]
+    }
+}
+
+pub type Saw<F> = Oscillator<F, SawWave>;
+pub type Pulse<F> = Oscillator<F, PulseWave<F>>;
+pub type Triangle<F> = Oscillator<F, TriangleWave>;
+pub type Sine<F> = Oscillator<F, SineWave>;
+
+/// Band limited oscillator
+///
+/// Jumps are corrected with PolyBLEP and changes in slope with PolyBLAMP
+/// residuals, including the discontinuities introduced by hard sync.
+#[derive(Clone)]
+pub struct Oscillator<F: Float, W: Waveform<F>> {
+    waveform: W,
+    phase: F,
+    increment: F,
+    pitch: f32,
+    sample_rate: u32,
+    pending: F,
+    sync: Option<F>,
+}
+
+impl<F: Float, W: Waveform<F> + Default> Oscillator<F, W> {
+    pub fn new() -> Self {
+        Self::from(W::default())
+    }
+}
+
+impl<F: Float, W: Waveform<F> + Default> Default for Oscillator<F, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float, W: Waveform<F>> Oscillator<F, W> {
+    pub fn from(waveform: W) -> Self {
+        let mut oscillator = Self {
+            waveform,
+            phase: F::ZERO,
+            increment: F::ZERO,
+            pitch: 440.0,
+            sample_rate: 44100,
+            pending: F::ZERO,
+            sync: None,
+        };
+
+        oscillator.set_pitch(440.0);
+        oscillator
+    }
+
+    pub fn waveform(&self) -> &W {
+        &self.waveform
+    }
+
+    pub fn waveform_mut(&mut self) -> &mut W {
+        &mut self.waveform
+    }
+
+    /// Correction for breakpoints in the phase range `(start, end]`, `offset` samples after the current sample
+    fn breakpoints(&mut self, start: F, end: F, offset: F) -> F {
+        let mut correction = F::ZERO;
+
+        for breakpoint in self.waveform.breakpoints().iter().flatten() {
+            for position in [breakpoint.phase, breakpoint.phase + F::from(1.0)] {
+                if position > start && position <= end {
+                    let d = offset + (position - start) / self.increment;
+                    correction += self.residual(d, breakpoint.jump, breakpoint.slope * self.increment);
+                }
+            }
+        }
+
+        correction
+    }
+
+    /// Queue the residual for the next sample and return the residual for this one
+    fn residual(&mut self, d: F, jump: F, slope: F) -> F {
+        self.pending += jump * blep_after(d) + slope * blamp_after(d);
+        jump * blep_before(d) + slope * blamp_before(d)
+    }
+}
+
+impl<F: Float> Oscillator<F, PulseWave<F>> {
+    pub fn width(&self) -> F {
+        self.waveform.width
+    }
+
+    /// Pulse width from 0 to 1, can be modulated while running
+    pub fn set_width(&mut self, width: F) {
+        self.waveform.width = width.max(F::from(0.01)).min(F::from(0.99));
+    }
+}
+
+impl<F: Float, W: Waveform<F>> Generator for Oscillator<F, W> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phase = F::ZERO;
+        self.pending = F::ZERO;
+        self.sync = None;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.set_pitch(self.pitch);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let mut output = self.waveform.value(self.phase) + self.pending;
+        self.pending = F::ZERO;
+
+        if self.increment <= F::ZERO {
+            return output;
+        }
+
+        let one = F::from(1.0);
+
+        match self.sync.take() {
+            Some(d) => {
+                /* Run up to the sync point, jump back to the start of the cycle, then run on */
+                let position = self.phase + self.increment * d;
+                output += self.breakpoints(self.phase, position, F::ZERO);
+
+                let position = if position >= one { position - one } else { position };
+                let jump = self.waveform.value(F::ZERO) - self.waveform.value(position);
+                let slope = (self.waveform.slope(F::ZERO) - self.waveform.slope(position)) * self.increment;
+                output += self.residual(d, jump, slope);
+
+                let end = self.increment * (one - d);
+                output += self.breakpoints(F::ZERO, end, d);
+                self.phase = end;
+            },
+            None => {
+                let end = self.phase + self.increment;
+                output += self.breakpoints(self.phase, end, F::ZERO);
+                self.phase = if end >= one { end - one } else { end };
+            }
+        }
+
+        output
+    }
+}
+
+impl<F: Float, W: Waveform<F>> Pitched for Oscillator<F, W> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+
+        /* Keep below Nyquist so breakpoints are never skipped */
+        let increment = f32::clamp(hz / self.sample_rate as f32, 0.0, 0.5);
+        self.increment = F::from(increment);
+    }
+}
+
+impl<F: Float, W: Waveform<F>> Phased for Oscillator<F, W> {
+    fn get_phase(&self) -> f32 {
+        self.phase.to_f32()
+    }
+
+    fn set_phase(&mut self, phase: f32) {
+        self.phase = F::from(phase - f32::floor(phase));
+    }
+}
+
+impl<F: Float, W: Waveform<F>> Syncable for Oscillator<F, W> {
+    fn next_wrap(&self) -> Option<f32> {
+        let end = self.phase + self.increment;
+
+        if self.increment > F::ZERO && end >= F::from(1.0) {
+            Some(((F::from(1.0) - self.phase) / self.increment).to_f32())
+        } else {
+            None
+        }
+    }
+
+    fn sync(&mut self, fraction: f32) {
+        self.sync = Some(F::from(f32::clamp(fraction, 0.0, 1.0)));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fft::*;
+    use crate::spectral::*;
+
+    pub(crate) const SAMPLE_RATE: u32 = 44100;
+    pub(crate) const PITCH: f32 = 5000.0;
+
+    /// Energy outside the harmonics of `pitch` relative to the total, in dB
+    ///
+    /// A second of audio puts each harmonic of a whole number pitch on its own
+    /// bin. At 44.1 kHz, partials folded back from above Nyquist land 4.1 kHz
+    /// away from them, so everything off the harmonics is aliasing.
+    pub(crate) fn aliasing_db(samples: &[f64], pitch: f32) -> f64 {
+        let size = samples.len();
+        let mut fft = RealFft::<f64>::new(size);
+        let window = Window::Hann.generate::<f64>(size);
+        let input: Vec<f64> = samples.iter().zip(&window).map(| (x, w) | x * w).collect();
+        let mut spectrum = vec![Complex::ZERO; fft.bins()];
+        fft.forward(input.as_slice(), spectrum.as_mut_slice());
+
+        let hz_per_bin = SAMPLE_RATE as f64 / size as f64;
+        let mut total = 0.0;
+        let mut aliasing = 0.0;
+
+        for (k, bin) in spectrum.iter().enumerate().skip(1) {
+            let harmonic = k as f64 * hz_per_bin / pitch as f64;
+            let distance = (harmonic - harmonic.round()).abs() * pitch as f64 / hz_per_bin;
+
+            total += bin.norm_sqr();
+            if distance > 2.0 {
+                aliasing += bin.norm_sqr();
+            }
+        }
+
+        10.0 * f64::log10(aliasing / total)
+    }
+
+    /// One second at `PITCH` after a short settling time
+    pub(crate) fn render<G: Generator<Output = f64>>(generator: &mut G) -> Vec<f64> {
+        generator.prepare(SAMPLE_RATE, 64);
+        (0..SAMPLE_RATE as usize + 64).map(| _ | generator.generate()).skip(64).collect()
+    }
+
+    fn naive<W: Waveform<f64>>(waveform: W) -> Vec<f64> {
+        let increment = PITCH as f64 / SAMPLE_RATE as f64;
+        (64..SAMPLE_RATE as usize + 64)
+            .map(| n | waveform.value((n as f64 * increment).fract()))
+            .collect()
+    }
+
+    #[test]
+    fn saw_aliasing_is_reduced() {
+        let mut saw = Saw::<f64>::new();
+        saw.set_pitch(PITCH);
+
+        let band_limited = aliasing_db(&render(&mut saw), PITCH);
+        let naive = aliasing_db(&naive(SawWave), PITCH);
+        assert!(band_limited < -20.0, "saw aliasing at {} dB", band_limited);
+        assert!(band_limited < naive - 12.0, "saw aliasing at {} dB against {} dB naive", band_limited, naive);
+    }
+
+    #[test]
+    fn square_aliasing_is_reduced() {
+        let mut square = Pulse::<f64>::new();
+        square.set_pitch(PITCH);
+
+        let band_limited = aliasing_db(&render(&mut square), PITCH);
+        let naive = aliasing_db(&naive(PulseWave::default()), PITCH);
+        assert!(band_limited < -20.0, "square aliasing at {} dB", band_limited);
+        assert!(band_limited < naive - 12.0, "square aliasing at {} dB against {} dB naive", band_limited, naive);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::float::*;
+
+/* Two sample polynomial residuals for band limiting discontinuities
+
+   `d` is the fraction of a sample between the current sample and the
+   discontinuity that follows it. The `before` residual applies to the
+   current sample and the `after` residual to the next one. */
+
+/// Residual of a unit step before the discontinuity
+#[inline]
+pub fn blep_before<F: Float>(d: F) -> F {
+    let x = F::from(1.0) - d;
+    F::from(0.5) * x * x
+}
+
+/// Residual of a unit step after the discontinuity
+#[inline]
+pub fn blep_after<F: Float>(d: F) -> F {
+    F::ZERO - F::from(0.5) * d * d
+}
+
+/// Residual of a unit change in slope per sample before the discontinuity
+#[inline]
+pub fn blamp_before<F: Float>(d: F) -> F {
+    let x = F::from(1.0) - d;
+    x * x * x / F::from(6.0)
+}
+
+/// Residual of a unit change in slope per sample after the discontinuity
+#[inline]
+pub fn blamp_after<F: Float>(d: F) -> F {
+    d * d * d / F::from(6.0)
+}
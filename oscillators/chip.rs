@@ -0,0 +1,623 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/// CPU clock of the NTSC NES, which drives its sound channels
+pub const NES_NTSC_CLOCK: f64 = 1_789_773.0;
+
+/// CPU clock of the PAL NES
+pub const NES_PAL_CLOCK: f64 = 1_662_607.0;
+
+/// Master clock of the Game Boy
+pub const GAME_BOY_CLOCK: f64 = 4_194_304.0;
+
+/* Clock cycles between shifts of the NES noise register, in the order of its period setting */
+const NOISE_PERIODS: [u32; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+
+/* Steps before the short noise mode repeats when started from the power-on state */
+const SHORT_NOISE_STEPS: u32 = 93;
+
+/* 4-bit sine, the usual starting point of a wave channel */
+const DEFAULT_WAVE: [u8; 32] = [
+    8, 9, 11, 12, 13, 14, 14, 15, 15, 15, 14, 14, 13, 12, 11, 9,
+    8, 6, 4, 3, 2, 1, 1, 0, 0, 0, 1, 1, 2, 3, 4, 6,
+];
+
+/// Programmable timer stepping a channel's sequencer every `divider * period` clock cycles
+///
+/// Each sample is the average of the channel's output over the clock cycles
+/// it covers, so steps landing between samples are weighed exactly rather
+/// than rounded to the nearest sample.
+#[derive(Copy, Clone)]
+struct ChipTimer {
+    clock: f64,
+    divider: u32,
+    period: u32,
+    min_period: u32,
+    max_period: u32,
+    sample_rate: u32,
+    /// Clock cycles left until the next step
+    counter: f64,
+}
+
+impl ChipTimer {
+    fn new(clock: f64, divider: u32, min_period: u32, max_period: u32) -> Self {
+        Self {
+            clock,
+            divider,
+            period: max_period,
+            min_period,
+            max_period,
+            sample_rate: 44100,
+            counter: (divider * max_period) as f64,
+        }
+    }
+
+    fn step_length(&self) -> f64 {
+        (self.divider * self.period) as f64
+    }
+
+    /// Frequency of a sequence of `steps` steps
+    fn frequency(&self, steps: u32) -> f32 {
+        (self.clock / (self.step_length() * steps as f64)) as f32
+    }
+
+    /// Nearest period to `hz` for a sequence of `steps` steps that the hardware can play
+    fn set_frequency(&mut self, hz: f32, steps: u32) {
+        let period = if hz > 0.0 {
+            f64::round(self.clock / (self.divider as f64 * steps as f64 * hz as f64))
+        } else {
+            self.max_period as f64
+        };
+
+        self.period = f64::clamp(period, self.min_period as f64, self.max_period as f64) as u32;
+    }
+
+    fn restart(&mut self) {
+        self.counter = self.step_length();
+    }
+
+    /// Average of the output over the next sample, starting at `level` and calling `step` for the level after each step
+    fn integrate<S: FnMut() -> f32>(&mut self, mut level: f32, mut step: S) -> f32 {
+        let ticks = self.clock / self.sample_rate as f64;
+        let mut remaining = ticks;
+        let mut area = 0.0;
+
+        while self.counter <= remaining {
+            area += level as f64 * self.counter;
+            remaining -= self.counter;
+            level = step();
+            self.counter = self.step_length();
+        }
+
+        area += level as f64 * remaining;
+        self.counter -= remaining;
+
+        (area / ticks) as f32
+    }
+}
+
+/// Duty cycle of a chip pulse channel
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Duty {
+    /// 12.5%
+    Eighth,
+    /// 25%
+    Quarter,
+    /// 50%
+    Half,
+    /// 75%, which sounds like 25% inverted
+    ThreeQuarters,
+}
+
+impl Duty {
+    /// The NES's eight-step sequence for the duty
+    fn sequence(&self) -> [u8; 8] {
+        match self {
+            Duty::Eighth => [0, 1, 0, 0, 0, 0, 0, 0],
+            Duty::Quarter => [0, 1, 1, 0, 0, 0, 0, 0],
+            Duty::Half => [0, 1, 1, 1, 1, 0, 0, 0],
+            Duty::ThreeQuarters => [1, 0, 0, 1, 1, 1, 1, 1],
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => Duty::Eighth,
+            1 => Duty::Quarter,
+            2 => Duty::Half,
+            _ => Duty::ThreeQuarters,
+        }
+    }
+}
+
+/// Pulse channel of the NES with four duty cycles and 16 volume levels
+///
+/// The pitch is quantised to the 11-bit timer, so it is only as accurate as
+/// the hardware, and is reported as the frequency actually played. The
+/// output is not band-limited, aliasing as the original does.
+pub struct ChipPulse<F: Float> {
+    duty: Duty,
+    volume: u8,
+
+    timer: ChipTimer,
+    position: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> ChipPulse<F> {
+    /* Eight steps, each lasting two clock cycles per timer period */
+    const STEPS: u32 = 8;
+
+    pub fn new() -> Self {
+        let mut pulse = Self {
+            duty: Duty::Half,
+            volume: 15,
+            /* Periods below 9 silence the channel on the hardware */
+            timer: ChipTimer::new(NES_NTSC_CLOCK, 2, 9, 2048),
+            position: 0,
+            _phantom: PhantomData,
+        };
+
+        pulse.set_pitch(440.0);
+        pulse.reset();
+        pulse
+    }
+
+    /// Clock rate in Hz, such as `NES_PAL_CLOCK`
+    pub fn set_clock(&mut self, hz: f64) {
+        let pitch = self.get_pitch();
+        self.timer.clock = f64::max(hz, 1.0);
+        self.set_pitch(pitch);
+    }
+
+    pub fn set_duty(&mut self, duty: Duty) {
+        self.duty = duty;
+    }
+
+    /// Volume from 0 to 15
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = u8::min(volume, 15);
+    }
+
+    /// Timer period in clock cycles per step, from 9 to 2048
+    pub fn period(&self) -> u32 {
+        self.timer.period
+    }
+}
+
+impl<F: Float> Default for ChipPulse<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for ChipPulse<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.position = 0;
+        self.timer.restart();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.timer.sample_rate = sample_rate;
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let sequence = self.duty.sequence();
+        let gain = self.volume as f32 / 15.0;
+        let position = &mut self.position;
+
+        /* Duty and volume changes take effect straight away, as on the hardware */
+        let level = (sequence[*position] as f32 * 2.0 - 1.0) * gain;
+        let output = self.timer.integrate(level, || {
+            *position = (*position + 1) % sequence.len();
+            (sequence[*position] as f32 * 2.0 - 1.0) * gain
+        });
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for ChipPulse<F> {
+    fn get_pitch(&self) -> f32 {
+        self.timer.frequency(Self::STEPS)
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.timer.set_frequency(hz, Self::STEPS);
+    }
+}
+
+impl<F: Float> Param for ChipPulse<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "duty" => self.set_duty(Duty::from_index(f32::max(value, 0.0) as usize)),
+            "volume" => self.set_volume(f32::clamp(value, 0.0, 15.0) as u8),
+            _ => ()
+        }
+    }
+}
+
+/// Triangle channel of the NES, stepping through 32 levels of a 4-bit ramp
+///
+/// The hardware triangle has no volume control. Its coarse steps give it the
+/// buzzy edge of the original, and its period is quantised like the pulse's,
+/// though at twice the resolution.
+pub struct ChipTriangle<F: Float> {
+    timer: ChipTimer,
+    position: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> ChipTriangle<F> {
+    const STEPS: u32 = 32;
+
+    pub fn new() -> Self {
+        let mut triangle = Self {
+            timer: ChipTimer::new(NES_NTSC_CLOCK, 1, 1, 2048),
+            position: 0,
+            _phantom: PhantomData,
+        };
+
+        triangle.set_pitch(440.0);
+        triangle.reset();
+        triangle
+    }
+
+    /// Clock rate in Hz, such as `NES_PAL_CLOCK`
+    pub fn set_clock(&mut self, hz: f64) {
+        let pitch = self.get_pitch();
+        self.timer.clock = f64::max(hz, 1.0);
+        self.set_pitch(pitch);
+    }
+
+    /// Timer period in clock cycles per step, from 1 to 2048
+    pub fn period(&self) -> u32 {
+        self.timer.period
+    }
+
+    /// Level of step `position`, falling from 15 to 0 and rising back to 15
+    fn level(position: usize) -> f32 {
+        let value = if position < 16 { 15 - position } else { position - 16 };
+        value as f32 / 7.5 - 1.0
+    }
+}
+
+impl<F: Float> Default for ChipTriangle<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for ChipTriangle<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.position = 0;
+        self.timer.restart();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.timer.sample_rate = sample_rate;
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let position = &mut self.position;
+
+        let output = self.timer.integrate(Self::level(*position), || {
+            *position = (*position + 1) % Self::STEPS as usize;
+            Self::level(*position)
+        });
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for ChipTriangle<F> {
+    fn get_pitch(&self) -> f32 {
+        self.timer.frequency(Self::STEPS)
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.timer.set_frequency(hz, Self::STEPS);
+    }
+}
+
+/// Feedback tap of the noise channel's shift register
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NoiseMode {
+    /// 32767-step sequence that sounds like white noise
+    Long,
+    /// 93-step sequence with a metallic pitched tone
+    Short,
+}
+
+/// Noise channel of the NES, a 15-bit linear feedback shift register
+///
+/// The shift rate is one of the hardware's 16 periods. In long mode the pitch
+/// is the shift rate, and in short mode it is the rate of the repeating
+/// 93-step sequence, so short noise can be played in tune.
+pub struct ChipNoise<F: Float> {
+    mode: NoiseMode,
+    volume: u8,
+
+    timer: ChipTimer,
+    register: u16,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> ChipNoise<F> {
+    pub fn new() -> Self {
+        let mut noise = Self {
+            mode: NoiseMode::Long,
+            volume: 15,
+            timer: ChipTimer::new(NES_NTSC_CLOCK, 1, NOISE_PERIODS[0], NOISE_PERIODS[15]),
+            register: 1,
+            _phantom: PhantomData,
+        };
+
+        noise.set_period_index(0);
+        noise.reset();
+        noise
+    }
+
+    /// Clock rate in Hz, such as `NES_PAL_CLOCK`
+    pub fn set_clock(&mut self, hz: f64) {
+        let pitch = self.get_pitch();
+        self.timer.clock = f64::max(hz, 1.0);
+        self.set_pitch(pitch);
+    }
+
+    pub fn set_mode(&mut self, mode: NoiseMode) {
+        let pitch = self.get_pitch();
+        self.mode = mode;
+        self.set_pitch(pitch);
+    }
+
+    /// Volume from 0 to 15
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = u8::min(volume, 15);
+    }
+
+    /// Choose one of the 16 hardware periods directly, from 0 for the highest to 15 for the lowest
+    pub fn set_period_index(&mut self, index: usize) {
+        self.timer.period = NOISE_PERIODS[usize::min(index, NOISE_PERIODS.len() - 1)];
+    }
+
+    /// Timer period in clock cycles per shift
+    pub fn period(&self) -> u32 {
+        self.timer.period
+    }
+
+    fn steps(&self) -> u32 {
+        match self.mode {
+            NoiseMode::Long => 1,
+            NoiseMode::Short => SHORT_NOISE_STEPS,
+        }
+    }
+
+    fn output(register: u16, gain: f32) -> f32 {
+        /* The channel is silent when the low bit is set */
+        if register & 1 == 0 { gain } else { -gain }
+    }
+}
+
+impl<F: Float> Default for ChipNoise<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for ChipNoise<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.register = 1;
+        self.timer.restart();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.timer.sample_rate = sample_rate;
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let tap = match self.mode {
+            NoiseMode::Long => 1,
+            NoiseMode::Short => 6,
+        };
+
+        let gain = self.volume as f32 / 15.0;
+        let register = &mut self.register;
+
+        let output = self.timer.integrate(Self::output(*register, gain), || {
+            let feedback = (*register ^ (*register >> tap)) & 1;
+            *register = (*register >> 1) | (feedback << 14);
+            Self::output(*register, gain)
+        });
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for ChipNoise<F> {
+    fn get_pitch(&self) -> f32 {
+        self.timer.frequency(self.steps())
+    }
+
+    /// Picks the hardware period nearest to the pitch on a log scale
+    fn set_pitch(&mut self, hz: f32) {
+        let steps = self.steps() as f64;
+        let target = f64::log2(self.timer.clock / (steps * f64::max(hz as f64, 1e-3)));
+
+        self.timer.period = *NOISE_PERIODS.iter()
+            .min_by(| a, b | {
+                let a = f64::abs(f64::log2(**a as f64) - target);
+                let b = f64::abs(f64::log2(**b as f64) - target);
+                a.total_cmp(&b)
+            })
+            .unwrap();
+    }
+}
+
+impl<F: Float> Param for ChipNoise<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "mode" => self.set_mode(if value >= 0.5 { NoiseMode::Short } else { NoiseMode::Long }),
+            "period" => self.set_period_index(f32::max(value, 0.0) as usize),
+            "volume" => self.set_volume(f32::clamp(value, 0.0, 15.0) as u8),
+            _ => ()
+        }
+    }
+}
+
+/// Wave channel of the Game Boy, playing a user table of 32 4-bit samples
+///
+/// Volume is the hardware's shift rather than a level: 0 mutes, 1 plays at
+/// full level, 2 at half and 3 at a quarter.
+pub struct ChipWave<F: Float> {
+    table: [u8; 32],
+    volume: u8,
+
+    timer: ChipTimer,
+    position: usize,
+    levels: [f32; 32],
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> ChipWave<F> {
+    const STEPS: u32 = 32;
+
+    pub fn new() -> Self {
+        Self::from(DEFAULT_WAVE)
+    }
+
+    /// Wave from 32 samples from 0 to 15, higher values being clamped
+    pub fn from(table: [u8; 32]) -> Self {
+        let mut wave = Self {
+            table: [0; 32],
+            volume: 1,
+            /* The 11-bit frequency register counts up to 2048, two clock cycles at a time */
+            timer: ChipTimer::new(GAME_BOY_CLOCK, 2, 1, 2048),
+            position: 0,
+            levels: [0.0; 32],
+            _phantom: PhantomData,
+        };
+
+        wave.set_table(table);
+        wave.set_pitch(440.0);
+        wave.reset();
+        wave
+    }
+
+    /// Wave quantised to 4 bits from one cycle of samples from -1 to 1, resampled to 32 steps
+    pub fn from_samples(samples: &[f32]) -> Self {
+        Self::from(Self::quantize(samples))
+    }
+
+    pub fn table(&self) -> &[u8; 32] {
+        &self.table
+    }
+
+    /// Replace the samples, which the hardware allows while the channel plays
+    pub fn set_table(&mut self, table: [u8; 32]) {
+        self.table = table.map(| value | u8::min(value, 15));
+        self.update();
+    }
+
+    pub fn set_samples(&mut self, samples: &[f32]) {
+        self.set_table(Self::quantize(samples));
+    }
+
+    /// Clock rate in Hz
+    pub fn set_clock(&mut self, hz: f64) {
+        let pitch = self.get_pitch();
+        self.timer.clock = f64::max(hz, 1.0);
+        self.set_pitch(pitch);
+    }
+
+    /// Volume code from 0 to 3
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = u8::min(volume, 3);
+        self.update();
+    }
+
+    /// Timer period in pairs of clock cycles per step, from 1 to 2048
+    pub fn period(&self) -> u32 {
+        self.timer.period
+    }
+
+    fn quantize(samples: &[f32]) -> [u8; 32] {
+        std::array::from_fn(| i | {
+            if samples.is_empty() {
+                return 8;
+            }
+
+            let x = samples[i * samples.len() / 32];
+            f32::round((f32::clamp(x, -1.0, 1.0) + 1.0) * 7.5) as u8
+        })
+    }
+
+    fn update(&mut self) {
+        if self.volume == 0 {
+            self.levels = [0.0; 32];
+            return;
+        }
+
+        let shift = self.volume - 1;
+        let centre = 7.5 / (1 << shift) as f32;
+        self.levels = self.table.map(| value | ((value >> shift) as f32 - centre) / 7.5);
+    }
+}
+
+impl<F: Float> Default for ChipWave<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for ChipWave<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.position = 0;
+        self.timer.restart();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.timer.sample_rate = sample_rate;
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let levels = &self.levels;
+        let position = &mut self.position;
+
+        let output = self.timer.integrate(levels[*position], || {
+            *position = (*position + 1) % levels.len();
+            levels[*position]
+        });
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for ChipWave<F> {
+    fn get_pitch(&self) -> f32 {
+        self.timer.frequency(Self::STEPS)
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.timer.set_frequency(hz, Self::STEPS);
+    }
+}
+
+impl<F: Float> Param for ChipWave<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        if name == "volume" {
+            self.set_volume(f32::clamp(value, 0.0, 3.0) as u8);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+pub mod blep;
+mod analog;
+mod chip;
+mod sync;
+mod wavetable;
+mod noise;
+mod unison;
+
+pub use analog::*;
+pub use chip::*;
+pub use sync::*;
+pub use wavetable::*;
+pub use noise::*;
+pub use unison::*;
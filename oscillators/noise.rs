@@ -0,0 +1,375 @@
+use std::marker::PhantomData;
+
+use crate::float::*;
+use crate::math::random::*;
+use crate::traits::*;
+
+/* Coloured noise is scaled to roughly -12 dBFS RMS, leaving headroom for its peaks */
+const COLOURED_RMS: f64 = 0.25;
+
+/* Rows of the Voss-McCartney generator, giving a -3 dB/octave slope down to sample_rate / 2^16 */
+const PINK_ROWS: usize = 16;
+
+/* Differenced pink noise keeps four uniform terms, the white sample and the updated row, each new and old */
+const BLUE_TERMS: usize = 4;
+
+/* Corner frequency of the leaky integrator that keeps brown noise from drifting */
+const BROWN_CORNER: f64 = 5.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Distribution {
+    /// Uniform between -1 and 1
+    Uniform,
+    /// Normal with the same RMS as uniform noise
+    Gaussian,
+}
+
+/// White noise with a flat spectrum
+///
+/// All noise generators draw from a seeded generator, so the same seed always
+/// renders the same signal. Multi-channel samples get independent channels.
+pub struct WhiteNoise<S: Sample> {
+    seed: u64,
+    random: Random,
+    distribution: Distribution,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> WhiteNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        Self::with_distribution(seed, Distribution::Uniform)
+    }
+
+    pub fn with_distribution(seed: u64, distribution: Distribution) -> Self {
+        Self {
+            seed,
+            random: Random::new(seed),
+            distribution,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn set_distribution(&mut self, distribution: Distribution) {
+        self.distribution = distribution;
+    }
+}
+
+impl<S: Sample> Generator for WhiteNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.random = Random::new(self.seed);
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn generate(&mut self) -> Self::Output {
+        let random = &mut self.random;
+
+        match self.distribution {
+            Distribution::Uniform => S::from_channels(| _ | S::Float::from_f64(random.bipolar())),
+            Distribution::Gaussian => S::from_channels(| _ | S::Float::from_f64(random.gaussian() * f64::sqrt(1.0 / 3.0)))
+        }
+    }
+}
+
+/// Pink noise falling at 3 dB per octave, using the Voss-McCartney algorithm
+pub struct PinkNoise<S: Sample> {
+    seed: u64,
+    random: Random,
+    counter: u32,
+    channels: Vec<PinkRows>,
+    _phantom: PhantomData<S>,
+}
+
+#[derive(Copy, Clone)]
+struct PinkRows {
+    rows: [f64; PINK_ROWS],
+    sum: f64,
+}
+
+impl PinkRows {
+    fn new(random: &mut Random) -> Self {
+        let mut rows = [0.0; PINK_ROWS];
+        for row in rows.iter_mut() {
+            *row = random.bipolar();
+        }
+
+        Self {
+            rows,
+            sum: rows.iter().sum(),
+        }
+    }
+
+    /// Update the row chosen by the counter and add a fresh white sample
+    fn next(&mut self, random: &mut Random, counter: u32) -> f64 {
+        let row = counter.trailing_zeros() as usize;
+        if row < PINK_ROWS {
+            let value = random.bipolar();
+            self.sum += value - self.rows[row];
+            self.rows[row] = value;
+        }
+
+        self.sum + random.bipolar()
+    }
+}
+
+impl<S: Sample> PinkNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        let mut random = Random::new(seed);
+        let channels = (0..S::CHANNELS)
+            .map(| _ | PinkRows::new(&mut random))
+            .collect();
+
+        Self {
+            seed,
+            random,
+            counter: 0,
+            channels,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn next(&mut self) -> S {
+        self.counter = self.counter.wrapping_add(1);
+
+        let counter = self.counter;
+        let random = &mut self.random;
+        let channels = &mut self.channels;
+
+        /* Each of the 17 uniform terms has an RMS of 1 / sqrt(3) */
+        let scale = COLOURED_RMS * f64::sqrt(3.0 / (PINK_ROWS + 1) as f64);
+
+        S::from_channels(| c | S::Float::from_f64(channels[c].next(random, counter) * scale))
+    }
+}
+
+impl<S: Sample> Generator for PinkNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        *self = Self::new(self.seed);
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn generate(&mut self) -> Self::Output {
+        self.next()
+    }
+}
+
+/// Brown noise falling at 6 dB per octave, integrated white noise with a slight leak below 5 Hz
+pub struct BrownNoise<S: Sample> {
+    seed: u64,
+    random: Random,
+    leak: f64,
+    scale: f64,
+    state: Vec<f64>,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> BrownNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        let mut noise = Self {
+            seed,
+            random: Random::new(seed),
+            leak: 0.0,
+            scale: 0.0,
+            state: vec![0.0; S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        noise.set_sample_rate(44100);
+        noise
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.leak = f64::exp(-2.0 * std::f64::consts::PI * BROWN_CORNER / sample_rate as f64);
+
+        /* The integrator multiplies the variance of its input by 1 / (1 - leak^2) */
+        self.scale = COLOURED_RMS * f64::sqrt(3.0 * (1.0 - self.leak * self.leak));
+    }
+}
+
+impl<S: Sample> Generator for BrownNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.random = Random::new(self.seed);
+        self.state.fill(0.0);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.set_sample_rate(sample_rate);
+        self.reset();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let random = &mut self.random;
+        let state = &mut self.state;
+        let (leak, scale) = (self.leak, self.scale);
+
+        S::from_channels(| c | {
+            state[c] = state[c] * leak + random.bipolar();
+            S::Float::from_f64(state[c] * scale)
+        })
+    }
+}
+
+/// Blue noise rising at 3 dB per octave, differentiated pink noise
+pub struct BlueNoise<S: Sample> {
+    pink: PinkNoise<S>,
+    last: S,
+}
+
+impl<S: Sample> BlueNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        let mut pink = PinkNoise::new(seed);
+        let last = pink.next();
+
+        Self { pink, last }
+    }
+}
+
+impl<S: Sample> Generator for BlueNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.pink.reset();
+        self.last = self.pink.next();
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn generate(&mut self) -> Self::Output {
+        let next = self.pink.next();
+        let output = next - self.last;
+        self.last = next;
+
+        /* Differencing keeps 4 of the pink generator's 17 equal terms, so scale its RMS back up */
+        output * S::Float::from_f64(f64::sqrt((PINK_ROWS + 1) as f64 / BLUE_TERMS as f64))
+    }
+}
+
+/// Violet noise rising at 6 dB per octave, differentiated white noise
+pub struct VioletNoise<S: Sample> {
+    white: WhiteNoise<S>,
+    last: S,
+}
+
+impl<S: Sample> VioletNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        let mut white = WhiteNoise::new(seed);
+        let last = white.generate();
+
+        Self { white, last }
+    }
+}
+
+impl<S: Sample> Generator for VioletNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.white.reset();
+        self.last = self.white.generate();
+    }
+
+    fn prepare(&mut self, _sample_rate: u32, _block_size: usize) {}
+
+    fn generate(&mut self) -> Self::Output {
+        let next = self.white.generate();
+        let output = next - self.last;
+        self.last = next;
+
+        /* The difference of two uniform samples has an RMS of sqrt(2 / 3) */
+        output * S::Float::from_f64(COLOURED_RMS * f64::sqrt(1.5))
+    }
+}
+
+/// Velvet noise, sparse impulses of random sign at random positions within each period
+///
+/// Sounds smoother than white noise at a fraction of the density, which makes it
+/// useful for cheap decorrelation and reverb tails.
+pub struct VelvetNoise<S: Sample> {
+    seed: u64,
+    random: Random,
+    density: f32,
+    sample_rate: u32,
+    period: usize,
+    channels: Vec<VelvetChannel>,
+    _phantom: PhantomData<S>,
+}
+
+#[derive(Copy, Clone)]
+struct VelvetChannel {
+    position: usize,
+    impulse: usize,
+    sign: f64,
+}
+
+impl<S: Sample> VelvetNoise<S> {
+    pub fn new(seed: u64) -> Self {
+        let mut noise = Self {
+            seed,
+            random: Random::new(seed),
+            density: 2000.0,
+            sample_rate: 44100,
+            period: 1,
+            channels: vec![VelvetChannel { position: 0, impulse: 0, sign: 1.0 }; S::CHANNELS],
+            _phantom: PhantomData,
+        };
+
+        noise.set_density(2000.0);
+        noise
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    /// Impulses per second
+    pub fn set_density(&mut self, density: f32) {
+        self.density = f32::clamp(density, 1.0, self.sample_rate as f32);
+        self.period = usize::max((self.sample_rate as f32 / self.density).round() as usize, 1);
+        self.reset();
+    }
+}
+
+impl<S: Sample> Generator for VelvetNoise<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.random = Random::new(self.seed);
+
+        for channel in self.channels.iter_mut() {
+            channel.position = 0;
+            channel.impulse = self.random.below(self.period);
+            channel.sign = if self.random.uniform() < 0.5 { -1.0 } else { 1.0 };
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.set_density(self.density);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let random = &mut self.random;
+        let channels = &mut self.channels;
+        let period = self.period;
+
+        S::from_channels(| c | {
+            let channel = &mut channels[c];
+            let output = if channel.position == channel.impulse { channel.sign } else { 0.0 };
+
+            channel.position += 1;
+            if channel.position == period {
+                channel.position = 0;
+                channel.impulse = random.below(period);
+                channel.sign = if random.uniform() < 0.5 { -1.0 } else { 1.0 };
+            }
+
+            S::Float::from_f64(output)
+        })
+    }
+}
@@ -0,0 +1,128 @@
+use crate::float::*;
+use crate::traits::*;
+
+/// An oscillator that can report and follow cycle restarts
+pub trait Syncable {
+    /// Fraction of the next sample interval until the cycle wraps, if it wraps during it
+    fn next_wrap(&self) -> Option<f32>;
+
+    /// Restart the cycle `fraction` of the way through the next sample interval
+    fn sync(&mut self, fraction: f32);
+}
+
+/// Hard sync, restarting the slave oscillator every time the master wraps
+pub struct HardSync<M, S> {
+    master: M,
+    slave: S,
+    ratio: f32,
+}
+
+impl<M, S> HardSync<M, S>
+    where
+        M: Generator + Pitched + Syncable,
+        S: Generator + Pitched + Syncable {
+
+    pub fn from(master: M, slave: S) -> Self {
+        let mut sync = Self {
+            master,
+            slave,
+            ratio: 1.0,
+        };
+
+        sync.set_pitch(sync.master.get_pitch());
+        sync
+    }
+
+    pub fn master(&mut self) -> &mut M {
+        &mut self.master
+    }
+
+    pub fn slave(&mut self) -> &mut S {
+        &mut self.slave
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Slave pitch relative to the master
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = f32::max(ratio, 0.0);
+        self.slave.set_pitch(self.master.get_pitch() * self.ratio);
+    }
+}
+
+impl<M, S> Generator for HardSync<M, S>
+    where
+        M: Generator + Pitched + Syncable,
+        S: Generator + Pitched + Syncable,
+        S::Output: Float {
+
+    type Output = S::Output;
+
+    fn reset(&mut self) {
+        self.master.reset();
+        self.slave.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.master.prepare(sample_rate, block_size);
+        self.slave.prepare(sample_rate, block_size);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        if let Some(fraction) = self.master.next_wrap() {
+            self.slave.sync(fraction);
+        }
+
+        self.master.generate();
+        self.slave.generate()
+    }
+}
+
+impl<M, S> Pitched for HardSync<M, S>
+    where
+        M: Generator + Pitched + Syncable,
+        S: Generator + Pitched + Syncable {
+
+    fn get_pitch(&self) -> f32 {
+        self.master.get_pitch()
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.master.set_pitch(hz);
+        self.slave.set_pitch(hz * self.ratio);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillators::*;
+    use crate::oscillators::analog::tests::*;
+
+    const RATIO: f32 = 1.7;
+
+    #[test]
+    fn hard_sync_aliasing_is_reduced() {
+        let mut sync = HardSync::from(Saw::<f64>::new(), Saw::<f64>::new());
+        sync.set_ratio(RATIO);
+        sync.set_pitch(PITCH);
+
+        let band_limited = aliasing_db(&render(&mut sync), PITCH);
+
+        /* The slave saw restarting at every wrap of the master */
+        let master = PITCH as f64 / SAMPLE_RATE as f64;
+        let naive: Vec<f64> = (64..SAMPLE_RATE as usize + 64)
+            .map(| n | {
+                let master_phase = (n as f64 * master).fract();
+                let slave_phase = (master_phase * RATIO as f64).fract();
+                slave_phase * 2.0 - 1.0
+            })
+            .collect();
+
+        let naive = aliasing_db(&naive, PITCH);
+        assert!(band_limited < -15.0, "hard sync aliasing at {} dB", band_limited);
+        assert!(band_limited < naive - 10.0, "hard sync aliasing at {} dB against {} dB naive", band_limited, naive);
+    }
+}
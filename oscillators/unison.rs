@@ -0,0 +1,249 @@
+use crate::float::*;
+use crate::math::random::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/* Relative detune of the seven JP-8000 supersaw voices, normalised to the outermost */
+const SUPERSAW_OFFSETS: [f32; 7] = [-1.0, -0.5716, -0.1775, 0.0, 0.1810, 0.5650, 0.9767];
+
+/// How detune is spread across the voices, from the centre outwards
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DetuneCurve {
+    /// Evenly spaced
+    Linear,
+    /// Bunched towards the centre with a few wide outer voices
+    Exponential,
+    /// The uneven spacing of the classic supersaw
+    Supersaw,
+}
+
+impl DetuneCurve {
+    /// Detune from -1 to 1 of a voice at `position` from -1 to 1
+    fn apply(&self, position: f32) -> f32 {
+        match self {
+            DetuneCurve::Linear => position,
+            DetuneCurve::Exponential => position * position.abs(),
+            DetuneCurve::Supersaw => {
+                let x = (position + 1.0) * 0.5 * (SUPERSAW_OFFSETS.len() - 1) as f32;
+                let i = usize::min(x.floor() as usize, SUPERSAW_OFFSETS.len() - 2);
+                let t = x - i as f32;
+                SUPERSAW_OFFSETS[i] + (SUPERSAW_OFFSETS[i + 1] - SUPERSAW_OFFSETS[i]) * t
+            }
+        }
+    }
+}
+
+/// Stack of `N` detuned copies of a pitched generator
+///
+/// Voices are spread over `detune` cents, panned alternately left and right
+/// by `spread` and mixed with the centre voice by `blend`, so a unison of
+/// seven saws is a supersaw. The output is stereo even when the generator is mono.
+pub struct Unison<G, const N: usize> {
+    voices: [G; N],
+    pitch: f32,
+    detune: f32,
+    curve: DetuneCurve,
+    spread: f32,
+    blend: f32,
+    phase_randomness: f32,
+    seed: u64,
+
+    random: Random,
+    offsets: [f32; N],
+    gains: [(f32, f32); N],
+}
+
+impl<G, const N: usize> Unison<G, N> {
+    pub fn voices(&mut self) -> &mut [G; N] {
+        &mut self.voices
+    }
+
+    /// Stereo width from 0 for mono to 1 for the outer voices panned hard left and right
+    pub fn set_spread(&mut self, spread: f32) {
+        self.spread = f32::clamp(spread, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Mix from 0 for only the centre voice to 1 for the detuned voices loudest
+    pub fn set_blend(&mut self, blend: f32) {
+        self.blend = f32::clamp(blend, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Position from -1 to 1 of voice `i`
+    fn position(i: usize) -> f32 {
+        if N > 1 {
+            2.0 * i as f32 / (N - 1) as f32 - 1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn update(&mut self) {
+        /* The voices nearest the middle are the centre, one for odd counts and two for even */
+        let centre = (0..N).map(| i | Self::position(i).abs()).fold(f32::MAX, f32::min);
+
+        /* Centre and side levels of the JP-8000's mix control */
+        let centre_gain = 0.99785 - 0.55366 * self.blend;
+        let side_gain = -0.73764 * self.blend * self.blend + 1.2841 * self.blend + 0.044372;
+
+        /* Uncorrelated voices add in power */
+        let scale = 1.0 / f32::sqrt(N as f32);
+
+        for i in 0..N {
+            let position = Self::position(i);
+            self.offsets[i] = f32::powf(2.0, self.curve.apply(position) * self.detune / 1200.0);
+
+            /* Mirrored pairs go to opposite sides, alternating by ring so neighbouring detunes are apart */
+            let ring = (position.abs() * (N - 1) as f32 * 0.5).round() as usize;
+            let side = if ring.is_multiple_of(2) { 1.0 } else { -1.0 };
+            let pan = self.spread * position * side;
+            let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+
+            let gain = if position.abs() <= centre + 1e-6 { centre_gain } else { side_gain };
+            let gain = gain * scale * std::f32::consts::SQRT_2;
+
+            self.gains[i] = (f32::cos(angle) * gain, f32::sin(angle) * gain);
+        }
+    }
+}
+
+impl<G: Pitched + Clone, const N: usize> Unison<G, N> {
+    pub fn from(generator: G) -> Self {
+        Self::from_voices(std::array::from_fn(| _ | generator.clone()))
+    }
+}
+
+impl<G: Pitched, const N: usize> Unison<G, N> {
+    pub fn from_voices(voices: [G; N]) -> Self {
+        let mut unison = Self {
+            voices,
+            pitch: 440.0,
+            detune: 25.0,
+            curve: DetuneCurve::Linear,
+            spread: 1.0,
+            blend: 0.5,
+            phase_randomness: 1.0,
+            seed: 0,
+            random: Random::new(0),
+            offsets: [1.0; N],
+            gains: [(0.0, 0.0); N],
+        };
+
+        unison.update();
+        unison.update_pitch();
+        unison
+    }
+
+    /// Detune in cents between the centre and the outermost voices
+    pub fn set_detune(&mut self, cents: f32) {
+        self.detune = f32::max(cents, 0.0);
+        self.update();
+        self.update_pitch();
+    }
+
+    pub fn set_curve(&mut self, curve: DetuneCurve) {
+        self.curve = curve;
+        self.update();
+        self.update_pitch();
+    }
+
+    fn update_pitch(&mut self) {
+        for (voice, offset) in self.voices.iter_mut().zip(self.offsets) {
+            voice.set_pitch(self.pitch * offset);
+        }
+    }
+}
+
+impl<G: Phased, const N: usize> Unison<G, N> {
+    /// How far apart the voices start when `randomize_phases` is called, from 0 for all together to 1 for anywhere
+    pub fn set_phase_randomness(&mut self, amount: f32) {
+        self.phase_randomness = f32::clamp(amount, 0.0, 1.0);
+    }
+
+    /// Seed of the phase randomisation
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.random = Random::new(seed);
+    }
+
+    /// Restart every voice at a new random phase
+    pub fn randomize_phases(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.set_phase(self.random.uniform() as f32 * self.phase_randomness);
+        }
+    }
+}
+
+impl<F, G, const N: usize> Generator for Unison<G, N>
+    where
+        F: Float,
+        G: Generator<Output = F> + Pitched {
+
+    type Output = Stereo<F>;
+
+    fn reset(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.reset();
+        }
+
+        self.random = Random::new(self.seed);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        for voice in self.voices.iter_mut() {
+            voice.prepare(sample_rate, block_size);
+        }
+
+        self.update_pitch();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let mut output = Stereo { left: F::ZERO, right: F::ZERO };
+
+        for (voice, (left, right)) in self.voices.iter_mut().zip(self.gains) {
+            let x = voice.generate();
+            output.left += x * F::from(left);
+            output.right += x * F::from(right);
+        }
+
+        output
+    }
+}
+
+impl<G: Pitched, const N: usize> Pitched for Unison<G, N> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Pitch of the centre, with each voice offset by its detune
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+        self.update_pitch();
+    }
+}
+
+impl<G: Pitched + Phased, const N: usize> Playable for Unison<G, N> {
+    /// Sets the pitch and restarts the voices at random phases
+    fn note_on(&mut self, pitch: f32, _pressure: f32) {
+        self.set_pitch(pitch);
+        self.randomize_phases();
+    }
+
+    fn note_off(&mut self) {}
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+}
+
+impl<G: Pitched, const N: usize> Param for Unison<G, N> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "detune" => self.set_detune(value),
+            "spread" => self.set_spread(value),
+            "blend" => self.set_blend(value),
+            _ => ()
+        }
+    }
+}
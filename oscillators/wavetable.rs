@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use crate::buffers::*;
+use crate::float::*;
+use crate::traits::*;
+use crate::fft::*;
+use crate::routing::param::*;
+
+/* Mip level tables are never shorter than this, so low levels still interpolate well */
+const MIN_TABLE_SIZE: usize = 64;
+
+/// Band limited copies of every frame of a wavetable, one level per octave
+///
+/// Level `k` keeps the lowest `C / 2 >> k` harmonics and is stored at no less
+/// than twice its bandwidth so cubic interpolation stays clean.
+pub struct Mipmap<F: Float> {
+    levels: Vec<MipLevel<F>>,
+    frames: usize,
+    size: usize,
+}
+
+struct MipLevel<F: Float> {
+    harmonics: usize,
+    size: usize,
+    /* Each frame has one guard sample before and two after for cubic interpolation */
+    frames: Vec<Vec<F>>,
+}
+
+impl<F: Float> Mipmap<F> {
+    pub fn from<const C: usize>(wavetable: &Wavetable<F, C>) -> Result<Self, String> {
+        let frames: Vec<&[F]> = wavetable.table
+            .iter()
+            .map(| frame | frame.as_slice())
+            .collect();
+
+        Self::from_frames(&frames)
+    }
+
+    /// Build from frames of equal power of two length
+    pub fn from_frames(frames: &[&[F]]) -> Result<Self, String> {
+        let size = match frames.first() {
+            Some(frame) => frame.len(),
+            None => return Err(String::from("Wavetable has no frames")),
+        };
+
+        if size < 4 || !size.is_power_of_two() {
+            return Err(format!("Wavetable frames of {} samples aren't a power of two of at least 4 samples", size));
+        }
+
+        if frames.iter().any(| frame | frame.len() != size) {
+            return Err(String::from("Wavetable frames must have equal length"));
+        }
+
+        let mut forward = RealFft::<F>::new(size);
+        let spectra: Vec<Vec<Complex<F>>> = frames
+            .iter()
+            .map(| frame | {
+                let mut spectrum = vec![Complex::new(); forward.bins()];
+                forward.forward(*frame, spectrum.as_mut_slice());
+                spectrum
+            })
+            .collect();
+
+        let mut levels = Vec::new();
+        let mut harmonics = size / 2;
+
+        while harmonics >= 1 {
+            let table_size = usize::min(size, usize::max(MIN_TABLE_SIZE, harmonics * 4));
+            let mut inverse = RealFft::<F>::new(table_size);
+            let scale = F::from(table_size as f32 / size as f32);
+
+            let frames = spectra
+                .iter()
+                .map(| spectrum | {
+                    /* Drop DC and every harmonic above this level */
+                    let mut bins = vec![Complex::new(); inverse.bins()];
+                    for k in 1..=usize::min(harmonics, inverse.bins() - 1) {
+                        bins[k] = spectrum[k] * scale;
+                    }
+
+                    let mut table = vec![F::ZERO; table_size];
+                    inverse.inverse(bins.as_slice(), table.as_mut_slice());
+
+                    let mut frame = Vec::with_capacity(table_size + 3);
+                    frame.push(table[table_size - 1]);
+                    frame.extend_from_slice(&table);
+                    frame.push(table[0]);
+                    frame.push(table[1]);
+                    frame
+                })
+                .collect();
+
+            levels.push(MipLevel {
+                harmonics,
+                size: table_size,
+                frames,
+            });
+
+            harmonics /= 2;
+        }
+
+        Ok(Self {
+            levels,
+            frames: frames.len(),
+            size,
+        })
+    }
+
+    /// Number of frames
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Length of a frame in the source wavetable
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of mip levels
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Level whose highest harmonic stays below Nyquist at `increment` cycles per sample
+    fn level(&self, increment: F) -> usize {
+        let half = F::from(0.5);
+
+        self.levels
+            .iter()
+            .position(| level | F::from(level.harmonics as f32) * increment <= half)
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    /// Cubic interpolated value of `frame` of `level` at `phase`
+    #[inline]
+    fn read(&self, level: usize, frame: usize, phase: F) -> F {
+        let level = &self.levels[level];
+        let table = &level.frames[frame];
+
+        let x = phase * F::from(level.size as f32);
+        let index = x.floor();
+        let t = x - index;
+        let i = usize::min(index.to_f32() as usize, level.size - 1);
+
+        let (y0, y1, y2, y3) = (table[i], table[i + 1], table[i + 2], table[i + 3]);
+        hermite(y0, y1, y2, y3, t)
+    }
+}
+
+/// Catmull-Rom interpolation between `y1` and `y2`
+#[inline]
+fn hermite<F: Float>(y0: F, y1: F, y2: F, y3: F, t: F) -> F {
+    let half = F::from(0.5);
+    let c1 = half * (y2 - y0);
+    let c2 = y0 - F::from(2.5) * y1 + F::from(2.0) * y2 - half * y3;
+    let c3 = half * (y3 - y0) + F::from(1.5) * (y1 - y2);
+
+    ((c3 * t + c2) * t + c1) * t + y1
+}
+
+/// Wavetable oscillator with continuous frame morphing
+///
+/// Picks the mip level for the current pitch so high notes don't alias, and
+/// crossfades between neighbouring frames by `position`, from 0 to 1.
+#[derive(Clone)]
+pub struct WavetableOscillator<F: Float> {
+    mipmap: Arc<Mipmap<F>>,
+    phase: F,
+    increment: F,
+    level: usize,
+    position: F,
+    pitch: f32,
+    sample_rate: u32,
+}
+
+impl<F: Float> WavetableOscillator<F> {
+    pub fn from<const C: usize>(wavetable: &Wavetable<F, C>) -> Result<Self, String> {
+        Ok(Self::from_mipmap(Arc::new(Mipmap::from(wavetable)?)))
+    }
+
+    /// Oscillator sharing an existing mipmap, so voices don't each rebuild it
+    pub fn from_mipmap(mipmap: Arc<Mipmap<F>>) -> Self {
+        let mut oscillator = Self {
+            mipmap,
+            phase: F::ZERO,
+            increment: F::ZERO,
+            level: 0,
+            position: F::ZERO,
+            pitch: 440.0,
+            sample_rate: 44100,
+        };
+
+        oscillator.set_pitch(440.0);
+        oscillator
+    }
+
+    pub fn mipmap(&self) -> &Arc<Mipmap<F>> {
+        &self.mipmap
+    }
+
+    pub fn set_mipmap(&mut self, mipmap: Arc<Mipmap<F>>) {
+        self.mipmap = mipmap;
+        self.set_pitch(self.pitch);
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position.to_f32()
+    }
+
+    /// Position in the wavetable from the first frame at 0 to the last at 1
+    pub fn set_position(&mut self, position: f32) {
+        self.position = F::from(f32::clamp(position, 0.0, 1.0));
+    }
+
+    /// Value at `phase` from 0 to 1 with the current position and mip level, without advancing
+    pub fn value_at(&self, phase: F) -> F {
+        let frames = self.mipmap.frames();
+        if frames == 0 {
+            return F::ZERO;
+        }
+
+        let phase = phase - phase.floor();
+        let x = self.position * F::from((frames - 1) as f32);
+        let index = x.floor();
+        let t = x - index;
+        let frame = usize::min(index.to_f32() as usize, frames - 1);
+
+        let a = self.mipmap.read(self.level, frame, phase);
+        if frame + 1 < frames && t > F::ZERO {
+            let b = self.mipmap.read(self.level, frame + 1, phase);
+            a + (b - a) * t
+        } else {
+            a
+        }
+    }
+}
+
+impl<F: Float> Generator for WavetableOscillator<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phase = F::ZERO;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.set_pitch(self.pitch);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let output = self.value_at(self.phase);
+
+        self.phase += self.increment;
+        if self.phase >= F::from(1.0) {
+            self.phase -= F::from(1.0);
+        }
+
+        output
+    }
+}
+
+impl<F: Float> Pitched for WavetableOscillator<F> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+
+        let increment = f32::clamp(hz / self.sample_rate as f32, 0.0, 0.5);
+        self.increment = F::from(increment);
+        self.level = self.mipmap.level(self.increment);
+    }
+}
+
+impl<F: Float> Phased for WavetableOscillator<F> {
+    fn get_phase(&self) -> f32 {
+        self.phase.to_f32()
+    }
+
+    fn set_phase(&mut self, phase: f32) {
+        self.phase = F::from(phase - f32::floor(phase));
+    }
+}
+
+impl<F: Float> Param for WavetableOscillator<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        if name == "position" {
+            self.set_position(value);
+        }
+    }
+}
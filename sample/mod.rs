@@ -2,6 +2,11 @@ pub mod cache;
 pub mod resampling;
 pub mod sample;
 pub mod sample_mut;
+pub mod riff;
+pub mod normalize;
+pub mod pitch;
 
 pub use sample::*;
 pub use sample_mut::*;
+pub use normalize::*;
+pub use pitch::*;
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+use std::path::Path;
+
+use crate::buffers::*;
+use crate::float::*;
+use crate::traits::*;
+use crate::metering::*;
+use crate::sample::sample::*;
+use crate::sample::riff::*;
+
+/// Normalization target level
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Level {
+    /// Sample peak in dBFS
+    Peak(f32),
+    /// RMS over the whole signal in dBFS
+    Rms(f32),
+    /// Integrated loudness in LUFS
+    Loudness(f32),
+}
+
+/// Levels of a complete signal
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Levels {
+    /// Sample peak in dBFS
+    pub peak: f32,
+    /// True peak in dBTP
+    pub true_peak: f32,
+    /// RMS over all channels in dBFS
+    pub rms: f32,
+    /// Integrated loudness in LUFS
+    pub loudness: f32,
+}
+
+impl Levels {
+    /// Gain in dB that brings these levels to `target` without the true peak exceeding `ceiling` dBTP
+    pub fn gain_to(&self, target: Level, ceiling: Option<f32>) -> f32 {
+        let gain = match target {
+            Level::Peak(db) => db - self.peak,
+            Level::Rms(db) => db - self.rms,
+            Level::Loudness(lufs) => lufs - self.loudness,
+        };
+
+        /* Silence can't be normalized */
+        let gain = if gain.is_finite() { gain } else { 0.0 };
+
+        match ceiling {
+            Some(ceiling) if self.true_peak > -144.0 => f32::min(gain, ceiling - self.true_peak),
+            _ => gain
+        }
+    }
+}
+
+/// Measure a block of samples
+pub fn measure<S: Sample, B: Block<Item = S> + ?Sized>(block: &B, sample_rate: u32) -> Levels {
+    let mut peak = PeakMeter::<S>::new(sample_rate);
+    let mut true_peak = TruePeakMeter::<S>::new();
+    let mut loudness = LoudnessMeter::<S>::new(sample_rate);
+    let mut squares = 0.0;
+
+    for s in block.as_slice() {
+        peak.process(*s);
+        true_peak.process(*s);
+        loudness.process(*s);
+
+        for channel in 0..S::CHANNELS {
+            let x = s.channel(channel).to_f64();
+            squares += x * x;
+        }
+    }
+
+    Levels {
+        peak: peak.max_peak_db(),
+        true_peak: true_peak.max_true_peak_db(),
+        rms: rms_db(squares, block.len() * S::CHANNELS),
+        loudness: loudness.integrated(),
+    }
+}
+
+/// Measure interleaved frames of `channels` samples
+pub fn measure_interleaved(samples: &[f32], channels: usize, sample_rate: u32) -> Levels {
+    let channels = usize::max(channels, 1);
+    let mut peak = PeakMeter::<f32>::with_channels(sample_rate, channels);
+    let mut true_peak = TruePeakMeter::<f32>::with_channels(channels);
+    let mut loudness = LoudnessMeter::<f32>::with_channels(sample_rate, channels);
+    let mut squares = 0.0;
+
+    for frame in samples.chunks_exact(channels) {
+        peak.process_frame(frame);
+        true_peak.process_frame(frame);
+        loudness.process_frame(frame);
+
+        for x in frame {
+            squares += *x as f64 * *x as f64;
+        }
+    }
+
+    Levels {
+        peak: peak.max_peak_db(),
+        true_peak: true_peak.max_true_peak_db(),
+        rms: rms_db(squares, samples.len()),
+        loudness: loudness.integrated(),
+    }
+}
+
+fn rms_db(squares: f64, count: usize) -> f32 {
+    if count == 0 || squares <= 0.0 {
+        return -144.0;
+    }
+
+    f32::max(10.0 * f64::log10(squares / count as f64) as f32, -144.0)
+}
+
+/// Normalize a buffer in place, returns the gain applied in dB
+pub fn normalize_buffer<S: Sample>(buffer: &mut Buffer<S>, sample_rate: u32, target: Level, ceiling: Option<f32>) -> f32 {
+    let gain = measure(buffer, sample_rate).gain_to(target, ceiling);
+    let linear = S::Float::from(f32::powf(10.0, gain / 20.0));
+
+    buffer.apply(| s | s * linear);
+    gain
+}
+
+/// Normalized copy of a sample file
+pub fn normalize_sample<S: Sample>(sample: &SampleFile<S>, sample_rate: u32, target: Level, ceiling: Option<f32>) -> SampleFile<S> {
+    let mut buffer = Buffer::from(sample.as_slice().to_vec());
+    normalize_buffer(&mut buffer, sample_rate, target, ceiling);
+
+    let mut normalized = SampleFile::from(Arc::new(buffer), sample.path().to_string());
+    normalized.start = sample.start;
+    normalized.end = sample.end;
+    normalized.pitch = sample.pitch;
+    normalized
+}
+
+/// Normalize a WAV file, writing the result to `output` with the same format and metadata
+///
+/// `output` may be the same path as `input`. Integer files are never pushed
+/// past full scale, so the gain may fall short of `target` when there's no
+/// `ceiling` to stop it clipping. Returns the gain applied in dB.
+pub fn normalize_file(input: &str, output: &str, target: Level, ceiling: Option<f32>) -> Result<f32, String> {
+    let mut reader = hound::WavReader::open(input).map_err(| e | e.to_string())?;
+    let spec = reader.spec();
+
+    /* Samples stay in f64 so 32 bit integers pass through unchanged at unity gain */
+    let full_scale = (1_i64 << (spec.bits_per_sample - 1)) as f64;
+    let mut samples: Vec<f64> = match spec.sample_format {
+        hound::SampleFormat::Float => {
+            reader.samples::<f32>()
+                .map(| v | v.map(| v | v as f64))
+                .collect::<Result<_, _>>()
+                .map_err(| e | e.to_string())?
+        },
+        hound::SampleFormat::Int => {
+            reader.samples::<i32>()
+                .map(| v | v.map(| v | v as f64 / full_scale))
+                .collect::<Result<_, _>>()
+                .map_err(| e | e.to_string())?
+        }
+    };
+
+    let metadata = read_metadata_chunks(input)?;
+    drop(reader);
+
+    let measured: Vec<f32> = samples.iter().map(| s | *s as f32).collect();
+    let levels = measure_interleaved(&measured, spec.channels as usize, spec.sample_rate);
+    let mut gain = levels.gain_to(target, ceiling) as f64;
+
+    /* The positive full scale is one step short of the negative one, so each side is capped at its own limit */
+    if spec.sample_format == hound::SampleFormat::Int {
+        let (low, high) = samples.iter().fold((0.0, 0.0), | (low, high), s | (f64::min(low, *s), f64::max(high, *s)));
+        if high > 0.0 {
+            gain = f64::min(gain, 20.0 * f64::log10((full_scale - 1.0) / full_scale / high));
+        }
+        if low < 0.0 {
+            gain = f64::min(gain, 20.0 * f64::log10(-1.0 / low));
+        }
+    }
+
+    let linear = f64::powf(10.0, gain / 20.0);
+    for s in samples.iter_mut() {
+        *s *= linear;
+    }
+
+    let mut writer = hound::WavWriter::create(output, spec).map_err(| e | e.to_string())?;
+
+    match spec.sample_format {
+        hound::SampleFormat::Float => {
+            for s in &samples {
+                writer.write_sample(*s as f32).map_err(| e | e.to_string())?;
+            }
+        },
+        hound::SampleFormat::Int => {
+            let (min, max) = (-full_scale, full_scale - 1.0);
+
+            for s in &samples {
+                let v = f64::clamp(f64::round(s * full_scale), min, max) as i32;
+                writer.write_sample(v).map_err(| e | e.to_string())?;
+            }
+        }
+    }
+
+    writer.finalize().map_err(| e | e.to_string())?;
+    append_chunks(output, &metadata)?;
+
+    Ok(gain as f32)
+}
+
+/// Path of each file processed in a batch with the gain applied in dB or the error
+pub type BatchResults = Vec<(String, Result<f32, String>)>;
+
+/// Normalize every WAV file in `input_dir` into `output_dir`
+pub fn normalize_directory(input_dir: &str, output_dir: &str, target: Level, ceiling: Option<f32>) -> Result<BatchResults, String> {
+    std::fs::create_dir_all(output_dir).map_err(| e | e.to_string())?;
+
+    let mut paths: Vec<_> = std::fs::read_dir(input_dir)
+        .map_err(| e | e.to_string())?
+        .filter_map(| entry | entry.ok().map(| entry | entry.path()))
+        .filter(| path | {
+            path.is_file() && path.extension()
+                .map(| ext | ext.eq_ignore_ascii_case("wav"))
+                .unwrap_or(false)
+        })
+        .collect();
+
+    paths.sort();
+
+    let mut results = Vec::with_capacity(paths.len());
+    for path in paths {
+        let input = path.to_string_lossy().to_string();
+        let output = match path.file_name() {
+            Some(name) => Path::new(output_dir).join(name).to_string_lossy().to_string(),
+            None => continue
+        };
+
+        let result = normalize_file(&input, &output, target, ceiling);
+        results.push((input, result));
+    }
+
+    Ok(results)
+}
@@ -0,0 +1,79 @@
+use crate::float::*;
+
+/* Cumulative mean normalized difference below which a period is accepted */
+const YIN_THRESHOLD: f32 = 0.15;
+
+/// Fundamental frequency in Hz of a pitched signal, or `None` if it isn't periodic
+///
+/// Uses the YIN estimator on a window from the middle of the signal, which must
+/// be at least two periods of the lowest detectable pitch long.
+pub fn detect_pitch<S: Sample>(samples: &[S], sample_rate: u32, min_hz: f32, max_hz: f32) -> Option<f32> {
+    let rate = sample_rate as f32;
+    let max_period = (rate / f32::max(min_hz, 1.0)).ceil() as usize;
+    let window = usize::min(max_period * 2, samples.len());
+    let start = (samples.len() - window) / 2;
+
+    let mono: Vec<f32> = samples[start..start + window]
+        .iter()
+        .map(| s | s.mono().to_f32())
+        .collect();
+
+    yin(&mono, rate / max_hz, rate / min_hz).map(| period | rate / period)
+}
+
+/// Period in samples between `min_period` and `max_period`, or `None` if nothing is periodic enough
+///
+/// The signal must be at least twice `max_period` long.
+pub fn yin(samples: &[f32], min_period: f32, max_period: f32) -> Option<f32> {
+    let min_tau = usize::max(min_period.floor() as usize, 2);
+    let max_tau = usize::min(max_period.ceil() as usize, samples.len() / 2);
+    let window = samples.len() - max_tau;
+
+    if min_tau + 1 >= max_tau {
+        return None;
+    }
+
+    /* Cumulative mean normalized difference function */
+    let mut difference = vec![1.0; max_tau + 2];
+    let mut total = 0.0;
+
+    for tau in 1..=max_tau + 1 {
+        if tau + window > samples.len() {
+            difference[tau] = 1.0;
+            continue;
+        }
+
+        let mut sum = 0.0;
+        for j in 0..window {
+            let d = samples[j] - samples[j + tau];
+            sum += d * d;
+        }
+
+        total += sum;
+        difference[tau] = if total > 0.0 { sum * tau as f32 / total } else { 1.0 };
+    }
+
+    let mut tau = min_tau;
+    while tau < max_tau {
+        if difference[tau] < YIN_THRESHOLD {
+            while tau + 1 < max_tau && difference[tau + 1] < difference[tau] {
+                tau += 1;
+            }
+
+            /* Parabolic interpolation around the minimum */
+            let (a, b, c) = (difference[tau - 1], difference[tau], difference[tau + 1]);
+            let denominator = a - 2.0 * b + c;
+            let offset = if denominator.abs() > f32::EPSILON {
+                f32::clamp(0.5 * (a - c) / denominator, -0.5, 0.5)
+            } else {
+                0.0
+            };
+
+            return Some(tau as f32 + offset);
+        }
+
+        tau += 1;
+    }
+
+    None
+}
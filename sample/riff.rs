@@ -0,0 +1,95 @@
+use std::fs::File;
+use std::io::{Read, Write, Seek, SeekFrom};
+
+/// A raw chunk of a RIFF file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn is(&self, id: &[u8; 4]) -> bool {
+        &self.id == id
+    }
+}
+
+/// Read every chunk of a RIFF WAVE file
+pub fn read_chunks(path: &str) -> Result<Vec<Chunk>, String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(| mut file | file.read_to_end(&mut bytes))
+        .map_err(| e | e.to_string())?;
+
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(String::from("Not a RIFF WAVE file"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+
+    while offset + 8 <= bytes.len() {
+        let id = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+        let size = u32::from_le_bytes([bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]) as usize;
+        let start = offset + 8;
+        let end = usize::min(start + size, bytes.len());
+
+        chunks.push(Chunk {
+            id,
+            data: bytes[start..end].to_vec(),
+        });
+
+        /* Chunks are padded to an even length */
+        offset = start + size + (size & 1);
+    }
+
+    Ok(chunks)
+}
+
+/// Chunks other than the format and audio data, such as `LIST`, `bext`, `smpl` or `cue `
+pub fn read_metadata_chunks(path: &str) -> Result<Vec<Chunk>, String> {
+    Ok(read_chunks(path)?
+        .into_iter()
+        .filter(| chunk | !chunk.is(b"fmt ") && !chunk.is(b"data") && !chunk.is(b"fact"))
+        .collect())
+}
+
+/// Append chunks to the end of an existing RIFF file and update its size
+pub fn append_chunks(path: &str, chunks: &[Chunk]) -> Result<(), String> {
+    if chunks.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(| e | e.to_string())?;
+
+    let mut length = file.seek(SeekFrom::End(0)).map_err(| e | e.to_string())?;
+
+    /* Keep the existing content word aligned */
+    if length % 2 == 1 {
+        file.write_all(&[0]).map_err(| e | e.to_string())?;
+        length += 1;
+    }
+
+    for chunk in chunks {
+        let mut bytes = Vec::with_capacity(chunk.data.len() + 9);
+        bytes.extend_from_slice(&chunk.id);
+        bytes.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&chunk.data);
+
+        if chunk.data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        file.write_all(&bytes).map_err(| e | e.to_string())?;
+        length += bytes.len() as u64;
+    }
+
+    file.seek(SeekFrom::Start(4)).map_err(| e | e.to_string())?;
+    file.write_all(&((length - 8) as u32).to_le_bytes()).map_err(| e | e.to_string())?;
+
+    Ok(())
+}
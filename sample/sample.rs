@@ -8,6 +8,7 @@ use crate::float::*;
 
 use crate::Generator;
 use crate::Pitched;
+use crate::Playable;
 
 #[derive(Clone)]
 pub struct SampleFile<S: Sample> {
@@ -266,6 +267,17 @@ impl<T: Sample> Generator for SamplePlayer<T> {
     }
 }
 
+/// Plays the sample from its start on every note
+impl<T: Sample> Playable for SamplePlayer<T> {
+    fn note_on(&mut self, _pitch: f32, _pressure: f32) {
+        self.stop();
+        self.play();
+    }
+
+    /// Keeps playing, so an envelope can release it
+    fn note_off(&mut self) {}
+}
+
 pub struct PitchedSamplePlayer<S: Sample> {
     player: Converter<S::Float, S, SamplePlayer<S>, Linear<S>>,
     pitch: f32,
@@ -304,6 +316,8 @@ impl<T: Sample> Pitched for PitchedSamplePlayer<T> {
     }
 
     fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+
         match &self.sample {
             Some(sample) => {
                 match sample.pitch {
@@ -316,6 +330,19 @@ impl<T: Sample> Pitched for PitchedSamplePlayer<T> {
     }
 }
 
+impl<T: Sample> Playable for PitchedSamplePlayer<T> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.set_pitch(pitch);
+        self.player.note_on(pitch, pressure);
+    }
+
+    fn note_off(&mut self) {}
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+}
+
 impl<T: Sample> Deref for PitchedSamplePlayer<T> {
     type Target = SamplePlayer<T>;
 
@@ -0,0 +1,376 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering, fence};
+
+use crate::float::*;
+use crate::traits::*;
+use crate::routing::node::*;
+use crate::routing::param::*;
+use crate::fft::*;
+use crate::spectral::window::*;
+
+const FLOOR_DB: f32 = -144.0;
+
+pub fn analyzer<S: Sample>(size: usize, bands: usize) -> AudioNode<Analyzer<S>> {
+    AudioNode(Analyzer::new(size, bands))
+}
+
+/// Pass-through spectrum analyzer
+///
+/// Audio is passed through unchanged while smoothed, log-frequency spectra are
+/// published to an `AnalyzerFeed` that can be read from any thread without locks.
+pub struct Analyzer<S: Sample> {
+    fft: RealFft<S::Float>,
+    size: usize,
+    hop: usize,
+    sample_rate: u32,
+    min_hz: f32,
+    max_hz: f32,
+    smoothing: f32,
+    slope: f32,
+    release: f32,
+    hold: f32,
+    fall: f32,
+    window: Vec<S::Float>,
+    input: Vec<S::Float>,
+    frame: Vec<S::Float>,
+    spectrum: Vec<Complex<S::Float>>,
+    power: Vec<f32>,
+    bands: Vec<Band>,
+    magnitudes: Vec<f32>,
+    peaks: Vec<f32>,
+    held: Vec<f32>,
+    position: usize,
+    count: usize,
+    feed: AnalyzerFeed,
+}
+
+#[derive(Copy, Clone)]
+struct Band {
+    frequency: f32,
+    weight: f32,
+    start: usize,
+    end: usize,
+    position: f32,
+}
+
+impl<S: Sample> Analyzer<S> {
+    pub fn new(size: usize, bands: usize) -> Self {
+        let size = usize::max(size, 16);
+        let bands = usize::max(bands, 2);
+        let fft = RealFft::new(size);
+        let bins = fft.bins();
+
+        let mut analyzer = Self {
+            fft,
+            size,
+            hop: size / 4,
+            sample_rate: 44100,
+            min_hz: 20.0,
+            max_hz: 20000.0,
+            smoothing: 1.0 / 6.0,
+            slope: 0.0,
+            release: 0.3,
+            hold: 1.0,
+            fall: 20.0,
+            window: Vec::new(),
+            input: vec![S::Float::ZERO; size],
+            frame: vec![S::Float::ZERO; size],
+            spectrum: vec![Complex::ZERO; bins],
+            power: vec![0.0; bins],
+            bands: Vec::with_capacity(bands),
+            magnitudes: vec![FLOOR_DB; bands],
+            peaks: vec![FLOOR_DB; bands],
+            held: vec![0.0; bands],
+            position: 0,
+            count: 0,
+            feed: AnalyzerFeed::new(bands),
+        };
+
+        analyzer.set_window(Window::Hann);
+        analyzer.update_bands();
+        analyzer
+    }
+
+    /// Handle for reading spectra from another thread
+    pub fn feed(&self) -> AnalyzerFeed {
+        self.feed.clone()
+    }
+
+    pub fn set_window(&mut self, window: Window) {
+        /* Scale so a full scale sine reads 0 dB */
+        let window: Vec<S::Float> = window.generate(self.size);
+        let mut sum = S::Float::ZERO;
+        for w in &window {
+            sum += *w;
+        }
+
+        let scale = S::Float::from(2.0) / sum;
+        self.window = window.iter().map(| w | *w * scale).collect();
+    }
+
+    /// Number of samples between analysis frames
+    pub fn set_hop(&mut self, hop: usize) {
+        self.hop = usize::clamp(hop, 1, self.size);
+    }
+
+    /// Frequency range covered by the log spaced bands
+    pub fn set_range(&mut self, min_hz: f32, max_hz: f32) {
+        self.min_hz = f32::max(min_hz, 1.0);
+        self.max_hz = f32::max(max_hz, self.min_hz * 2.0);
+        self.update_bands();
+    }
+
+    /// Fractional octave smoothing bandwidth, for example `1.0 / 3.0`, or zero to disable
+    pub fn set_smoothing(&mut self, octaves: f32) {
+        self.smoothing = f32::max(octaves, 0.0);
+        self.update_bands();
+    }
+
+    /// Tilt in dB per octave around 1 kHz, 4.5 dB/oct approximates pink noise as flat
+    pub fn set_slope(&mut self, db_per_octave: f32) {
+        self.slope = db_per_octave;
+        self.update_bands();
+    }
+
+    /// Time in seconds for magnitudes to fall towards a quieter spectrum
+    pub fn set_release(&mut self, seconds: f32) {
+        self.release = f32::max(seconds, 0.0);
+    }
+
+    /// Time in seconds peaks are held before falling at `fall` dB per second
+    pub fn set_peak_hold(&mut self, seconds: f32, fall: f32) {
+        self.hold = f32::max(seconds, 0.0);
+        self.fall = f32::max(fall, 0.0);
+    }
+
+    fn update_bands(&mut self) {
+        let count = self.magnitudes.len();
+        let bin_hz = self.sample_rate as f32 / self.size as f32;
+        let last = self.power.len() - 1;
+        let ratio = self.max_hz / self.min_hz;
+        let width = f32::powf(2.0, self.smoothing / 2.0);
+
+        self.bands.clear();
+
+        for i in 0..count {
+            let frequency = self.min_hz * f32::powf(ratio, i as f32 / (count - 1) as f32);
+            let position = f32::min(frequency / bin_hz, last as f32);
+            let start = f32::ceil(frequency / width / bin_hz) as usize;
+            let end = usize::min(f32::floor(frequency * width / bin_hz) as usize, last);
+
+            self.bands.push(Band {
+                frequency,
+                weight: self.slope * f32::log2(frequency / 1000.0),
+                start,
+                end,
+                position,
+            });
+        }
+
+        self.feed.publish_frequencies(&self.bands);
+    }
+
+    fn analyze(&mut self) {
+        for (j, f) in self.frame.iter_mut().enumerate() {
+            *f = self.input[(self.position + j) % self.size] * self.window[j];
+        }
+
+        self.fft.forward(self.frame.as_slice(), self.spectrum.as_mut_slice());
+
+        for (p, s) in self.power.iter_mut().zip(&self.spectrum) {
+            *p = s.norm_sqr().to_f32();
+        }
+
+        let frame_time = self.hop as f32 / self.sample_rate as f32;
+        let release = if self.release > 0.0 { 1.0 - f32::exp(-frame_time / self.release) } else { 1.0 };
+
+        for (i, band) in self.bands.iter().enumerate() {
+            let power = if band.start <= band.end {
+                let bins = &self.power[band.start..=band.end];
+                bins.iter().sum::<f32>() / bins.len() as f32
+            } else {
+                let index = band.position as usize;
+                let next = usize::min(index + 1, self.power.len() - 1);
+                let t = band.position - index as f32;
+                self.power[index] + (self.power[next] - self.power[index]) * t
+            };
+
+            let db = f32::max(10.0 * f32::log10(f32::max(power, 1e-30)) + band.weight, FLOOR_DB);
+
+            let magnitude = &mut self.magnitudes[i];
+            if db > *magnitude {
+                *magnitude = db;
+            } else {
+                *magnitude += (db - *magnitude) * release;
+            }
+
+            if db >= self.peaks[i] {
+                self.peaks[i] = db;
+                self.held[i] = 0.0;
+            } else if self.held[i] < self.hold {
+                self.held[i] += frame_time;
+            } else {
+                self.peaks[i] = f32::max(self.peaks[i] - self.fall * frame_time, *magnitude);
+            }
+        }
+
+        self.feed.publish(&self.magnitudes, &self.peaks);
+    }
+}
+
+impl<S: Sample> Processor for Analyzer<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        self.input.fill(S::Float::ZERO);
+        self.magnitudes.fill(FLOOR_DB);
+        self.peaks.fill(FLOOR_DB);
+        self.held.fill(0.0);
+        self.position = 0;
+        self.count = 0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update_bands();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        self.input[self.position] = input.mono();
+        self.position = (self.position + 1) % self.size;
+        self.count += 1;
+
+        if self.count >= self.hop {
+            self.count = 0;
+            self.analyze();
+        }
+
+        input
+    }
+}
+
+impl<S: Sample> Param for Analyzer<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "smoothing" => self.set_smoothing(value),
+            "slope" => self.set_slope(value),
+            "release" => self.set_release(value),
+            "hold" => self.set_peak_hold(value, self.fall),
+            "fall" => self.set_peak_hold(self.hold, value),
+            _ => ()
+        }
+    }
+}
+
+/* Lock free feed */
+
+/// Latest analyzer output, magnitudes and peaks are in dB
+#[derive(Clone, Default)]
+pub struct Spectrum {
+    pub frequencies: Vec<f32>,
+    pub magnitudes: Vec<f32>,
+    pub peaks: Vec<f32>,
+}
+
+/// Reader side of an `Analyzer`
+///
+/// The analyzer writes through a sequence lock, so the audio thread never
+/// waits. Readers retry if they race with a write.
+#[derive(Clone)]
+pub struct AnalyzerFeed {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    sequence: AtomicU64,
+    frequencies: Vec<AtomicU32>,
+    magnitudes: Vec<AtomicU32>,
+    peaks: Vec<AtomicU32>,
+}
+
+impl AnalyzerFeed {
+    fn new(bands: usize) -> Self {
+        let init = | value: f32 | (0..bands).map(| _ | AtomicU32::new(value.to_bits())).collect();
+
+        Self {
+            shared: Arc::new(Shared {
+                sequence: AtomicU64::new(0),
+                frequencies: init(0.0),
+                magnitudes: init(FLOOR_DB),
+                peaks: init(FLOOR_DB),
+            })
+        }
+    }
+
+    pub fn bands(&self) -> usize {
+        self.shared.magnitudes.len()
+    }
+
+    /// Incremented twice for every published frame
+    pub fn sequence(&self) -> u64 {
+        self.shared.sequence.load(Ordering::Acquire)
+    }
+
+    /// Copy the latest spectrum, returns false if a consistent copy couldn't be made
+    pub fn read(&self, spectrum: &mut Spectrum) -> bool {
+        let shared = &self.shared;
+        let bands = self.bands();
+
+        spectrum.frequencies.resize(bands, 0.0);
+        spectrum.magnitudes.resize(bands, FLOOR_DB);
+        spectrum.peaks.resize(bands, FLOOR_DB);
+
+        for _ in 0..16 {
+            let before = shared.sequence.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+
+            for i in 0..bands {
+                spectrum.frequencies[i] = f32::from_bits(shared.frequencies[i].load(Ordering::Relaxed));
+                spectrum.magnitudes[i] = f32::from_bits(shared.magnitudes[i].load(Ordering::Relaxed));
+                spectrum.peaks[i] = f32::from_bits(shared.peaks[i].load(Ordering::Relaxed));
+            }
+
+            fence(Ordering::Acquire);
+
+            if shared.sequence.load(Ordering::Relaxed) == before {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn write<F: FnOnce(&Shared)>(&self, f: F) {
+        let shared = &self.shared;
+        let sequence = shared.sequence.load(Ordering::Relaxed);
+
+        shared.sequence.store(sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        f(shared);
+        shared.sequence.store(sequence + 2, Ordering::Release);
+    }
+
+    fn publish(&self, magnitudes: &[f32], peaks: &[f32]) {
+        self.write(| shared | {
+            for (dest, v) in shared.magnitudes.iter().zip(magnitudes) {
+                dest.store(v.to_bits(), Ordering::Relaxed);
+            }
+
+            for (dest, v) in shared.peaks.iter().zip(peaks) {
+                dest.store(v.to_bits(), Ordering::Relaxed);
+            }
+        });
+    }
+
+    fn publish_frequencies(&self, bands: &[Band]) {
+        self.write(| shared | {
+            for (dest, band) in shared.frequencies.iter().zip(bands) {
+                dest.store(band.frequency.to_bits(), Ordering::Relaxed);
+            }
+        });
+    }
+}
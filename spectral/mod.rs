@@ -0,0 +1,9 @@
+mod window;
+mod stft;
+mod analyzer;
+mod sinusoidal;
+
+pub use window::*;
+pub use stft::*;
+pub use analyzer::*;
+pub use sinusoidal::*;
@@ -0,0 +1,654 @@
+use std::sync::Arc;
+
+use crate::float::*;
+use crate::fft::*;
+use crate::math::random::*;
+use crate::routing::param::*;
+use crate::sample::*;
+use crate::spectral::window::*;
+use crate::traits::*;
+
+/* Bands of the residual noise envelope, log spaced above the first */
+const NOISE_BANDS: usize = 24;
+
+/* Upper edge of the lowest noise band in Hz */
+const NOISE_LOWEST: f32 = 100.0;
+
+/* Half width in bins of a peak's main lobe, excluded from the noise estimate */
+const MAIN_LOBE: usize = 4;
+
+/* Pitch assumed for models of unpitched samples */
+const DEFAULT_PITCH: f32 = 261.63;
+
+/// A point on a partial track
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TrackPoint {
+    pub frequency: f32,
+    pub amplitude: f32,
+    /// Phase in radians at the frame centre
+    pub phase: f32,
+}
+
+/// A partial followed across consecutive frames
+#[derive(Clone, PartialEq, Debug)]
+pub struct Track {
+    /// Frame of the first point
+    pub start: usize,
+    pub points: Vec<TrackPoint>,
+}
+
+impl Track {
+    /// Frame after the last point
+    pub fn end(&self) -> usize {
+        self.start + self.points.len()
+    }
+
+    /// Point at `frame`, silent at the last known frequency outside the track
+    fn point(&self, frame: isize) -> TrackPoint {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+
+        if frame < self.start as isize {
+            TrackPoint { amplitude: 0.0, ..first }
+        } else if frame >= self.end() as isize {
+            TrackPoint { amplitude: 0.0, ..last }
+        } else {
+            self.points[frame as usize - self.start]
+        }
+    }
+}
+
+/// Sinusoidal model of a sound, partial tracks plus a residual noise envelope
+#[derive(Clone, PartialEq, Debug)]
+pub struct SinusoidalModel {
+    pub sample_rate: u32,
+    /// Samples between frames
+    pub hop: usize,
+    pub frames: usize,
+    /// Fundamental of the analysed sound, if it has one
+    pub pitch: Option<f32>,
+    /// Tracks sorted by their first frame
+    pub tracks: Vec<Track>,
+    /// Edges in Hz of the noise bands
+    pub noise_bands: Vec<f32>,
+    /// Per frame and band, the RMS level per unit bandwidth of the residual
+    pub noise: Vec<Vec<f32>>,
+}
+
+impl SinusoidalModel {
+    /// Length in samples at the model's sample rate
+    pub fn len(&self) -> usize {
+        self.frames * self.hop
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Noise levels interpolated at a fractional `frame`
+    fn noise_at(&self, frame: f32, levels: &mut [f32]) {
+        let i = frame.floor() as usize;
+        let t = frame - frame.floor();
+
+        for (b, level) in levels.iter_mut().enumerate() {
+            let a = self.noise.get(i).map_or(0.0, | noise | noise[b]);
+            let c = self.noise.get(i + 1).map_or(0.0, | noise | noise[b]);
+            *level = a + (c - a) * t;
+        }
+    }
+}
+
+/// Analysis of samples into a `SinusoidalModel`
+///
+/// Each frame's spectral peaks are found with parabolic interpolation, and
+/// peaks in consecutive frames close enough in frequency are joined into
+/// tracks. Whatever the peaks don't explain is kept as the residual noise
+/// envelope.
+pub struct SinusoidalAnalyzer {
+    size: usize,
+    hop: usize,
+    window: Window,
+    max_partials: usize,
+    threshold: f32,
+    min_length: usize,
+}
+
+impl SinusoidalAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            size: 2048,
+            hop: 256,
+            window: Window::BlackmanHarris,
+            max_partials: 100,
+            threshold: -80.0,
+            min_length: 8,
+        }
+    }
+
+    /// Analysis frame size in samples, long enough for a few periods of the lowest partial
+    pub fn set_size(&mut self, size: usize) {
+        self.size = usize::max(size + size % 2, 16);
+    }
+
+    /// Samples between frames
+    pub fn set_hop(&mut self, hop: usize) {
+        self.hop = usize::max(hop, 1);
+    }
+
+    /// Most peaks kept per frame, the loudest first
+    pub fn set_max_partials(&mut self, max_partials: usize) {
+        self.max_partials = max_partials;
+    }
+
+    /// Level in dBFS below which peaks are ignored
+    pub fn set_threshold(&mut self, db: f32) {
+        self.threshold = db;
+    }
+
+    /// Frames a track must last to be kept, shorter ones are left in the residual
+    pub fn set_min_length(&mut self, frames: usize) {
+        self.min_length = usize::max(frames, 1);
+    }
+
+    pub fn analyze<S: Sample>(&self, sample: &SampleFile<S>, sample_rate: u32) -> SinusoidalModel {
+        let end = usize::min(sample.end, sample.len());
+        let start = usize::min(sample.start, end);
+        let signal: Vec<f32> = sample.as_slice()[start..end]
+            .iter()
+            .map(| s | s.mono().to_f32())
+            .collect();
+
+        let size = self.size;
+        let window: Vec<f32> = self.window.generate(size);
+        let window_sum: f32 = window.iter().sum();
+        let window_power: f32 = window.iter().map(| w | w * w).sum();
+        let bin_hz = sample_rate as f32 / size as f32;
+
+        let mut fft = RealFft::<f32>::new(size);
+        let mut frame = vec![0.0; size];
+        let mut spectrum = vec![Complex::new(); fft.bins()];
+        let mut excluded = vec![false; fft.bins()];
+
+        let nyquist = sample_rate as f32 * 0.5;
+        let noise_bands: Vec<f32> = std::iter::once(0.0)
+            .chain((0..NOISE_BANDS).map(| b | NOISE_LOWEST * f32::powf(nyquist / NOISE_LOWEST, b as f32 / (NOISE_BANDS - 1) as f32)))
+            .collect();
+
+        let frames = signal.len() / self.hop + 1;
+        let mut peaks = Vec::with_capacity(frames);
+
+        for m in 0..frames {
+            self.transform(&signal, m, &window, &mut fft, &mut frame, &mut spectrum);
+            peaks.push(self.find_peaks(&spectrum, window_sum, bin_hz));
+        }
+
+        let tracks = self.track(peaks);
+
+        /* The residual is what's left once the main lobes of the kept tracks are taken out */
+        let mut excluded_bins: Vec<Vec<usize>> = vec![Vec::new(); frames];
+        for track in &tracks {
+            for (m, point) in track.points.iter().enumerate() {
+                excluded_bins[track.start + m].push((point.frequency / bin_hz).round() as usize);
+            }
+        }
+
+        let mut noise = Vec::with_capacity(frames);
+        for (m, bins) in excluded_bins.iter().enumerate() {
+            self.transform(&signal, m, &window, &mut fft, &mut frame, &mut spectrum);
+
+            excluded.fill(false);
+            for bin in bins {
+                let low = bin.saturating_sub(MAIN_LOBE);
+                let high = usize::min(bin + MAIN_LOBE, excluded.len() - 1);
+                excluded[low..=high].fill(true);
+            }
+
+            noise.push(
+                noise_bands.windows(2)
+                    .map(| edges | {
+                        let low = (edges[0] / bin_hz).ceil() as usize;
+                        let high = usize::min((edges[1] / bin_hz).ceil() as usize, spectrum.len());
+
+                        let (sum, count) = (low..high)
+                            .filter(| k | !excluded[*k])
+                            .fold((0.0, 0), | (sum, count), k | (sum + spectrum[k].norm_sqr(), count + 1));
+
+                        /* White noise of variance v has an expected bin power of v times the window's power */
+                        if count > 0 { f32::sqrt(sum / count as f32 / window_power) } else { 0.0 }
+                    })
+                    .collect()
+            );
+        }
+
+        SinusoidalModel {
+            sample_rate,
+            hop: self.hop,
+            frames,
+            pitch: sample.pitch.or_else(|| detect_pitch(&signal, sample_rate, 40.0, 2000.0)),
+            tracks,
+            noise_bands,
+            noise,
+        }
+    }
+
+    /// Spectrum of frame `m`, zero phase windowed so the frame centre is at index 0 and peak phases are phases at the centre
+    fn transform(&self, signal: &[f32], m: usize, window: &[f32], fft: &mut RealFft<f32>, frame: &mut [f32], spectrum: &mut [Complex<f32>]) {
+        let size = window.len();
+        let half = size / 2;
+
+        for (j, w) in window.iter().enumerate() {
+            let n = (m * self.hop + j) as isize - half as isize;
+            let x = if n >= 0 { signal.get(n as usize).copied().unwrap_or(0.0) } else { 0.0 };
+            frame[(j + half) % size] = x * w;
+        }
+
+        fft.forward(frame, spectrum);
+    }
+
+    /// Loudest local maxima above the threshold, with interpolated frequency and amplitude
+    fn find_peaks(&self, spectrum: &[Complex<f32>], window_sum: f32, bin_hz: f32) -> Vec<TrackPoint> {
+        /* A full scale sinusoid peaks at half the window's sum */
+        let scale = 2.0 / window_sum;
+        let db: Vec<f32> = spectrum.iter()
+            .map(| c | 20.0 * f32::log10(f32::max(c.norm() * scale, 1e-12)))
+            .collect();
+
+        let mut peaks: Vec<TrackPoint> = (1..db.len().saturating_sub(1))
+            .filter(| k | db[*k] > self.threshold && db[*k] > db[k - 1] && db[*k] >= db[k + 1])
+            .map(| k | {
+                let (a, b, c) = (db[k - 1], db[k], db[k + 1]);
+                let denominator = a - 2.0 * b + c;
+                let p = if denominator.abs() > 1e-9 { 0.5 * (a - c) / denominator } else { 0.0 };
+
+                TrackPoint {
+                    frequency: (k as f32 + p) * bin_hz,
+                    amplitude: f32::powf(10.0, (b - 0.25 * (a - c) * p) / 20.0),
+                    phase: spectrum[k].arg(),
+                }
+            })
+            .collect();
+
+        peaks.sort_by(| a, b | b.amplitude.total_cmp(&a.amplitude));
+        peaks.truncate(self.max_partials);
+        peaks
+    }
+
+    /// Join peaks of consecutive frames into tracks, the loudest tracks choosing first
+    fn track(&self, frames: Vec<Vec<TrackPoint>>) -> Vec<Track> {
+        let mut tracks: Vec<Track> = Vec::new();
+        let mut active: Vec<usize> = Vec::new();
+
+        for (m, peaks) in frames.into_iter().enumerate() {
+            let mut used = vec![false; peaks.len()];
+            let mut next = Vec::with_capacity(peaks.len());
+
+            active.sort_by(| a, b | {
+                let a = tracks[*a].points.last().unwrap().amplitude;
+                let b = tracks[*b].points.last().unwrap().amplitude;
+                b.total_cmp(&a)
+            });
+
+            for &t in &active {
+                let last = *tracks[t].points.last().unwrap();
+                let limit = f32::max(last.frequency * 0.03, 10.0);
+
+                let closest = peaks.iter()
+                    .enumerate()
+                    .filter(| (i, _) | !used[*i])
+                    .map(| (i, peak) | (i, f32::abs(peak.frequency - last.frequency)))
+                    .filter(| (_, distance) | *distance <= limit)
+                    .min_by(| a, b | a.1.total_cmp(&b.1));
+
+                if let Some((i, _)) = closest {
+                    used[i] = true;
+                    tracks[t].points.push(peaks[i]);
+                    next.push(t);
+                }
+            }
+
+            for (i, peak) in peaks.iter().enumerate() {
+                if !used[i] {
+                    next.push(tracks.len());
+                    tracks.push(Track { start: m, points: vec![*peak] });
+                }
+            }
+
+            active = next;
+        }
+
+        tracks.retain(| track | track.points.len() >= self.min_length);
+        tracks.sort_by_key(| track | track.start);
+        tracks
+    }
+}
+
+impl Default for SinusoidalAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An oscillator following one track
+#[derive(Copy, Clone)]
+struct TrackVoice {
+    track: usize,
+    phase: f32,
+    /// Frequency offset in Hz reaching the next frame's analysed phase
+    correction: f32,
+}
+
+/// Resynthesis of a `SinusoidalModel` with independent pitch and time scaling
+///
+/// Partials are oscillators following their tracks, with frequency and
+/// amplitude interpolated between frames. The residual is shaped white noise
+/// overlap-added with a sine window, following the model's noise envelope.
+/// Pitch scales only the partials, so breath and bow noise keep their colour.
+pub struct SinusoidalPlayer<F: Float> {
+    model: Arc<SinusoidalModel>,
+    ratio: f32,
+    time_scale: f32,
+    sines: f32,
+    noise_gain: f32,
+    seed: u64,
+
+    sample_rate: u32,
+    position: f32,
+    playing: bool,
+    next_track: usize,
+    frame: isize,
+    voices: Vec<TrackVoice>,
+
+    random: Random,
+    fft: RealFft<f32>,
+    noise_hop: usize,
+    noise_window: Vec<f32>,
+    noise_levels: Vec<f32>,
+    noise_spectrum: Vec<Complex<f32>>,
+    noise_frame: Vec<f32>,
+    noise_output: Vec<f32>,
+    noise_index: usize,
+    _phantom: std::marker::PhantomData<F>,
+}
+
+impl<F: Float> SinusoidalPlayer<F> {
+    pub fn from(model: Arc<SinusoidalModel>) -> Self {
+        let mut player = Self {
+            model,
+            ratio: 1.0,
+            time_scale: 1.0,
+            sines: 1.0,
+            noise_gain: 1.0,
+            seed: 0,
+            sample_rate: 44100,
+            position: 0.0,
+            playing: false,
+            next_track: 0,
+            frame: -1,
+            voices: Vec::new(),
+            random: Random::new(0),
+            fft: RealFft::new(2),
+            noise_hop: 1,
+            noise_window: Vec::new(),
+            noise_levels: vec![0.0; NOISE_BANDS],
+            noise_spectrum: Vec::new(),
+            noise_frame: Vec::new(),
+            noise_output: Vec::new(),
+            noise_index: 0,
+            _phantom: std::marker::PhantomData,
+        };
+
+        let sample_rate = player.model.sample_rate;
+        player.prepare(sample_rate, 1);
+        player
+    }
+
+    pub fn model(&self) -> &Arc<SinusoidalModel> {
+        &self.model
+    }
+
+    pub fn set_model(&mut self, model: Arc<SinusoidalModel>) {
+        self.model = model;
+        self.prepare(self.sample_rate, 1);
+    }
+
+    /// Frequency ratio of the partials
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = f32::max(ratio, 0.0);
+    }
+
+    /// Duration multiplier, 2 plays at half speed without changing the pitch
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = f32::max(scale, 0.01);
+    }
+
+    /// Gain of the partials
+    pub fn set_sines(&mut self, gain: f32) {
+        self.sines = gain;
+    }
+
+    /// Gain of the residual noise
+    pub fn set_noise(&mut self, gain: f32) {
+        self.noise_gain = gain;
+    }
+
+    /// Seed of the residual noise
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.random = Random::new(seed);
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.reset();
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Playback position in model frames
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Start the tracks that begin by `frame`, fading in from the frame before
+    fn start_tracks(&mut self, frame: isize) {
+        let tracks = &self.model.tracks;
+
+        while self.next_track < tracks.len() && tracks[self.next_track].start as isize <= frame + 1 {
+            let track = &tracks[self.next_track];
+            let first = track.points[0];
+
+            /* Wind the phase back so it matches the analysis when the first point is reached */
+            let samples_to_start = (track.start as f32 - self.position) * self.model.hop as f32 * self.time_scale
+                * self.sample_rate as f32 / self.model.sample_rate as f32;
+            let step = 2.0 * std::f32::consts::PI * first.frequency * self.ratio / self.sample_rate as f32;
+
+            self.voices.push(TrackVoice {
+                track: self.next_track,
+                phase: first.phase - step * samples_to_start,
+                correction: 0.0,
+            });
+
+            self.next_track += 1;
+        }
+    }
+
+    fn generate_sines(&mut self) -> f32 {
+        use std::f32::consts::TAU;
+
+        let frame = self.position.floor() as isize;
+        let t = self.position - self.position.floor();
+        let tracks = &self.model.tracks;
+        let rate = self.sample_rate as f32;
+        let hop = self.model.hop as f32;
+        let step = TAU * self.ratio / rate;
+        let nyquist = rate * 0.5;
+        let mut output = 0.0;
+
+        /* Unscaled playback follows the analysed phases, so the partials line up with the original */
+        let faithful = self.ratio == 1.0 && self.time_scale == 1.0 && self.sample_rate == self.model.sample_rate;
+        let crossed = frame != self.frame;
+        self.frame = frame;
+
+        self.voices.retain(| voice | (tracks[voice.track].end() as isize) >= frame);
+
+        for voice in self.voices.iter_mut() {
+            let track = &tracks[voice.track];
+            let a = track.point(frame);
+            let b = track.point(frame + 1);
+
+            if crossed {
+                voice.correction = 0.0;
+
+                if faithful && frame >= track.start as isize && frame + 1 < track.end() as isize {
+                    voice.phase = a.phase + TAU * a.frequency * t * hop / rate;
+
+                    let expected = std::f32::consts::PI * (a.frequency + b.frequency) * hop / rate;
+                    let advance = b.phase - a.phase;
+                    let turns = ((expected - advance) / TAU).round();
+                    voice.correction = (advance + TAU * turns - expected) * rate / (TAU * hop);
+                }
+            }
+
+            let frequency = a.frequency + (b.frequency - a.frequency) * t + voice.correction;
+            let amplitude = a.amplitude + (b.amplitude - a.amplitude) * t;
+
+            if frequency * self.ratio < nyquist {
+                output += amplitude * f32::cos(voice.phase);
+            }
+
+            voice.phase = (voice.phase + step * frequency).rem_euclid(TAU);
+        }
+
+        output
+    }
+
+    /// Overlap-add the next frame of noise shaped by the envelope at the current position
+    fn noise_frame(&mut self) {
+        let size = self.noise_window.len();
+        let bin_hz = self.sample_rate as f32 / size as f32;
+
+        self.model.noise_at(self.position, &mut self.noise_levels);
+
+        /* Bins of magnitude sigma * sqrt(N) with random phases give noise of variance sigma^2 after the 1 / N inverse */
+        let scale = f32::sqrt(size as f32);
+        let mut band = 0;
+        for (k, bin) in self.noise_spectrum.iter_mut().enumerate() {
+            let frequency = k as f32 * bin_hz;
+            while band + 1 < NOISE_BANDS && frequency >= self.model.noise_bands[band + 1] {
+                band += 1;
+            }
+
+            let phase = self.random.uniform() as f32 * 2.0 * std::f32::consts::PI;
+            *bin = Complex::from_polar(self.noise_levels[band] * scale, phase);
+        }
+
+        self.fft.inverse(self.noise_spectrum.as_slice(), self.noise_frame.as_mut_slice());
+
+        /* The squared sine window sums to 1 at 50% overlap, so the noise power stays constant */
+        for j in 0..size {
+            let index = (self.noise_index + j) % size;
+            self.noise_output[index] += self.noise_frame[j] * self.noise_window[j];
+        }
+    }
+}
+
+impl<F: Float> Generator for SinusoidalPlayer<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.position = 0.0;
+        self.next_track = 0;
+        self.frame = -1;
+        self.voices.clear();
+        self.random = Random::new(self.seed);
+        self.noise_output.fill(0.0);
+        self.noise_index = 0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+
+        /* Noise frames match the model's hop in time, at the playback rate */
+        let hop = (self.model.hop as f32 * sample_rate as f32 / self.model.sample_rate as f32).round() as usize;
+        self.noise_hop = usize::max(hop, 1);
+
+        let size = self.noise_hop * 2;
+        self.fft = RealFft::new(size);
+        self.noise_window = Window::Sine.generate(size);
+        self.noise_spectrum = vec![Complex::new(); self.fft.bins()];
+        self.noise_frame = vec![0.0; size];
+        self.noise_output = vec![0.0; size];
+
+        self.reset();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        if !self.playing {
+            return F::ZERO;
+        }
+
+        if self.noise_index.is_multiple_of(self.noise_hop) {
+            self.noise_frame();
+        }
+
+        let frame = self.position.floor() as isize;
+        self.start_tracks(frame);
+
+        let sines = self.generate_sines() * self.sines;
+
+        let size = self.noise_output.len();
+        let noise = self.noise_output[self.noise_index] * self.noise_gain;
+        self.noise_output[self.noise_index] = 0.0;
+        self.noise_index = (self.noise_index + 1) % size;
+
+        self.position += self.model.sample_rate as f32 / (self.sample_rate as f32 * self.model.hop as f32 * self.time_scale);
+        if self.position > self.model.frames as f32 {
+            self.playing = false;
+        }
+
+        F::from(sines + noise)
+    }
+}
+
+impl<F: Float> Pitched for SinusoidalPlayer<F> {
+    fn get_pitch(&self) -> f32 {
+        self.model.pitch.unwrap_or(DEFAULT_PITCH) * self.ratio
+    }
+
+    /// Pitch relative to the model's fundamental, or middle C if it has none
+    fn set_pitch(&mut self, hz: f32) {
+        self.set_ratio(hz / self.model.pitch.unwrap_or(DEFAULT_PITCH));
+    }
+}
+
+impl<F: Float> Playable for SinusoidalPlayer<F> {
+    fn note_on(&mut self, pitch: f32, _pressure: f32) {
+        self.stop();
+        self.set_pitch(pitch);
+        self.play();
+    }
+
+    fn note_off(&mut self) {}
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+}
+
+impl<F: Float> Param for SinusoidalPlayer<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "ratio" => self.set_ratio(value),
+            "time" => self.set_time_scale(value),
+            "sines" => self.set_sines(value),
+            "noise" => self.set_noise(value),
+            _ => ()
+        }
+    }
+}
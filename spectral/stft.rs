@@ -0,0 +1,194 @@
+use crate::float::*;
+use crate::traits::*;
+use crate::routing::node::*;
+use crate::fft::*;
+use crate::spectral::window::*;
+
+/// Per-frame callback of a short-time Fourier transform
+///
+/// Receives the `size / 2 + 1` bins of each analysis frame and may modify them
+/// in place before they are resynthesised.
+pub trait SpectralProcessor<F: Float> {
+    fn prepare(&mut self, _sample_rate: u32, _size: usize, _hop: usize) {}
+    fn process_spectrum(&mut self, spectrum: &mut [Complex<F>]);
+}
+
+impl<F: Float, T: FnMut(&mut [Complex<F>])> SpectralProcessor<F> for T {
+    fn process_spectrum(&mut self, spectrum: &mut [Complex<F>]) {
+        (self)(spectrum)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Resynthesis {
+    /// Frames are windowed on analysis only
+    OverlapAdd,
+    /// Frames are windowed on analysis and again on resynthesis
+    WeightedOverlapAdd,
+}
+
+pub fn stft<F: Float, P: SpectralProcessor<F>>(processor: P, size: usize, hop: usize, window: Window) -> AudioNode<Stft<F, P>> {
+    AudioNode(Stft::new(processor, size, hop, window))
+}
+
+/// Streaming short-time Fourier transform processor
+///
+/// Every `hop` samples the last `size` input samples are windowed, transformed,
+/// passed to the spectral callback and overlap-added back into the output. The
+/// output is delayed by `latency()` samples.
+pub struct Stft<F: Float, P: SpectralProcessor<F>> {
+    processor: P,
+    fft: RealFft<F>,
+    size: usize,
+    hop: usize,
+    window: Window,
+    resynthesis: Resynthesis,
+    analysis: Vec<F>,
+    synthesis: Vec<F>,
+    input: Vec<F>,
+    output: Vec<F>,
+    frame: Vec<F>,
+    spectrum: Vec<Complex<F>>,
+    position: usize,
+    count: usize,
+}
+
+impl<F: Float, P: SpectralProcessor<F>> Stft<F, P> {
+    pub fn new(processor: P, size: usize, hop: usize, window: Window) -> Self {
+        let size = usize::max(size, 2);
+        let hop = usize::clamp(hop, 1, size);
+        let fft = RealFft::new(size);
+        let bins = fft.bins();
+
+        let mut stft = Self {
+            processor,
+            fft,
+            size,
+            hop,
+            window,
+            resynthesis: Resynthesis::WeightedOverlapAdd,
+            analysis: Vec::new(),
+            synthesis: Vec::new(),
+            input: vec![F::ZERO; size],
+            output: vec![F::ZERO; size],
+            frame: vec![F::ZERO; size],
+            spectrum: vec![Complex::ZERO; bins],
+            position: 0,
+            count: 0,
+        };
+
+        stft.update_windows();
+        stft
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn resynthesis(&self) -> Resynthesis {
+        self.resynthesis
+    }
+
+    pub fn set_window(&mut self, window: Window) {
+        self.window = window;
+        self.update_windows();
+    }
+
+    pub fn set_resynthesis(&mut self, resynthesis: Resynthesis) {
+        self.resynthesis = resynthesis;
+        self.update_windows();
+    }
+
+    /// Delay in samples between input and resynthesised output
+    pub fn latency(&self) -> usize {
+        self.size - 1
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    fn update_windows(&mut self) {
+        self.analysis = self.window.generate(self.size);
+
+        let synthesis: Vec<F> = match self.resynthesis {
+            Resynthesis::OverlapAdd => vec![F::from(1.0); self.size],
+            Resynthesis::WeightedOverlapAdd => self.analysis.clone(),
+        };
+
+        /* Divide out the summed window overlap so an unmodified spectrum reconstructs exactly */
+        let mut overlap = vec![F::ZERO; self.hop];
+        for i in 0..self.size {
+            overlap[i % self.hop] += self.analysis[i] * synthesis[i];
+        }
+
+        self.synthesis = synthesis
+            .iter()
+            .enumerate()
+            .map(| (i, w) | {
+                let gain = overlap[i % self.hop];
+                if gain > F::from(1e-6) { *w / gain } else { F::ZERO }
+            })
+            .collect();
+    }
+
+    /// Analyse the last `size` inputs and add the result to the output starting at `slot`
+    fn process_frame(&mut self, slot: usize) {
+        for (j, f) in self.frame.iter_mut().enumerate() {
+            *f = self.input[(self.position + j) % self.size] * self.analysis[j];
+        }
+
+        self.fft.forward(self.frame.as_slice(), self.spectrum.as_mut_slice());
+        self.processor.process_spectrum(&mut self.spectrum);
+        self.fft.inverse(self.spectrum.as_slice(), self.frame.as_mut_slice());
+
+        for (j, f) in self.frame.iter().enumerate() {
+            self.output[(slot + j) % self.size] += *f * self.synthesis[j];
+        }
+    }
+}
+
+impl<F: Float, P: SpectralProcessor<F>> Processor for Stft<F, P> {
+    type Input = F;
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.input.fill(F::ZERO);
+        self.output.fill(F::ZERO);
+        self.position = 0;
+        self.count = 0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.processor.prepare(sample_rate, self.size, self.hop);
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        /* Input and output share an index, the output slot is reused once it has been read */
+        let slot = self.position;
+        self.input[slot] = input;
+        self.position = (self.position + 1) % self.size;
+        self.count += 1;
+
+        if self.count == self.hop {
+            self.count = 0;
+            self.process_frame(slot);
+        }
+
+        let output = self.output[slot];
+        self.output[slot] = F::ZERO;
+        output
+    }
+}
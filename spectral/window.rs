@@ -0,0 +1,113 @@
+use crate::buffers::*;
+use crate::float::*;
+
+/// Analysis and design windows
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+    /// Kaiser window with shape parameter `beta`
+    Kaiser(f64),
+    FlatTop,
+    /// Tukey window with taper fraction `alpha` between 0 (rectangular) and 1 (Hann)
+    Tukey(f64),
+    Sine,
+}
+
+impl Window {
+    /// Periodic window of `size` points, suited to spectral analysis and overlap-add
+    pub fn generate<F: Float>(&self, size: usize) -> Vec<F> {
+        let mut window = vec![F::ZERO; size];
+        self.fill_periodic(window.as_mut_slice());
+        window
+    }
+
+    /// Symmetric window of `size` points, suited to filter design
+    pub fn generate_symmetric<F: Float>(&self, size: usize) -> Vec<F> {
+        let mut window = vec![F::ZERO; size];
+        self.fill_symmetric(window.as_mut_slice());
+        window
+    }
+
+    pub fn fill_periodic<B: Block + ?Sized>(&self, block: &mut B) where B::Item: Float {
+        let size = block.len();
+        for (i, v) in block.as_slice_mut().iter_mut().enumerate() {
+            *v = B::Item::from_f64(self.value(i as f64, size as f64));
+        }
+    }
+
+    pub fn fill_symmetric<B: Block + ?Sized>(&self, block: &mut B) where B::Item: Float {
+        let size = block.len();
+        if size == 1 {
+            block.as_slice_mut()[0] = B::Item::from(1.0);
+            return;
+        }
+
+        for (i, v) in block.as_slice_mut().iter_mut().enumerate() {
+            *v = B::Item::from_f64(self.value(i as f64, (size - 1) as f64));
+        }
+    }
+
+    /// Window value at position `i` of a window with period `n`
+    fn value(&self, i: f64, n: f64) -> f64 {
+        use std::f64::consts::PI;
+
+        let x = i / n;
+        let cosine = | a: &[f64] | {
+            a.iter()
+                .enumerate()
+                .map(| (k, a) | {
+                    let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                    sign * a * f64::cos(2.0 * PI * k as f64 * x)
+                })
+                .sum::<f64>()
+        };
+
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => cosine(&[0.5, 0.5]),
+            Window::Hamming => cosine(&[0.54, 0.46]),
+            Window::Blackman => cosine(&[0.42, 0.5, 0.08]),
+            Window::BlackmanHarris => cosine(&[0.35875, 0.48829, 0.14128, 0.01168]),
+            Window::FlatTop => cosine(&[0.21557895, 0.41663158, 0.277263158, 0.083578947, 0.006947368]),
+            Window::Sine => f64::sin(PI * x),
+            Window::Kaiser(beta) => {
+                let r = 2.0 * x - 1.0;
+                bessel_i0(beta * f64::sqrt(f64::max(1.0 - r * r, 0.0))) / bessel_i0(*beta)
+            },
+            Window::Tukey(alpha) => {
+                let alpha = f64::clamp(*alpha, 0.0, 1.0);
+                let edge = alpha / 2.0;
+
+                if alpha == 0.0 || (x >= edge && x <= 1.0 - edge) {
+                    1.0
+                } else if x < edge {
+                    0.5 * (1.0 - f64::cos(PI * x / edge))
+                } else {
+                    0.5 * (1.0 - f64::cos(PI * (1.0 - x) / edge))
+                }
+            }
+        }
+    }
+}
+
+/// Zeroth order modified Bessel function of the first kind
+pub fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+
+    for k in 1..64 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+
+        if term < sum * 1e-17 {
+            break;
+        }
+    }
+
+    sum
+}
@@ -0,0 +1,303 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/* Samples between corrections of the rotators' magnitude drift */
+const RENORMALIZE_INTERVAL: usize = 256;
+
+/* Partials fade out over this fraction of the band below Nyquist rather than dropping out */
+const NYQUIST_FADE: f32 = 0.1;
+
+/// A sine partial of an additive voice
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Partial {
+    /// Frequency relative to the fundamental
+    pub ratio: f32,
+    pub amplitude: f32,
+    /// Starting phase in cycles
+    pub phase: f32,
+}
+
+impl Partial {
+    pub fn new(ratio: f32, amplitude: f32, phase: f32) -> Self {
+        Self { ratio, amplitude, phase }
+    }
+}
+
+/// Partials of an additive voice
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct PartialTable {
+    pub partials: Vec<Partial>,
+}
+
+impl PartialTable {
+    pub fn from(partials: Vec<Partial>) -> Self {
+        Self { partials }
+    }
+
+    /// Harmonics 1 to `count` with amplitudes from `amplitude(harmonic)`
+    pub fn harmonics<A: Fn(usize) -> f32>(count: usize, amplitude: A) -> Self {
+        Self {
+            partials: (1..=count)
+                .map(| n | Partial::new(n as f32, amplitude(n), 0.0))
+                .collect()
+        }
+    }
+
+    /// Sawtooth from `count` harmonics
+    pub fn saw(count: usize) -> Self {
+        Self::harmonics(count, | n | 2.0 / (std::f32::consts::PI * n as f32))
+    }
+
+    /// Square from the odd harmonics up to `count`
+    pub fn square(count: usize) -> Self {
+        Self::harmonics(count, | n | if n % 2 == 1 { 4.0 / (std::f32::consts::PI * n as f32) } else { 0.0 })
+    }
+
+    /// Tonewheel organ from nine drawbar levels from 0 to 1, in the usual 16' to 1' order
+    pub fn organ(drawbars: [f32; 9]) -> Self {
+        let ratios = [0.5, 1.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0];
+
+        Self {
+            partials: ratios.iter()
+                .zip(drawbars)
+                .map(| (ratio, level) | Partial::new(*ratio, f32::clamp(level, 0.0, 1.0) / 9.0, 0.0))
+                .collect()
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(| e | e.to_string())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(| e | e.to_string())
+    }
+
+    /// Load a table saved as JSON, such as `{"partials": [{"ratio": 1.0, "amplitude": 1.0, "phase": 0.0}]}`
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(| e | e.to_string())?;
+        Self::from_json(&json)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()?).map_err(| e | e.to_string())
+    }
+}
+
+/// Additive oscillator summing up to hundreds of sine partials
+///
+/// Each partial is a complex rotator, so a sample costs a complex multiply
+/// per audible partial. The rotators run in order of ratio so those above
+/// Nyquist are skipped entirely, and bends retune the rotators without
+/// resetting their phases.
+pub struct Additive<F: Float> {
+    partials: Vec<Partial>,
+    /// Indices into `partials` sorted by ratio, in the order of the rotators
+    order: Vec<usize>,
+    pitch: f32,
+    tilt: f32,
+    odd: f32,
+    even: f32,
+
+    sample_rate: u32,
+    active: usize,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    step_real: Vec<f32>,
+    step_imaginary: Vec<f32>,
+    gains: Vec<f32>,
+    counter: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Additive<F> {
+    pub fn from(table: PartialTable) -> Self {
+        let mut additive = Self {
+            partials: Vec::new(),
+            order: Vec::new(),
+            pitch: 220.0,
+            tilt: 0.0,
+            odd: 1.0,
+            even: 1.0,
+            sample_rate: 44100,
+            active: 0,
+            real: Vec::new(),
+            imaginary: Vec::new(),
+            step_real: Vec::new(),
+            step_imaginary: Vec::new(),
+            gains: Vec::new(),
+            counter: 0,
+            _phantom: PhantomData,
+        };
+
+        additive.set_table(table);
+        additive
+    }
+
+    /// Partials in the order of the table they came from
+    pub fn partials(&self) -> &[Partial] {
+        &self.partials
+    }
+
+    /// Replace the partials and restart them at their phases
+    pub fn set_table(&mut self, table: PartialTable) {
+        self.partials = table.partials;
+        self.order = (0..self.partials.len()).collect();
+        self.order.sort_by(| a, b | self.partials[*a].ratio.total_cmp(&self.partials[*b].ratio));
+
+        let count = self.partials.len();
+        self.real.resize(count, 0.0);
+        self.imaginary.resize(count, 0.0);
+        self.step_real.resize(count, 1.0);
+        self.step_imaginary.resize(count, 0.0);
+        self.gains.resize(count, 0.0);
+
+        self.update();
+        self.reset();
+    }
+
+    /// Change the amplitude of partial `index` of the table without restarting it
+    pub fn set_amplitude(&mut self, index: usize, amplitude: f32) {
+        if let Some(partial) = self.partials.get_mut(index) {
+            partial.amplitude = amplitude;
+            self.update();
+        }
+    }
+
+    /// Spectral tilt in dB per octave above the fundamental, negative for a darker sound
+    pub fn set_tilt(&mut self, db_per_octave: f32) {
+        self.tilt = db_per_octave;
+        self.update();
+    }
+
+    /// Gain of the odd harmonics, counting the fundamental as the first
+    pub fn set_odd(&mut self, gain: f32) {
+        self.odd = f32::max(gain, 0.0);
+        self.update();
+    }
+
+    /// Gain of the even harmonics
+    pub fn set_even(&mut self, gain: f32) {
+        self.even = f32::max(gain, 0.0);
+        self.update();
+    }
+
+    /// Number of partials below Nyquist at the current pitch
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    fn update(&mut self) {
+        let nyquist = self.sample_rate as f32 * 0.5;
+        let fade_start = nyquist * (1.0 - NYQUIST_FADE);
+
+        self.active = self.order
+            .iter()
+            .position(| &index | self.partials[index].ratio * self.pitch >= nyquist)
+            .unwrap_or(self.partials.len());
+
+        for (i, &index) in self.order.iter().take(self.active).enumerate() {
+            let partial = &self.partials[index];
+            let frequency = partial.ratio * self.pitch;
+            let step = 2.0 * std::f32::consts::PI * frequency / self.sample_rate as f32;
+
+            self.step_real[i] = f32::cos(step);
+            self.step_imaginary[i] = f32::sin(step);
+
+            let tilt = f32::powf(10.0, self.tilt * f32::log2(f32::max(partial.ratio, 1e-3)) / 20.0);
+            let harmonic = partial.ratio.round() as usize;
+            let parity = if harmonic.is_multiple_of(2) { self.even } else { self.odd };
+            let fade = f32::clamp((nyquist - frequency) / (nyquist - fade_start), 0.0, 1.0);
+
+            self.gains[i] = partial.amplitude * tilt * parity * fade;
+        }
+    }
+}
+
+impl<F: Float> Generator for Additive<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        for (i, &index) in self.order.iter().enumerate() {
+            let phase = 2.0 * std::f32::consts::PI * self.partials[index].phase;
+            self.real[i] = f32::cos(phase);
+            self.imaginary[i] = f32::sin(phase);
+        }
+
+        self.counter = 0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let count = self.active;
+        let mut output = 0.0;
+
+        for i in 0..count {
+            output += self.imaginary[i] * self.gains[i];
+
+            let (re, im) = (self.real[i], self.imaginary[i]);
+            self.real[i] = re * self.step_real[i] - im * self.step_imaginary[i];
+            self.imaginary[i] = re * self.step_imaginary[i] + im * self.step_real[i];
+        }
+
+        /* Rounding slowly changes the rotators' magnitudes, so pull them back towards 1 */
+        self.counter += 1;
+        if self.counter >= RENORMALIZE_INTERVAL {
+            self.counter = 0;
+
+            for i in 0..count {
+                let magnitude = self.real[i] * self.real[i] + self.imaginary[i] * self.imaginary[i];
+                let correction = (3.0 - magnitude) * 0.5;
+                self.real[i] *= correction;
+                self.imaginary[i] *= correction;
+            }
+        }
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for Additive<F> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = f32::max(hz, 0.0);
+        self.update();
+    }
+}
+
+impl<F: Float> Playable for Additive<F> {
+    /// Restarts every partial at its phase, so the waveform's shape is the same on every note
+    fn note_on(&mut self, pitch: f32, _pressure: f32) {
+        self.set_pitch(pitch);
+        self.reset();
+    }
+
+    fn note_off(&mut self) {}
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+}
+
+impl<F: Float> Param for Additive<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tilt" => self.set_tilt(value),
+            "odd" => self.set_odd(value),
+            "even" => self.set_even(value),
+            _ => ()
+        }
+    }
+}
@@ -0,0 +1,950 @@
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+
+use crate::event::*;
+use crate::float::*;
+use crate::math::random::*;
+use crate::oscillators::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/* Frequencies of the six square oscillators of the 808's cymbal and hi-hat */
+const METALLIC_RATIOS: [f32; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+
+/* General MIDI note of the open hi-hat */
+const OPEN_HAT_NOTE: u32 = 46;
+
+/// Exponential decay towards silence
+#[derive(Copy, Clone, Default)]
+struct Decay {
+    level: f32,
+    coefficient: f32,
+}
+
+impl Decay {
+    /// Fall by 60 dB over `seconds`
+    fn set_time(&mut self, seconds: f32, sample_rate: u32) {
+        let samples = f32::max(seconds, 0.001) * sample_rate as f32;
+        self.coefficient = f32::powf(10.0, -3.0 / samples);
+    }
+
+    fn trigger(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    fn next(&mut self) -> f32 {
+        let level = self.level;
+        self.level *= self.coefficient;
+        level
+    }
+}
+
+/// One-pole highpass
+#[derive(Copy, Clone, Default)]
+struct Highpass {
+    coefficient: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Highpass {
+    fn set_cutoff(&mut self, hz: f32, sample_rate: u32) {
+        self.coefficient = f32::exp(-2.0 * PI * hz / sample_rate as f32);
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.coefficient * (self.y1 + x - self.x1);
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Two-pole bandpass with unity gain at the centre
+#[derive(Copy, Clone, Default)]
+struct Bandpass {
+    a1: f32,
+    a2: f32,
+    gain: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Bandpass {
+    fn set(&mut self, hz: f32, q: f32, sample_rate: u32) {
+        let hz = f32::min(hz, sample_rate as f32 * 0.45);
+        let r = f32::exp(-PI * hz / (q * sample_rate as f32));
+
+        self.a1 = 2.0 * r * f32::cos(2.0 * PI * hz / sample_rate as f32);
+        self.a2 = -r * r;
+        self.gain = (1.0 - r * r) * 0.5;
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.gain * (x - self.x2) + self.a1 * self.y1 + self.a2 * self.y2;
+
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+
+        y
+    }
+}
+
+/// Analog-style bass drum from a sine swept down in pitch, with a noise click on the attack
+pub struct Kick<F: Float> {
+    tune: f32,
+    sweep: f32,
+    pitch_decay: f32,
+    decay: f32,
+    click: f32,
+    drive: f32,
+
+    sample_rate: u32,
+    phase: f32,
+    pitch_envelope: Decay,
+    amplitude: Decay,
+    click_envelope: Decay,
+    click_filter: Highpass,
+    random: Random,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Kick<F> {
+    pub fn new() -> Self {
+        let mut kick = Self {
+            tune: 50.0,
+            sweep: 2.0,
+            pitch_decay: 0.06,
+            decay: 0.5,
+            click: 0.3,
+            drive: 0.0,
+            sample_rate: 44100,
+            phase: 0.0,
+            pitch_envelope: Decay::default(),
+            amplitude: Decay::default(),
+            click_envelope: Decay::default(),
+            click_filter: Highpass::default(),
+            random: Random::new(0),
+            _phantom: PhantomData,
+        };
+
+        kick.update();
+        kick
+    }
+
+    /// Frequency the body settles at
+    pub fn set_tune(&mut self, hz: f32) {
+        self.tune = f32::max(hz, 1.0);
+    }
+
+    /// Octaves above the tuning that the sweep starts at
+    pub fn set_sweep(&mut self, octaves: f32) {
+        self.sweep = f32::max(octaves, 0.0);
+    }
+
+    /// Seconds for the sweep to settle
+    pub fn set_pitch_decay(&mut self, seconds: f32) {
+        self.pitch_decay = seconds;
+        self.update();
+    }
+
+    /// Seconds for the body to fall by 60 dB
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = seconds;
+        self.update();
+    }
+
+    /// Level of the click from 0 to 1
+    pub fn set_click(&mut self, level: f32) {
+        self.click = f32::clamp(level, 0.0, 1.0);
+    }
+
+    /// Saturation from 0 for clean to 1 for heavily driven
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = f32::clamp(drive, 0.0, 1.0);
+    }
+
+    pub fn trigger(&mut self, velocity: f32) {
+        let velocity = f32::clamp(velocity, 0.0, 1.0);
+
+        self.phase = 0.0;
+        self.pitch_envelope.trigger(1.0);
+        self.amplitude.trigger(velocity);
+        self.click_envelope.trigger(velocity);
+    }
+
+    fn update(&mut self) {
+        self.pitch_envelope.set_time(self.pitch_decay, self.sample_rate);
+        self.amplitude.set_time(self.decay, self.sample_rate);
+        self.click_envelope.set_time(0.005, self.sample_rate);
+        self.click_filter.set_cutoff(2000.0, self.sample_rate);
+    }
+}
+
+impl<F: Float> Default for Kick<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for Kick<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.pitch_envelope.trigger(0.0);
+        self.amplitude.trigger(0.0);
+        self.click_envelope.trigger(0.0);
+        self.click_filter.reset();
+        self.random = Random::new(0);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let frequency = self.tune * f32::exp2(self.sweep * self.pitch_envelope.next());
+        let body = f32::sin(2.0 * PI * self.phase) * self.amplitude.next();
+
+        self.phase += frequency / self.sample_rate as f32;
+        self.phase -= f32::floor(self.phase);
+
+        let noise = self.click_filter.process(self.random.bipolar() as f32);
+        let click = noise * self.click_envelope.next() * self.click;
+
+        let x = body + click;
+        let output = if self.drive > 0.0 {
+            /* Normalised so a full-scale hit stays at full scale */
+            let gain = 1.0 + self.drive * 9.0;
+            f32::tanh(x * gain) / f32::tanh(gain)
+        } else {
+            x
+        };
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Playable for Kick<F> {
+    /// Triggers with the pressure as velocity, whatever the pitch
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.trigger(pressure);
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for Kick<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tune" => self.set_tune(value),
+            "sweep" => self.set_sweep(value),
+            "pitch_decay" => self.set_pitch_decay(value),
+            "decay" => self.set_decay(value),
+            "click" => self.set_click(value),
+            "drive" => self.set_drive(value),
+            _ => ()
+        }
+    }
+}
+
+/// Snare drum from two sines for the shell and highpassed noise for the wires
+pub struct Snare<F: Float> {
+    tune: f32,
+    decay: f32,
+    snappy: f32,
+    snappy_decay: f32,
+    tone: f32,
+
+    sample_rate: u32,
+    phases: [f32; 2],
+    body: Decay,
+    wires: Decay,
+    filter: Highpass,
+    random: Random,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Snare<F> {
+    pub fn new() -> Self {
+        let mut snare = Self {
+            tune: 180.0,
+            decay: 0.2,
+            snappy: 0.6,
+            snappy_decay: 0.25,
+            tone: 0.5,
+            sample_rate: 44100,
+            phases: [0.0; 2],
+            body: Decay::default(),
+            wires: Decay::default(),
+            filter: Highpass::default(),
+            random: Random::new(1),
+            _phantom: PhantomData,
+        };
+
+        snare.update();
+        snare
+    }
+
+    /// Frequency of the lower shell mode
+    pub fn set_tune(&mut self, hz: f32) {
+        self.tune = f32::max(hz, 1.0);
+    }
+
+    /// Seconds for the shell to fall by 60 dB
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = seconds;
+        self.update();
+    }
+
+    /// Level of the wires from 0 to 1
+    pub fn set_snappy(&mut self, level: f32) {
+        self.snappy = f32::clamp(level, 0.0, 1.0);
+    }
+
+    /// Seconds for the wires to fall by 60 dB
+    pub fn set_snappy_decay(&mut self, seconds: f32) {
+        self.snappy_decay = seconds;
+        self.update();
+    }
+
+    /// Brightness of the wires from 0 to 1
+    pub fn set_tone(&mut self, tone: f32) {
+        self.tone = f32::clamp(tone, 0.0, 1.0);
+        self.update();
+    }
+
+    pub fn trigger(&mut self, velocity: f32) {
+        let velocity = f32::clamp(velocity, 0.0, 1.0);
+
+        self.phases = [0.0; 2];
+        self.body.trigger(velocity);
+        self.wires.trigger(velocity);
+    }
+
+    fn update(&mut self) {
+        self.body.set_time(self.decay, self.sample_rate);
+        self.wires.set_time(self.snappy_decay, self.sample_rate);
+        self.filter.set_cutoff(1000.0 * f32::powf(8.0, self.tone), self.sample_rate);
+    }
+}
+
+impl<F: Float> Default for Snare<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for Snare<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phases = [0.0; 2];
+        self.body.trigger(0.0);
+        self.wires.trigger(0.0);
+        self.filter.reset();
+        self.random = Random::new(1);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        /* The second mode sits near where the 808 puts its upper oscillator */
+        let frequencies = [self.tune, self.tune * 1.83];
+        let gains = [0.6, 0.4];
+
+        let mut shell = 0.0;
+        for i in 0..2 {
+            shell += f32::sin(2.0 * PI * self.phases[i]) * gains[i];
+            self.phases[i] += frequencies[i] / self.sample_rate as f32;
+            self.phases[i] -= f32::floor(self.phases[i]);
+        }
+
+        let noise = self.filter.process(self.random.bipolar() as f32);
+        let shell = shell * self.body.next() * (1.0 - 0.5 * self.snappy);
+        let wires = noise * self.wires.next() * self.snappy;
+
+        F::from(shell + wires)
+    }
+}
+
+impl<F: Float> Playable for Snare<F> {
+    /// Triggers with the pressure as velocity, whatever the pitch
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.trigger(pressure);
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for Snare<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tune" => self.set_tune(value),
+            "decay" => self.set_decay(value),
+            "snappy" => self.set_snappy(value),
+            "snappy_decay" => self.set_snappy_decay(value),
+            "tone" => self.set_tone(value),
+            _ => ()
+        }
+    }
+}
+
+/// Closed and open hi-hat from six detuned squares, bandpassed for a metallic sound
+///
+/// Both hats share one voice, so a closed hit chokes a ringing open one as
+/// on a real hi-hat. Note events play the open hat on the General MIDI open
+/// hi-hat note and the closed hat on any other.
+pub struct HiHat<F: Float> {
+    tune: f32,
+    closed_decay: f32,
+    open_decay: f32,
+    tone: f32,
+
+    sample_rate: u32,
+    oscillators: [Pulse<f32>; 6],
+    amplitude: Decay,
+    bandpass: Bandpass,
+    highpass: Highpass,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> HiHat<F> {
+    pub fn new() -> Self {
+        let mut hat = Self {
+            tune: 1.0,
+            closed_decay: 0.08,
+            open_decay: 0.6,
+            tone: 0.5,
+            sample_rate: 44100,
+            oscillators: std::array::from_fn(| _ | Pulse::new()),
+            amplitude: Decay::default(),
+            bandpass: Bandpass::default(),
+            highpass: Highpass::default(),
+            _phantom: PhantomData,
+        };
+
+        hat.update();
+        hat
+    }
+
+    /// Multiplier of the oscillator frequencies
+    pub fn set_tune(&mut self, ratio: f32) {
+        self.tune = f32::max(ratio, 0.01);
+        self.update();
+    }
+
+    /// Seconds for the closed hat to fall by 60 dB
+    pub fn set_closed_decay(&mut self, seconds: f32) {
+        self.closed_decay = seconds;
+    }
+
+    /// Seconds for the open hat to fall by 60 dB
+    pub fn set_open_decay(&mut self, seconds: f32) {
+        self.open_decay = seconds;
+    }
+
+    /// Brightness from 0 to 1
+    pub fn set_tone(&mut self, tone: f32) {
+        self.tone = f32::clamp(tone, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Play the closed hat, choking the open one
+    pub fn close(&mut self, velocity: f32) {
+        self.amplitude.set_time(self.closed_decay, self.sample_rate);
+        self.amplitude.trigger(f32::clamp(velocity, 0.0, 1.0));
+    }
+
+    /// Play the open hat
+    pub fn open(&mut self, velocity: f32) {
+        self.amplitude.set_time(self.open_decay, self.sample_rate);
+        self.amplitude.trigger(f32::clamp(velocity, 0.0, 1.0));
+    }
+
+    fn update(&mut self) {
+        for (oscillator, ratio) in self.oscillators.iter_mut().zip(METALLIC_RATIOS) {
+            oscillator.set_pitch(ratio * self.tune);
+        }
+
+        let centre = 7000.0 * f32::powf(2.0, self.tone);
+        self.bandpass.set(centre, 1.0, self.sample_rate);
+        self.highpass.set_cutoff(centre * 0.7, self.sample_rate);
+    }
+}
+
+impl<F: Float> Default for HiHat<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for HiHat<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.reset();
+        }
+
+        self.amplitude.trigger(0.0);
+        self.bandpass.reset();
+        self.highpass.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.sample_rate = sample_rate;
+
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.prepare(sample_rate, block_size);
+        }
+
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let metal: f32 = self.oscillators.iter_mut().map(| oscillator | oscillator.generate()).sum();
+        let x = self.highpass.process(self.bandpass.process(metal));
+
+        F::from(x * self.amplitude.next())
+    }
+}
+
+impl<F: Float> Playable for HiHat<F> {
+    /// Plays the open hat on note 46 and the closed hat otherwise
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        if pitch_to_num(pitch) == OPEN_HAT_NOTE {
+            self.open(pressure);
+        } else {
+            self.close(pressure);
+        }
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for HiHat<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tune" => self.set_tune(value),
+            "closed_decay" => self.set_closed_decay(value),
+            "open_decay" => self.set_open_decay(value),
+            "tone" => self.set_tone(value),
+            _ => ()
+        }
+    }
+}
+
+/// Hand clap from a few quick bursts of bandpassed noise and a longer tail
+pub struct Clap<F: Float> {
+    tone: f32,
+    spread: f32,
+    decay: f32,
+
+    sample_rate: u32,
+    elapsed: usize,
+    velocity: f32,
+    bursts: Decay,
+    tail: Decay,
+    filter: Bandpass,
+    random: Random,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Clap<F> {
+    /* Bursts before the tail, one for each hand that doesn't quite land together */
+    const BURSTS: usize = 4;
+
+    pub fn new() -> Self {
+        let mut clap = Self {
+            tone: 1200.0,
+            spread: 0.01,
+            decay: 0.3,
+            sample_rate: 44100,
+            elapsed: usize::MAX,
+            velocity: 0.0,
+            bursts: Decay::default(),
+            tail: Decay::default(),
+            filter: Bandpass::default(),
+            random: Random::new(2),
+            _phantom: PhantomData,
+        };
+
+        clap.update();
+        clap
+    }
+
+    /// Centre frequency of the noise
+    pub fn set_tone(&mut self, hz: f32) {
+        self.tone = f32::max(hz, 20.0);
+        self.update();
+    }
+
+    /// Seconds between the bursts
+    pub fn set_spread(&mut self, seconds: f32) {
+        self.spread = f32::max(seconds, 0.001);
+    }
+
+    /// Seconds for the tail to fall by 60 dB
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = seconds;
+        self.update();
+    }
+
+    pub fn trigger(&mut self, velocity: f32) {
+        self.velocity = f32::clamp(velocity, 0.0, 1.0);
+        self.elapsed = 0;
+        self.tail.trigger(0.0);
+    }
+
+    fn update(&mut self) {
+        self.bursts.set_time(0.02, self.sample_rate);
+        self.tail.set_time(self.decay, self.sample_rate);
+        self.filter.set(self.tone, 1.5, self.sample_rate);
+    }
+}
+
+impl<F: Float> Default for Clap<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for Clap<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.elapsed = usize::MAX;
+        self.bursts.trigger(0.0);
+        self.tail.trigger(0.0);
+        self.filter.reset();
+        self.random = Random::new(2);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let spacing = usize::max((self.spread * self.sample_rate as f32) as usize, 1);
+
+        if self.elapsed < spacing * Self::BURSTS && self.elapsed.is_multiple_of(spacing) {
+            self.bursts.trigger(self.velocity);
+
+            if self.elapsed == spacing * (Self::BURSTS - 1) {
+                self.tail.trigger(self.velocity * 0.5);
+            }
+        }
+
+        self.elapsed = self.elapsed.saturating_add(1);
+
+        let envelope = f32::max(self.bursts.next(), self.tail.next());
+        let noise = self.filter.process(self.random.bipolar() as f32);
+
+        /* The narrow band passes only a little of the white noise */
+        F::from(noise * envelope * 2.0)
+    }
+}
+
+impl<F: Float> Playable for Clap<F> {
+    /// Triggers with the pressure as velocity, whatever the pitch
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.trigger(pressure);
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for Clap<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tone" => self.set_tone(value),
+            "spread" => self.set_spread(value),
+            "decay" => self.set_decay(value),
+            _ => ()
+        }
+    }
+}
+
+/// Tom from a sine dropping slightly in pitch, with a touch of noise for the stick
+pub struct Tom<F: Float> {
+    tune: f32,
+    sweep: f32,
+    decay: f32,
+    noise: f32,
+
+    sample_rate: u32,
+    phase: f32,
+    pitch_envelope: Decay,
+    amplitude: Decay,
+    noise_envelope: Decay,
+    filter: Bandpass,
+    random: Random,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Tom<F> {
+    pub fn from(tune: f32) -> Self {
+        let mut tom = Self {
+            tune: f32::max(tune, 1.0),
+            sweep: 0.5,
+            decay: 0.5,
+            noise: 0.2,
+            sample_rate: 44100,
+            phase: 0.0,
+            pitch_envelope: Decay::default(),
+            amplitude: Decay::default(),
+            noise_envelope: Decay::default(),
+            filter: Bandpass::default(),
+            random: Random::new(3),
+            _phantom: PhantomData,
+        };
+
+        tom.update();
+        tom
+    }
+
+    pub fn low() -> Self {
+        Self::from(90.0)
+    }
+
+    pub fn mid() -> Self {
+        Self::from(130.0)
+    }
+
+    pub fn high() -> Self {
+        Self::from(180.0)
+    }
+
+    /// Frequency the head settles at
+    pub fn set_tune(&mut self, hz: f32) {
+        self.tune = f32::max(hz, 1.0);
+        self.update();
+    }
+
+    /// Octaves above the tuning that the drop starts at
+    pub fn set_sweep(&mut self, octaves: f32) {
+        self.sweep = f32::max(octaves, 0.0);
+    }
+
+    /// Seconds for the head to fall by 60 dB
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = seconds;
+        self.update();
+    }
+
+    /// Level of the stick noise from 0 to 1
+    pub fn set_noise(&mut self, level: f32) {
+        self.noise = f32::clamp(level, 0.0, 1.0);
+    }
+
+    pub fn trigger(&mut self, velocity: f32) {
+        let velocity = f32::clamp(velocity, 0.0, 1.0);
+
+        self.phase = 0.0;
+        self.pitch_envelope.trigger(1.0);
+        self.amplitude.trigger(velocity);
+        self.noise_envelope.trigger(velocity);
+    }
+
+    fn update(&mut self) {
+        self.pitch_envelope.set_time(self.decay * 0.5, self.sample_rate);
+        self.amplitude.set_time(self.decay, self.sample_rate);
+        self.noise_envelope.set_time(0.05, self.sample_rate);
+        self.filter.set(self.tune * 8.0, 1.0, self.sample_rate);
+    }
+}
+
+impl<F: Float> Generator for Tom<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.pitch_envelope.trigger(0.0);
+        self.amplitude.trigger(0.0);
+        self.noise_envelope.trigger(0.0);
+        self.filter.reset();
+        self.random = Random::new(3);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let frequency = self.tune * f32::exp2(self.sweep * self.pitch_envelope.next());
+        let head = f32::sin(2.0 * PI * self.phase) * self.amplitude.next();
+
+        self.phase += frequency / self.sample_rate as f32;
+        self.phase -= f32::floor(self.phase);
+
+        let noise = self.filter.process(self.random.bipolar() as f32);
+        let stick = noise * self.noise_envelope.next() * self.noise;
+
+        F::from(head + stick)
+    }
+}
+
+impl<F: Float> Playable for Tom<F> {
+    /// Triggers with the pressure as velocity, whatever the pitch
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.trigger(pressure);
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for Tom<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tune" => self.set_tune(value),
+            "sweep" => self.set_sweep(value),
+            "decay" => self.set_decay(value),
+            "noise" => self.set_noise(value),
+            _ => ()
+        }
+    }
+}
+
+/// Cowbell from two squares a fifth-ish apart through a bandpass, with a sharp attack and a ringing tail
+pub struct Cowbell<F: Float> {
+    tune: f32,
+    decay: f32,
+
+    sample_rate: u32,
+    oscillators: [Pulse<f32>; 2],
+    attack: Decay,
+    tail: Decay,
+    filter: Bandpass,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> Cowbell<F> {
+    pub fn new() -> Self {
+        let mut cowbell = Self {
+            tune: 1.0,
+            decay: 0.4,
+            sample_rate: 44100,
+            oscillators: std::array::from_fn(| _ | Pulse::new()),
+            attack: Decay::default(),
+            tail: Decay::default(),
+            filter: Bandpass::default(),
+            _phantom: PhantomData,
+        };
+
+        cowbell.update();
+        cowbell
+    }
+
+    /// Multiplier of the 540 and 800 Hz oscillators
+    pub fn set_tune(&mut self, ratio: f32) {
+        self.tune = f32::max(ratio, 0.01);
+        self.update();
+    }
+
+    /// Seconds for the tail to fall by 60 dB
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = seconds;
+        self.update();
+    }
+
+    pub fn trigger(&mut self, velocity: f32) {
+        let velocity = f32::clamp(velocity, 0.0, 1.0);
+
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.set_phase(0.0);
+        }
+
+        self.attack.trigger(velocity * 0.6);
+        self.tail.trigger(velocity * 0.4);
+    }
+
+    fn update(&mut self) {
+        self.oscillators[0].set_pitch(540.0 * self.tune);
+        self.oscillators[1].set_pitch(800.0 * self.tune);
+
+        self.attack.set_time(0.04, self.sample_rate);
+        self.tail.set_time(self.decay, self.sample_rate);
+        self.filter.set(2640.0 * self.tune, 1.0, self.sample_rate);
+    }
+}
+
+impl<F: Float> Default for Cowbell<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for Cowbell<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.reset();
+        }
+
+        self.attack.trigger(0.0);
+        self.tail.trigger(0.0);
+        self.filter.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.sample_rate = sample_rate;
+
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.prepare(sample_rate, block_size);
+        }
+
+        self.update();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let metal: f32 = self.oscillators.iter_mut().map(| oscillator | oscillator.generate()).sum();
+        let x = self.filter.process(metal * 0.5);
+        let envelope = self.attack.next() + self.tail.next();
+
+        F::from(x * envelope * 1.5)
+    }
+}
+
+impl<F: Float> Playable for Cowbell<F> {
+    /// Triggers with the pressure as velocity, whatever the pitch
+    fn note_on(&mut self, _pitch: f32, pressure: f32) {
+        self.trigger(pressure);
+    }
+
+    fn note_off(&mut self) {}
+}
+
+impl<F: Float> Param for Cowbell<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "tune" => self.set_tune(value),
+            "decay" => self.set_decay(value),
+            _ => ()
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use crate::float::*;
+use crate::traits::*;
+use crate::oscillators::*;
+
+/* Phase deviation in radians of a modulator at full level, close to the DX7's maximum index */
+const MODULATION_INDEX: f32 = 4.0 * std::f32::consts::PI;
+
+/* Phase deviation in radians of an operator at full feedback */
+const FEEDBACK_INDEX: f32 = std::f32::consts::PI;
+
+/// Operator frequency relative to the note or fixed in Hz
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Frequency {
+    Ratio(f32),
+    Fixed(f32),
+}
+
+/// Four stage rate/level envelope in the style of the DX7
+///
+/// On note on the level moves from `levels[3]` through `levels[0]`, `levels[1]`
+/// and `levels[2]`, taking `times[0..3]` seconds per stage, then holds
+/// `levels[2]`. On note off it moves to `levels[3]` in `times[3]` seconds.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct OperatorEnvelope {
+    pub levels: [f32; 4],
+    pub times: [f32; 4],
+}
+
+impl OperatorEnvelope {
+    pub fn adsr(attack: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        Self {
+            levels: [1.0, sustain, sustain, 0.0],
+            times: [attack, decay, 0.0, release],
+        }
+    }
+}
+
+impl Default for OperatorEnvelope {
+    fn default() -> Self {
+        Self::adsr(0.002, 0.0, 1.0, 0.05)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Stage {
+    Idle,
+    Segment(usize),
+    Sustain,
+    Release,
+}
+
+pub enum OperatorSource<F: Float> {
+    Sine,
+    Wavetable(WavetableOscillator<F>),
+}
+
+/// A phase modulation operator
+pub struct Operator<F: Float> {
+    source: OperatorSource<F>,
+    frequency: Frequency,
+    detune: f32,
+    level: f32,
+    feedback: f32,
+    velocity_sensitivity: f32,
+    key_sync: bool,
+    envelope: OperatorEnvelope,
+
+    pitch: f32,
+    sample_rate: u32,
+    phase: F,
+    increment: F,
+    velocity: f32,
+    stage: Stage,
+    amplitude: f32,
+    target: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl<F: Float> Operator<F> {
+    pub fn new() -> Self {
+        Self::from(OperatorSource::Sine)
+    }
+
+    pub fn from(source: OperatorSource<F>) -> Self {
+        let mut operator = Self {
+            source,
+            frequency: Frequency::Ratio(1.0),
+            detune: 0.0,
+            level: 1.0,
+            feedback: 0.0,
+            velocity_sensitivity: 0.0,
+            key_sync: true,
+            envelope: OperatorEnvelope::default(),
+            pitch: 440.0,
+            sample_rate: 44100,
+            phase: F::ZERO,
+            increment: F::ZERO,
+            velocity: 1.0,
+            stage: Stage::Idle,
+            amplitude: 0.0,
+            target: 0.0,
+            step: 0.0,
+            remaining: 0,
+        };
+
+        operator.update_frequency();
+        operator
+    }
+
+    pub fn source_mut(&mut self) -> &mut OperatorSource<F> {
+        &mut self.source
+    }
+
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        self.frequency = frequency;
+        self.update_frequency();
+    }
+
+    /// Detune in cents
+    pub fn set_detune(&mut self, cents: f32) {
+        self.detune = cents;
+        self.update_frequency();
+    }
+
+    /// Output level from 0 to 1, which sets the modulation index when modulating another operator
+    pub fn set_level(&mut self, level: f32) {
+        self.level = f32::max(level, 0.0);
+    }
+
+    /// Amount of this operator's own output, or the output routed back to it, added to its phase
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = f32::clamp(feedback, 0.0, 1.0);
+    }
+
+    /// How much note pressure scales the output, from 0 to 1
+    pub fn set_velocity_sensitivity(&mut self, sensitivity: f32) {
+        self.velocity_sensitivity = f32::clamp(sensitivity, 0.0, 1.0);
+    }
+
+    /// Restart the phase on every note
+    pub fn set_key_sync(&mut self, key_sync: bool) {
+        self.key_sync = key_sync;
+    }
+
+    pub fn envelope(&self) -> &OperatorEnvelope {
+        &self.envelope
+    }
+
+    pub fn set_envelope(&mut self, envelope: OperatorEnvelope) {
+        self.envelope = envelope;
+    }
+
+    /// The envelope hasn't finished
+    pub fn active(&self) -> bool {
+        self.stage != Stage::Idle || self.amplitude > 0.0
+    }
+
+    fn update_frequency(&mut self) {
+        let hz = match self.frequency {
+            Frequency::Ratio(ratio) => self.pitch * ratio,
+            Frequency::Fixed(hz) => hz,
+        } * f32::powf(2.0, self.detune / 1200.0);
+
+        self.increment = F::from(f32::clamp(hz / self.sample_rate as f32, 0.0, 0.5));
+
+        if let OperatorSource::Wavetable(oscillator) = &mut self.source {
+            oscillator.set_pitch(hz);
+        }
+    }
+
+    fn start_stage(&mut self, stage: Stage) {
+        let mut stage = stage;
+
+        loop {
+            let (target, time) = match stage {
+                Stage::Segment(i) => (self.envelope.levels[i], self.envelope.times[i]),
+                Stage::Release => (self.envelope.levels[3], self.envelope.times[3]),
+                _ => {
+                    self.stage = stage;
+                    return;
+                }
+            };
+
+            let samples = f32::round(f32::max(time, 0.0) * self.sample_rate as f32) as usize;
+
+            if samples > 0 {
+                self.stage = stage;
+                self.target = target;
+                self.step = (target - self.amplitude) / samples as f32;
+                self.remaining = samples;
+                return;
+            }
+
+            self.amplitude = target;
+            stage = next_stage(stage);
+        }
+    }
+
+    fn next_amplitude(&mut self) -> f32 {
+        match self.stage {
+            Stage::Segment(_) | Stage::Release => {
+                self.amplitude += self.step;
+                self.remaining -= 1;
+
+                if self.remaining == 0 {
+                    self.amplitude = self.target;
+                    self.start_stage(next_stage(self.stage));
+                }
+            },
+            _ => ()
+        }
+
+        self.amplitude
+    }
+
+    /// Next output with `modulation` radians added to the phase
+    pub fn process(&mut self, modulation: F) -> F {
+        let amplitude = self.next_amplitude() * self.level
+            * (1.0 - self.velocity_sensitivity + self.velocity_sensitivity * self.velocity);
+
+        let phase = self.phase + modulation / F::from(2.0 * std::f32::consts::PI);
+        let value = match &self.source {
+            OperatorSource::Sine => (phase * F::from(2.0) * F::PI).sin(),
+            OperatorSource::Wavetable(oscillator) => oscillator.value_at(phase),
+        };
+
+        self.phase += self.increment;
+        if self.phase >= F::from(1.0) {
+            self.phase -= F::from(1.0);
+        }
+
+        value * F::from(amplitude)
+    }
+}
+
+fn next_stage(stage: Stage) -> Stage {
+    match stage {
+        Stage::Segment(2) => Stage::Sustain,
+        Stage::Segment(i) => Stage::Segment(i + 1),
+        _ => Stage::Idle,
+    }
+}
+
+impl<F: Float> Default for Operator<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for Operator<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.phase = F::ZERO;
+        self.stage = Stage::Idle;
+        self.amplitude = 0.0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        self.sample_rate = sample_rate;
+
+        if let OperatorSource::Wavetable(oscillator) = &mut self.source {
+            oscillator.prepare(sample_rate, block_size);
+        }
+
+        self.update_frequency();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        self.process(F::ZERO)
+    }
+}
+
+impl<F: Float> Pitched for Operator<F> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Pitch of the note, which the frequency ratio applies to
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+        self.update_frequency();
+    }
+}
+
+impl<F: Float> Playable for Operator<F> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.set_pitch(pitch);
+        self.velocity = f32::clamp(pressure, 0.0, 1.0);
+
+        if self.key_sync {
+            self.phase = F::ZERO;
+        }
+
+        self.amplitude = self.envelope.levels[3];
+        self.start_stage(Stage::Segment(0));
+    }
+
+    fn note_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.start_stage(Stage::Release);
+        }
+    }
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+
+    fn set_pressure(&mut self, pressure: f32) {
+        self.velocity = f32::clamp(pressure, 0.0, 1.0);
+    }
+}
+
+/* DX7 algorithms as (modulator, target) pairs and carriers, numbered from 1 as on the front panel */
+type Routing = (&'static [(usize, usize)], &'static [usize]);
+
+const DX7_ALGORITHMS: [Routing; 32] = [
+    (&[(2, 1), (4, 3), (5, 4), (6, 5), (6, 6)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 4), (6, 5), (2, 2)], &[1, 3]),
+    (&[(2, 1), (3, 2), (5, 4), (6, 5), (6, 6)], &[1, 4]),
+    (&[(2, 1), (3, 2), (5, 4), (6, 5), (4, 6)], &[1, 4]),
+    (&[(2, 1), (4, 3), (6, 5), (6, 6)], &[1, 3, 5]),
+    (&[(2, 1), (4, 3), (6, 5), (5, 6)], &[1, 3, 5]),
+    (&[(2, 1), (4, 3), (5, 3), (6, 5), (6, 6)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 3), (6, 5), (4, 4)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 3), (6, 5), (2, 2)], &[1, 3]),
+    (&[(2, 1), (3, 2), (5, 4), (6, 4), (3, 3)], &[1, 4]),
+    (&[(2, 1), (3, 2), (5, 4), (6, 4), (6, 6)], &[1, 4]),
+    (&[(2, 1), (4, 3), (5, 3), (6, 3), (2, 2)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 3), (6, 3), (6, 6)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 4), (6, 4), (6, 6)], &[1, 3]),
+    (&[(2, 1), (4, 3), (5, 4), (6, 4), (2, 2)], &[1, 3]),
+    (&[(2, 1), (3, 1), (5, 1), (4, 3), (6, 5), (6, 6)], &[1]),
+    (&[(2, 1), (3, 1), (5, 1), (4, 3), (6, 5), (2, 2)], &[1]),
+    (&[(2, 1), (3, 1), (4, 1), (5, 4), (6, 5), (3, 3)], &[1]),
+    (&[(2, 1), (3, 2), (6, 4), (6, 5), (6, 6)], &[1, 4, 5]),
+    (&[(3, 1), (3, 2), (5, 4), (6, 4), (3, 3)], &[1, 2, 4]),
+    (&[(3, 1), (3, 2), (6, 4), (6, 5), (3, 3)], &[1, 2, 4, 5]),
+    (&[(2, 1), (6, 3), (6, 4), (6, 5), (6, 6)], &[1, 3, 4, 5]),
+    (&[(3, 2), (6, 4), (6, 5), (6, 6)], &[1, 2, 4, 5]),
+    (&[(6, 3), (6, 4), (6, 5), (6, 6)], &[1, 2, 3, 4, 5]),
+    (&[(6, 4), (6, 5), (6, 6)], &[1, 2, 3, 4, 5]),
+    (&[(3, 2), (5, 4), (6, 4), (6, 6)], &[1, 2, 4]),
+    (&[(3, 2), (5, 4), (6, 4), (3, 3)], &[1, 2, 4]),
+    (&[(2, 1), (4, 3), (5, 4), (5, 5)], &[1, 3, 6]),
+    (&[(4, 3), (6, 5), (6, 6)], &[1, 2, 3, 5]),
+    (&[(4, 3), (5, 4), (5, 5)], &[1, 2, 3, 6]),
+    (&[(6, 5), (6, 6)], &[1, 2, 3, 4, 5]),
+    (&[(6, 6)], &[1, 2, 3, 4, 5, 6]),
+];
+
+/// Routing between operators
+///
+/// Operators are evaluated from the highest index down. A connection from a
+/// higher to a lower index is modulation within the same sample. A connection
+/// to the same or a higher index reads the previous samples and is scaled by
+/// the target operator's feedback.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Algorithm {
+    connections: Vec<(usize, usize)>,
+    carriers: Vec<usize>,
+}
+
+impl Algorithm {
+    /// Algorithm from `(modulator, target)` pairs and the operators heard at the output, indexed from 0
+    pub fn new(connections: &[(usize, usize)], carriers: &[usize]) -> Self {
+        Self {
+            connections: connections.to_vec(),
+            carriers: carriers.to_vec(),
+        }
+    }
+
+    /// One of the 32 six operator DX7 algorithms, numbered from 1
+    ///
+    /// Operator 1 on the DX7 is index 0.
+    pub fn dx7(number: usize) -> Option<Self> {
+        let (connections, carriers) = DX7_ALGORITHMS.get(number.checked_sub(1)?)?;
+
+        Some(Self {
+            connections: connections.iter().map(| (from, to) | (from - 1, to - 1)).collect(),
+            carriers: carriers.iter().map(| c | c - 1).collect(),
+        })
+    }
+
+    pub fn connections(&self) -> &[(usize, usize)] {
+        &self.connections
+    }
+
+    pub fn carriers(&self) -> &[usize] {
+        &self.carriers
+    }
+
+    /// Number of operators the algorithm needs
+    pub fn operators(&self) -> usize {
+        self.connections
+            .iter()
+            .flat_map(| (from, to) | [*from, *to])
+            .chain(self.carriers.iter().copied())
+            .max()
+            .map(| max | max + 1)
+            .unwrap_or(0)
+    }
+}
+
+/// Phase modulation synth voice of `N` operators wired by an `Algorithm`
+pub struct FmSynth<F: Float, const N: usize> {
+    operators: [Operator<F>; N],
+    algorithm: Algorithm,
+    modulators: [Vec<usize>; N],
+    feedback: [Vec<usize>; N],
+    outputs: [F; N],
+    history: [F; N],
+    pitch: f32,
+}
+
+impl<F: Float, const N: usize> FmSynth<F, N> {
+    pub fn new(algorithm: Algorithm) -> Result<Self, String> {
+        let mut synth = Self {
+            operators: std::array::from_fn(| _ | Operator::new()),
+            algorithm: Algorithm::new(&[], &[]),
+            modulators: std::array::from_fn(| _ | Vec::new()),
+            feedback: std::array::from_fn(| _ | Vec::new()),
+            outputs: [F::ZERO; N],
+            history: [F::ZERO; N],
+            pitch: 440.0,
+        };
+
+        synth.set_algorithm(algorithm)?;
+        Ok(synth)
+    }
+
+    pub fn operator(&mut self, index: usize) -> &mut Operator<F> {
+        &mut self.operators[index]
+    }
+
+    pub fn operators(&mut self) -> &mut [Operator<F>; N] {
+        &mut self.operators
+    }
+
+    pub fn algorithm(&self) -> &Algorithm {
+        &self.algorithm
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: Algorithm) -> Result<(), String> {
+        if algorithm.operators() > N {
+            return Err(format!("Algorithm uses {} operators but the synth has {}", algorithm.operators(), N));
+        }
+
+        for i in 0..N {
+            self.modulators[i].clear();
+            self.feedback[i].clear();
+        }
+
+        for (from, to) in algorithm.connections() {
+            if from > to {
+                self.modulators[*to].push(*from);
+            } else {
+                self.feedback[*to].push(*from);
+            }
+        }
+
+        self.algorithm = algorithm;
+        Ok(())
+    }
+
+    /// Any carrier envelope hasn't finished
+    pub fn active(&self) -> bool {
+        self.algorithm.carriers().iter().any(| c | self.operators[*c].active())
+    }
+}
+
+impl<F: Float, const N: usize> Generator for FmSynth<F, N> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        for operator in self.operators.iter_mut() {
+            operator.reset();
+        }
+
+        self.outputs = [F::ZERO; N];
+        self.history = [F::ZERO; N];
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        for operator in self.operators.iter_mut() {
+            operator.prepare(sample_rate, block_size);
+        }
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let index = F::from(MODULATION_INDEX);
+        let half = F::from(0.5);
+
+        for i in (0..N).rev() {
+            let mut modulation = F::ZERO;
+            for j in &self.modulators[i] {
+                modulation += self.outputs[*j];
+            }
+
+            modulation *= index;
+
+            /* Average the last two samples of feedback like the DX7 to tame its noise */
+            if !self.feedback[i].is_empty() {
+                let mut feedback = F::ZERO;
+                for j in &self.feedback[i] {
+                    feedback += (self.outputs[*j] + self.history[*j]) * half;
+                }
+
+                modulation += feedback * F::from(self.operators[i].feedback * FEEDBACK_INDEX);
+            }
+
+            self.history[i] = self.outputs[i];
+            self.outputs[i] = self.operators[i].process(modulation);
+        }
+
+        let carriers = self.algorithm.carriers();
+        let mut output = F::ZERO;
+        for c in carriers {
+            output += self.outputs[*c];
+        }
+
+        if carriers.is_empty() {
+            output
+        } else {
+            output / F::from(carriers.len() as f32)
+        }
+    }
+}
+
+impl<F: Float, const N: usize> Pitched for FmSynth<F, N> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = hz;
+
+        for operator in self.operators.iter_mut() {
+            operator.set_pitch(hz);
+        }
+    }
+}
+
+impl<F: Float, const N: usize> Playable for FmSynth<F, N> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.pitch = pitch;
+
+        for operator in self.operators.iter_mut() {
+            operator.note_on(pitch, pressure);
+        }
+    }
+
+    fn note_off(&mut self) {
+        for operator in self.operators.iter_mut() {
+            operator.note_off();
+        }
+    }
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+
+    fn set_pressure(&mut self, pressure: f32) {
+        for operator in self.operators.iter_mut() {
+            operator.set_pressure(pressure);
+        }
+    }
+}
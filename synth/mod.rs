@@ -0,0 +1,11 @@
+mod additive;
+mod drums;
+mod fm;
+mod modal;
+mod string;
+
+pub use additive::*;
+pub use drums::*;
+pub use fm::*;
+pub use modal::*;
+pub use string::*;
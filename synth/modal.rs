@@ -0,0 +1,350 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::float::*;
+use crate::routing::param::*;
+use crate::traits::*;
+
+/// A single resonant mode
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Mode {
+    /// Frequency relative to the fundamental
+    pub ratio: f32,
+    /// Seconds to fall by 60 dB
+    pub decay: f32,
+    pub gain: f32,
+}
+
+impl Mode {
+    pub fn new(ratio: f32, decay: f32, gain: f32) -> Self {
+        Self { ratio, decay, gain }
+    }
+}
+
+/// Frequencies, decays and gains of the modes of a resonating body
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct ModeTable {
+    pub modes: Vec<Mode>,
+}
+
+impl ModeTable {
+    pub fn from(modes: Vec<Mode>) -> Self {
+        Self { modes }
+    }
+
+    /// Modes at `ratios` with decays falling by `falloff` and gains by `rolloff` as the ratio rises
+    fn preset(ratios: &[f32], decay: f32, falloff: f32, rolloff: f32) -> Self {
+        let mut modes: Vec<Mode> = ratios.iter()
+            .map(| ratio | Mode::new(*ratio, decay / f32::powf(*ratio, falloff), 1.0 / f32::powf(*ratio, rolloff)))
+            .collect();
+
+        /* Gains sum to 1 so a full strike can't clip */
+        let total: f32 = modes.iter().map(| mode | mode.gain).sum();
+        for mode in modes.iter_mut() {
+            mode.gain /= total;
+        }
+
+        Self { modes }
+    }
+
+    /// Free bar such as a marimba or xylophone key before tuning
+    pub fn bar() -> Self {
+        Self::preset(&[1.0, 2.756, 5.404, 8.933, 13.344, 18.637], 1.5, 1.0, 0.5)
+    }
+
+    /// Church bell with its hum, prime, tierce, quint and nominal partials
+    pub fn bell() -> Self {
+        let mut table = Self::preset(
+            &[0.5, 1.0, 1.183, 1.506, 2.0, 2.514, 2.662, 3.011, 4.166, 5.433, 6.796, 8.215],
+            6.0,
+            0.7,
+            0.3
+        );
+
+        /* The hum note is quieter than the strike tone but outlasts it */
+        table.modes[0].gain = table.modes[1].gain * 0.5;
+        table
+    }
+
+    /// Simply supported rectangular plate with sides in a ratio of 1.3
+    pub fn plate() -> Self {
+        let aspect: f32 = 1.3 * 1.3;
+        let base = 1.0 + 1.0 / aspect;
+
+        let mut ratios: Vec<f32> = (1..=4)
+            .flat_map(| m | (1..=4).map(move | n | (m * m) as f32 + (n * n) as f32 / aspect))
+            .map(| f | f / base)
+            .collect();
+
+        ratios.sort_by(| a, b | a.total_cmp(b));
+        Self::preset(&ratios, 3.0, 0.5, 0.25)
+    }
+
+    /// Circular membrane such as a drum head, from the zeros of the Bessel functions
+    pub fn membrane() -> Self {
+        let zeros = [2.405, 3.832, 5.136, 5.520, 6.380, 7.016, 7.588, 8.417, 8.654, 8.771, 9.761, 9.936];
+        let ratios: Vec<f32> = zeros.iter().map(| zero | zero / zeros[0]).collect();
+
+        Self::preset(&ratios, 0.6, 1.5, 0.5)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(| e | e.to_string())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(| e | e.to_string())
+    }
+
+    /// Load a table saved as JSON, such as `{"modes": [{"ratio": 1.0, "decay": 2.0, "gain": 1.0}]}`
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(| e | e.to_string())?;
+        Self::from_json(&json)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()?).map_err(| e | e.to_string())
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct Resonator {
+    a1: f32,
+    a2: f32,
+    /// Input gain giving unity gain at the resonance when processing audio
+    input: f32,
+    /// Impulse size for a strike with the mode's gain and the mallet's hardness
+    strike: f32,
+    /// False for modes at or above Nyquist, which are skipped
+    active: bool,
+}
+
+#[derive(Copy, Clone, Default)]
+struct ResonatorState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl ResonatorState {
+    /// Two-pole resonator with zeros at DC and Nyquist, `g (1 - z^-2) / (1 - a1 z^-1 - a2 z^-2)`
+    fn process(&mut self, resonator: &Resonator, x: f32, g: f32) -> f32 {
+        let y = g * (x - self.x2) + resonator.a1 * self.y1 + resonator.a2 * self.y2;
+
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+
+        y
+    }
+}
+
+/// Bank of tuned two-pole resonators for modal synthesis
+///
+/// As a `Processor` the input excites every mode, giving the input the
+/// resonance of the body in the mode table. As a `Generator` the bank rings
+/// when struck, either by `strike` or by note events. Modes above Nyquist
+/// are skipped, keeping their place so every mode keeps its own state.
+pub struct ModalBank<S: Sample> {
+    table: ModeTable,
+    pitch: f32,
+    decay: f32,
+    hardness: f32,
+    mix: f32,
+
+    sample_rate: u32,
+    resonators: Vec<Resonator>,
+    states: Vec<Vec<ResonatorState>>,
+    velocity: f32,
+    _phantom: PhantomData<S>,
+}
+
+impl<S: Sample> ModalBank<S> {
+    pub fn from(table: ModeTable) -> Self {
+        let mut bank = Self {
+            table,
+            pitch: 220.0,
+            decay: 1.0,
+            hardness: 0.5,
+            mix: 1.0,
+            sample_rate: 44100,
+            resonators: Vec::new(),
+            states: vec![Vec::new(); S::CHANNELS],
+            velocity: 0.0,
+            _phantom: PhantomData,
+        };
+
+        bank.update();
+        bank
+    }
+
+    pub fn table(&self) -> &ModeTable {
+        &self.table
+    }
+
+    pub fn set_table(&mut self, table: ModeTable) {
+        self.table = table;
+        self.update();
+    }
+
+    /// Multiplier of every mode's decay time
+    pub fn set_decay(&mut self, scale: f32) {
+        self.decay = f32::max(scale, 0.001);
+        self.update();
+    }
+
+    /// Mallet hardness from 0 for soft, exciting only the low modes, to 1 for hard
+    pub fn set_hardness(&mut self, hardness: f32) {
+        self.hardness = f32::clamp(hardness, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Balance of the processed input from 0 for dry to 1 for only the resonance
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = f32::clamp(mix, 0.0, 1.0);
+    }
+
+    /// Excite every mode as if struck with `velocity` from 0 to 1
+    pub fn strike(&mut self, velocity: f32) {
+        self.velocity += f32::clamp(velocity, 0.0, 1.0);
+    }
+
+    fn update(&mut self) {
+        let nyquist = self.sample_rate as f32 * 0.49;
+
+        /* A mallet in contact for a shorter time excites higher modes */
+        let cutoff = 200.0 * f32::powf(100.0, self.hardness);
+
+        self.resonators.resize(self.table.modes.len(), Resonator::default());
+        for (i, mode) in self.table.modes.iter().enumerate() {
+            let frequency = mode.ratio * self.pitch;
+            if frequency <= 0.0 || frequency >= nyquist {
+                /* Silence it, so it comes back from rest if the pitch falls again */
+                self.resonators[i].active = false;
+                for states in self.states.iter_mut().filter(| states | i < states.len()) {
+                    states[i] = ResonatorState::default();
+                }
+
+                continue;
+            }
+
+            let theta = 2.0 * std::f32::consts::PI * frequency / self.sample_rate as f32;
+            let samples = f32::max(mode.decay * self.decay, 0.001) * self.sample_rate as f32;
+            let r = f32::powf(10.0, -3.0 / samples);
+            let hardness = 1.0 / (1.0 + (frequency / cutoff) * (frequency / cutoff));
+
+            self.resonators[i] = Resonator {
+                a1: 2.0 * r * f32::cos(theta),
+                a2: -r * r,
+                input: (1.0 - r * r) * 0.5,
+                /* The numerator doubles the amplitude of the ringing sinusoid */
+                strike: 0.5 * mode.gain * hardness,
+                active: true,
+            };
+        }
+
+        for states in self.states.iter_mut() {
+            states.resize(self.resonators.len(), ResonatorState::default());
+        }
+    }
+}
+
+impl<S: Sample> Processor for ModalBank<S> {
+    type Input = S;
+    type Output = S;
+
+    fn reset(&mut self) {
+        for states in self.states.iter_mut() {
+            states.fill(ResonatorState::default());
+        }
+
+        self.velocity = 0.0;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update();
+    }
+
+    fn process(&mut self, input: Self::Input) -> Self::Output {
+        let resonators = &self.resonators;
+        let states = &mut self.states;
+        let mix = self.mix;
+
+        S::from_channels(| c | {
+            let x = input.channel(c).to_f32();
+            let wet: f32 = states[c].iter_mut()
+                .zip(resonators)
+                .filter(| (_, resonator) | resonator.active)
+                .map(| (state, resonator) | state.process(resonator, x, resonator.input))
+                .sum();
+
+            S::Float::from(x * (1.0 - mix) + wet * mix)
+        })
+    }
+}
+
+impl<S: Sample> Generator for ModalBank<S> {
+    type Output = S;
+
+    fn reset(&mut self) {
+        Processor::reset(self);
+    }
+
+    fn prepare(&mut self, sample_rate: u32, block_size: usize) {
+        Processor::prepare(self, sample_rate, block_size);
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let resonators = &self.resonators;
+        let states = &mut self.states;
+        let velocity = self.velocity;
+        self.velocity = 0.0;
+
+        S::from_channels(| c | {
+            let output: f32 = states[c].iter_mut()
+                .zip(resonators)
+                .filter(| (_, resonator) | resonator.active)
+                .map(| (state, resonator) | state.process(resonator, velocity, resonator.strike))
+                .sum();
+
+            S::Float::from(output)
+        })
+    }
+}
+
+impl<S: Sample> Pitched for ModalBank<S> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Frequency of modes with a ratio of 1
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = f32::max(hz, 0.0);
+        self.update();
+    }
+}
+
+impl<S: Sample> Playable for ModalBank<S> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.set_pitch(pitch);
+        self.strike(pressure);
+    }
+
+    /// Struck bodies ring on after the note ends
+    fn note_off(&mut self) {}
+}
+
+impl<S: Sample> Param for ModalBank<S> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "decay" => self.set_decay(value),
+            "hardness" => self.set_hardness(value),
+            "mix" => self.set_mix(value),
+            _ => ()
+        }
+    }
+}
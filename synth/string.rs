@@ -0,0 +1,621 @@
+use std::marker::PhantomData;
+
+use crate::buffers::*;
+use crate::float::*;
+use crate::math::random::*;
+use crate::routing::param::*;
+use crate::sample::*;
+use crate::traits::*;
+
+/* Lowest pitch the delay lines are allocated for in prepare, lower notes allocate on demand */
+const LOWEST_PITCH: f32 = 20.0;
+
+/* Allpass sections in the dispersion filter */
+const DISPERSION_STAGES: usize = 4;
+
+/* Level below which a string counts as silent after a full period */
+const SILENCE: f32 = 1e-5;
+
+/// Phase delay in samples at `w` radians per sample of a filter with frequency response `response`
+fn phase_delay(w: f32, response: (f32, f32)) -> f32 {
+    -f32::atan2(response.1, response.0) / w
+}
+
+/// Response of `(c + z^-1) / (1 + c z^-1)` at `w`
+fn allpass_response(c: f32, w: f32) -> (f32, f32) {
+    let (s, k) = (f32::sin(w), f32::cos(w));
+
+    /* (c + e^-jw) / (1 + c e^-jw) */
+    let (nr, ni) = (c + k, -s);
+    let (dr, di) = (1.0 + c * k, -c * s);
+    let d = dr * dr + di * di;
+
+    ((nr * dr + ni * di) / d, (ni * dr - nr * di) / d)
+}
+
+/// First order allpass `(c + z^-1) / (1 + c z^-1)`
+#[derive(Copy, Clone, Default)]
+struct Allpass {
+    c: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl Allpass {
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.c * x + self.x1 - self.c * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
+
+/// Signal that sets a plucked string in motion
+#[derive(Clone, PartialEq, Debug)]
+pub enum Excitation {
+    /// One period of seeded white noise
+    Noise,
+    /// A recorded pluck or strike, played into the string in full
+    Sample(Vec<f32>),
+}
+
+impl Excitation {
+    /// Mono mix of the playable region of a sample with its DC offset removed
+    pub fn from_sample<S: Sample>(sample: &SampleFile<S>) -> Self {
+        let end = usize::min(sample.end, sample.len());
+        let start = usize::min(sample.start, end);
+
+        let mut samples: Vec<f32> = sample.as_slice()[start..end]
+            .iter()
+            .map(| s | s.mono().to_f32())
+            .collect();
+
+        /* Any DC offset would ring on in the string */
+        let mean = samples.iter().sum::<f32>() / usize::max(samples.len(), 1) as f32;
+        for sample in samples.iter_mut() {
+            *sample -= mean;
+        }
+
+        Excitation::Sample(samples)
+    }
+}
+
+/// Plucked string using the extended Karplus-Strong algorithm
+///
+/// A delay line loops through a damping lowpass, a loss gain, a dispersion
+/// allpass chain for stiffness and a tuning allpass that supplies the
+/// fractional part of the period, so notes stay in tune at any pitch.
+/// The excitation is filtered by a comb for the pick position.
+pub struct PluckedString<F: Float> {
+    pitch: f32,
+    damping: f32,
+    decay: f32,
+    release: f32,
+    pick_position: f32,
+    stiffness: f32,
+    excitation: Excitation,
+    seed: u64,
+
+    sample_rate: u32,
+    delay: RingBuffer<f32>,
+    lowpass: f32,
+    last: f32,
+    gain: f32,
+    release_gain: f32,
+    dispersion: [Allpass; DISPERSION_STAGES],
+    tuning: Allpass,
+    random: Random,
+    burst: Vec<f32>,
+    position: usize,
+    amplitude: f32,
+    held: bool,
+    silent: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> PluckedString<F> {
+    pub fn new() -> Self {
+        let mut string = Self {
+            pitch: 110.0,
+            damping: 0.5,
+            decay: 4.0,
+            release: 0.15,
+            pick_position: 0.13,
+            stiffness: 0.0,
+            excitation: Excitation::Noise,
+            seed: 0,
+            sample_rate: 44100,
+            delay: RingBuffer::init(0.0, 1),
+            lowpass: 0.0,
+            last: 0.0,
+            gain: 1.0,
+            release_gain: 1.0,
+            dispersion: [Allpass::default(); DISPERSION_STAGES],
+            tuning: Allpass::default(),
+            random: Random::new(0),
+            burst: Vec::new(),
+            position: usize::MAX,
+            amplitude: 0.0,
+            held: false,
+            silent: usize::MAX,
+            _phantom: PhantomData,
+        };
+
+        string.update();
+        string
+    }
+
+    /// High frequency loss from 0 for a bright string to 1 for a dull one
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = f32::clamp(damping, 0.0, 1.0);
+        self.update();
+    }
+
+    /// Seconds for the fundamental to fall by 60 dB while the note is held
+    pub fn set_decay(&mut self, seconds: f32) {
+        self.decay = f32::max(seconds, 0.01);
+        self.update();
+    }
+
+    /// Seconds for the fundamental to fall by 60 dB after the note is released
+    pub fn set_release(&mut self, seconds: f32) {
+        self.release = f32::max(seconds, 0.01);
+        self.update();
+    }
+
+    /// Pick position along the string, from the bridge at 0 to the middle at 0.5
+    pub fn set_pick_position(&mut self, position: f32) {
+        self.pick_position = f32::clamp(position, 0.0, 0.5);
+    }
+
+    /// Inharmonicity from 0 for an ideal string to 1 for a stiff, piano-like one
+    pub fn set_stiffness(&mut self, stiffness: f32) {
+        self.stiffness = f32::clamp(stiffness, 0.0, 1.0);
+        self.update();
+    }
+
+    pub fn set_excitation(&mut self, excitation: Excitation) {
+        self.excitation = excitation;
+        self.position = usize::MAX;
+    }
+
+    /// Seed of the noise excitation
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.random = Random::new(seed);
+    }
+
+    /// The string is still sounding
+    pub fn active(&self) -> bool {
+        self.silent < self.delay.len()
+    }
+
+    /// Pluck the string with `velocity` from 0 to 1
+    pub fn pluck(&mut self, velocity: f32) {
+        self.amplitude = f32::clamp(velocity, 0.0, 1.0);
+        self.position = 0;
+        self.silent = 0;
+
+        if matches!(self.excitation, Excitation::Noise) {
+            let period = usize::max((self.sample_rate as f32 / self.pitch).round() as usize, 1);
+
+            /* Softer plucks are darker, like a pick or finger pressing less into the string */
+            let smoothing = 0.2 + 0.8 * self.amplitude;
+            let mut state = 0.0;
+
+            self.burst.clear();
+            for _ in 0..period {
+                state += (self.random.bipolar() as f32 - state) * smoothing;
+                self.burst.push(state);
+            }
+
+            /* Any DC would circulate forever once the loop gain reaches 1 on high notes */
+            let mean = self.burst.iter().sum::<f32>() / period as f32;
+            for sample in self.burst.iter_mut() {
+                *sample -= mean;
+            }
+        }
+    }
+
+    fn excitation(&self, index: usize) -> f32 {
+        let samples = match &self.excitation {
+            Excitation::Noise => &self.burst,
+            Excitation::Sample(samples) => samples,
+        };
+
+        samples.get(index).copied().unwrap_or(0.0)
+    }
+
+    fn excitation_length(&self) -> usize {
+        match &self.excitation {
+            Excitation::Noise => self.burst.len(),
+            Excitation::Sample(samples) => samples.len(),
+        }
+    }
+
+    /// Next excitation sample, with the comb notch of the pick position
+    fn next_excitation(&mut self) -> f32 {
+        let period = self.sample_rate as f32 / self.pitch;
+        let offset = (self.pick_position * period).round() as usize;
+
+        /* The comb runs past the end so its delayed copy cancels the excitation's DC */
+        if self.position >= self.excitation_length() + offset {
+            return 0.0;
+        }
+
+        let mut x = self.excitation(self.position);
+        if offset > 0 && self.position >= offset {
+            x -= self.excitation(self.position - offset);
+        }
+
+        self.position += 1;
+
+        /* The comb can double the peak level */
+        if offset > 0 {
+            x * self.amplitude * 0.5
+        } else {
+            x * self.amplitude
+        }
+    }
+
+    /// Recalculate the loop filters and delay length for the current settings
+    fn update(&mut self) {
+        let period = self.sample_rate as f32 / self.pitch;
+        let w = 2.0 * std::f32::consts::PI / period;
+
+        /* Damping filter (1 - b) + b z^-1 */
+        self.lowpass = 0.5 * self.damping;
+        let b = self.lowpass;
+        let damping = ((1.0 - b) + b * f32::cos(w), -b * f32::sin(w));
+        let magnitude = f32::sqrt(damping.0 * damping.0 + damping.1 * damping.1);
+
+        /* Dispersion is reduced on high notes until the loop is long enough to hold it */
+        let mut c = -0.6 * self.stiffness;
+        let mut other = phase_delay(w, damping) + DISPERSION_STAGES as f32 * phase_delay(w, allpass_response(c, w));
+        while period - other < 1.1 && c != 0.0 {
+            c = if c > -0.01 { 0.0 } else { c * 0.5 };
+            other = phase_delay(w, damping) + DISPERSION_STAGES as f32 * phase_delay(w, allpass_response(c, w));
+        }
+
+        for stage in self.dispersion.iter_mut() {
+            stage.c = c;
+        }
+
+        /* The integer delay leaves between 0.1 and 1.1 samples for the tuning allpass, where it's best behaved */
+        let remaining = f32::max(period - other, 1.1);
+        let length = f32::max((remaining - 0.1).floor(), 1.0);
+        let fraction = remaining - length;
+
+        self.delay.resize(length as usize);
+        self.tuning.c = f32::sin(w * (1.0 - fraction) / 2.0) / f32::sin(w * (1.0 + fraction) / 2.0);
+
+        /* Loss per period for the decay time, with the damping filter's loss at the fundamental taken out.
+         * The gain stays below 1 so rounding errors at DC, where the damping filter has no loss, die away. */
+        let loss = |seconds: f32| f32::powf(10.0, -3.0 / (self.pitch * seconds));
+        self.gain = f32::min(loss(self.decay) / magnitude, 0.9999);
+        self.release_gain = f32::min(loss(self.release) / magnitude, 0.9999);
+    }
+}
+
+impl<F: Float> Default for PluckedString<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for PluckedString<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.delay.clear();
+        self.last = 0.0;
+        self.tuning.reset();
+        for stage in self.dispersion.iter_mut() {
+            stage.reset();
+        }
+
+        self.random = Random::new(self.seed);
+        self.position = usize::MAX;
+        self.held = false;
+        self.silent = usize::MAX;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.delay = RingBuffer::init(0.0, (sample_rate as f32 / LOWEST_PITCH) as usize);
+        self.burst = Vec::with_capacity(self.delay.capacity());
+        self.update();
+        self.reset();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let delayed = self.delay.get(self.delay.len());
+
+        let b = self.lowpass;
+        let mut y = (1.0 - b) * delayed + b * self.last;
+        self.last = delayed;
+
+        y *= if self.held { self.gain } else { self.release_gain };
+
+        for stage in self.dispersion.iter_mut() {
+            y = stage.process(y);
+        }
+
+        let output = self.tuning.process(y) + self.next_excitation();
+
+        self.delay.push(output);
+
+        if output.abs() > SILENCE {
+            self.silent = 0;
+        } else {
+            self.silent = self.silent.saturating_add(1);
+        }
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for PluckedString<F> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = f32::clamp(hz, 1.0, self.sample_rate as f32 / 4.0);
+        self.update();
+    }
+}
+
+impl<F: Float> Playable for PluckedString<F> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.held = true;
+        self.set_pitch(pitch);
+        self.pluck(pressure);
+    }
+
+    fn note_off(&mut self) {
+        self.held = false;
+    }
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+}
+
+impl<F: Float> Param for PluckedString<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "damping" => self.set_damping(value),
+            "decay" => self.set_decay(value),
+            "release" => self.set_release(value),
+            "pick" => self.set_pick_position(value),
+            "stiffness" => self.set_stiffness(value),
+            _ => ()
+        }
+    }
+}
+
+/// Bowed string using a two delay line waveguide with a bow friction table
+///
+/// The bow splits the string into a neck and a bridge side. Each sample the
+/// difference between bow and string velocity passes through the friction
+/// table, so the string sticks to the bow and slips free in a sawtooth-like
+/// Helmholtz motion. Note on starts the bow, note off lifts it. As on a real
+/// string, a fast bow close to the bridge with too little force locks onto a
+/// harmonic instead of the fundamental.
+pub struct BowedString<F: Float> {
+    pitch: f32,
+    force: f32,
+    bow_position: f32,
+    attack: f32,
+    release: f32,
+
+    sample_rate: u32,
+    neck: RingBuffer<f32>,
+    bridge: RingBuffer<f32>,
+    neck_delay: f32,
+    bridge_delay: f32,
+    filter_pole: f32,
+    filter_state: f32,
+    velocity: f32,
+    target: f32,
+    held: bool,
+    silent: usize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Float> BowedString<F> {
+    pub fn new() -> Self {
+        let mut string = Self {
+            pitch: 220.0,
+            force: 0.75,
+            bow_position: 0.127,
+            attack: 0.05,
+            release: 0.1,
+            sample_rate: 44100,
+            neck: RingBuffer::init(0.0, 1),
+            bridge: RingBuffer::init(0.0, 1),
+            neck_delay: 1.0,
+            bridge_delay: 1.0,
+            filter_pole: 0.0,
+            filter_state: 0.0,
+            velocity: 0.0,
+            target: 0.0,
+            held: false,
+            silent: usize::MAX,
+            _phantom: PhantomData,
+        };
+
+        string.prepare(44100, 1);
+        string
+    }
+
+    /// Bow force from 0 for a light, airy tone to 1 for a heavy, scratchy one
+    pub fn set_force(&mut self, force: f32) {
+        self.force = f32::clamp(force, 0.0, 1.0);
+    }
+
+    /// Bow position along the string from the bridge at 0 to the middle at 0.5
+    pub fn set_bow_position(&mut self, position: f32) {
+        self.bow_position = f32::clamp(position, 0.01, 0.5);
+        self.update();
+    }
+
+    /// Seconds for the bow to reach full speed after note on
+    pub fn set_attack(&mut self, seconds: f32) {
+        self.attack = f32::max(seconds, 0.001);
+    }
+
+    /// Seconds for the bow to stop after note off
+    pub fn set_release(&mut self, seconds: f32) {
+        self.release = f32::max(seconds, 0.001);
+    }
+
+    /// The bow is moving or the string is still ringing
+    pub fn active(&self) -> bool {
+        self.velocity > 0.0 || self.silent < self.sample_rate as usize / 10
+    }
+
+    /// Reflection of the bow for a velocity difference between bow and string
+    fn bow_table(&self, delta: f32) -> f32 {
+        let slope = 5.0 - 4.0 * self.force;
+        let x = f32::abs(delta * slope + 0.001) + 0.75;
+        f32::clamp(f32::powi(x, -4), 0.01, 0.98)
+    }
+
+    fn update(&mut self) {
+        let period = self.sample_rate as f32 / self.pitch;
+        let w = 2.0 * std::f32::consts::PI / period;
+
+        /* The string filter's phase delay at the fundamental is part of the loop */
+        let p = self.filter_pole;
+        let filter = f32::atan2(p * f32::sin(w), 1.0 - p * f32::cos(w)) / w;
+        let length = f32::max(period - filter, 2.0);
+
+        self.bridge_delay = f32::max(length * self.bow_position, 1.0);
+        self.neck_delay = f32::max(length - self.bridge_delay, 1.0);
+    }
+}
+
+impl<F: Float> Default for BowedString<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Generator for BowedString<F> {
+    type Output = F;
+
+    fn reset(&mut self) {
+        self.neck.clear();
+        self.bridge.clear();
+        self.filter_state = 0.0;
+        self.velocity = 0.0;
+        self.target = 0.0;
+        self.held = false;
+        self.silent = usize::MAX;
+    }
+
+    fn prepare(&mut self, sample_rate: u32, _block_size: usize) {
+        let capacity = (sample_rate as f32 / LOWEST_PITCH) as usize;
+
+        self.sample_rate = sample_rate;
+        self.neck = RingBuffer::init(0.0, capacity);
+        self.neck.resize(capacity);
+        self.bridge = RingBuffer::init(0.0, capacity);
+        self.bridge.resize(capacity);
+        self.filter_pole = 0.75 - 0.2 * 22050.0 / sample_rate as f32;
+        self.update();
+        self.reset();
+    }
+
+    fn generate(&mut self) -> Self::Output {
+        let rate = if self.held {
+            0.2 / (self.attack * self.sample_rate as f32)
+        } else {
+            0.2 / (self.release * self.sample_rate as f32)
+        };
+
+        if self.velocity < self.target {
+            self.velocity = f32::min(self.velocity + rate, self.target);
+        } else {
+            self.velocity = f32::max(self.velocity - rate, self.target);
+        }
+
+        let bridge_out = self.bridge.read(self.bridge_delay);
+        let neck_out = self.neck.read(self.neck_delay);
+
+        /* Lossy, slightly lowpassed reflection at the bridge and a rigid one at the nut */
+        self.filter_state = 0.95 * (1.0 - self.filter_pole) * bridge_out + self.filter_pole * self.filter_state;
+        let bridge_reflection = -self.filter_state;
+        let nut_reflection = -neck_out;
+
+        let delta = self.velocity - (bridge_reflection + nut_reflection);
+        let bow = if self.velocity > 0.0 { delta * self.bow_table(delta) } else { 0.0 };
+
+        self.neck.push(bridge_reflection + bow);
+        self.bridge.push(nut_reflection + bow);
+
+        let output = bridge_out;
+        if output.abs() > SILENCE {
+            self.silent = 0;
+        } else {
+            self.silent = self.silent.saturating_add(1);
+        }
+
+        F::from(output)
+    }
+}
+
+impl<F: Float> Pitched for BowedString<F> {
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    fn set_pitch(&mut self, hz: f32) {
+        self.pitch = f32::clamp(hz, LOWEST_PITCH, self.sample_rate as f32 / 8.0);
+        self.update();
+    }
+}
+
+impl<F: Float> Playable for BowedString<F> {
+    fn note_on(&mut self, pitch: f32, pressure: f32) {
+        self.held = true;
+        self.silent = 0;
+        self.set_pitch(pitch);
+        self.set_pressure(pressure);
+    }
+
+    fn note_off(&mut self) {
+        self.held = false;
+        self.target = 0.0;
+    }
+
+    fn bend(&mut self, pitch: f32) {
+        self.set_pitch(pitch);
+    }
+
+    /// Bow speed follows the note pressure
+    fn set_pressure(&mut self, pressure: f32) {
+        if self.held {
+            self.target = 0.02 + 0.12 * f32::clamp(pressure, 0.0, 1.0);
+        }
+    }
+}
+
+impl<F: Float> Param for BowedString<F> {
+    fn set_param(&mut self, name: &'static str, value: f32) {
+        match name {
+            "force" => self.set_force(value),
+            "position" => self.set_bow_position(value),
+            "attack" => self.set_attack(value),
+            "release" => self.set_release(value),
+            _ => ()
+        }
+    }
+}
@@ -1,9 +1,13 @@
 pub mod basic;
 pub mod pitched;
+pub mod phased;
+pub mod playable;
 pub mod loadable;
 mod switcher;
 
 pub use basic::*;
 pub use pitched::*;
+pub use phased::*;
+pub use playable::*;
 pub use loadable::*;
 pub use switcher::*;
\ No newline at end of file
@@ -0,0 +1,5 @@
+pub trait Phased {
+    /// Position within the current cycle, from 0 to 1
+    fn get_phase(&self) -> f32;
+    fn set_phase(&mut self, phase: f32);
+}
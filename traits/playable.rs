@@ -0,0 +1,62 @@
+use crate::buffers::*;
+use crate::event::*;
+use crate::traits::basic::*;
+
+/// A voice started and stopped by note events
+pub trait Playable {
+    /// Start a note at `pitch` in Hz with `pressure` from 0 to 1
+    fn note_on(&mut self, pitch: f32, pressure: f32);
+    fn note_off(&mut self);
+
+    /// Pitch change of the playing note
+    fn bend(&mut self, _pitch: f32) {}
+
+    /// Pressure change of the playing note
+    fn set_pressure(&mut self, _pressure: f32) {}
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::NoteOn { pitch, pressure } => self.note_on(pitch, pressure),
+            Event::NoteOff => self.note_off(),
+            Event::Pitch(pitch) => self.bend(pitch),
+            Event::Pressure(pressure) => self.set_pressure(pressure),
+            Event::Other(_, _) => ()
+        }
+    }
+}
+
+pub trait PlayableGenerator {
+    type Output;
+
+    /// Generate a block, applying each event at its sample offset
+    ///
+    /// Events must be sorted by offset. Events past the end of the block are applied after it.
+    fn play_block<OutBuffer, Events>(&mut self, events: &Events, output: &mut OutBuffer)
+        where
+            OutBuffer: Block<Item = Self::Output> + ?Sized,
+            Events: Block<Item = NoteMessage> + ?Sized;
+}
+
+impl<Out, G: Generator<Output = Out> + Playable> PlayableGenerator for G {
+    type Output = Out;
+
+    fn play_block<OutBuffer, Events>(&mut self, events: &Events, output: &mut OutBuffer)
+        where
+            OutBuffer: Block<Item = Self::Output> + ?Sized,
+            Events: Block<Item = NoteMessage> + ?Sized {
+
+        let mut events = events.as_slice().iter().peekable();
+
+        for (i, dest) in output.as_slice_mut().iter_mut().enumerate() {
+            while let Some(message) = events.next_if(| message | message.offset <= i) {
+                self.event(message.note);
+            }
+
+            *dest = self.generate();
+        }
+
+        for message in events {
+            self.event(message.note);
+        }
+    }
+}